@@ -1,20 +1,117 @@
-use clap::{Args, Parser, Subcommand};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use atrium_api::types::string::{Datetime, Did, Handle as HandleStr};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
 use zeroize::ZeroizeOnDrop;
 
+use crate::keystore::{KeyAlgorithm, KeyFormat};
+use crate::remote::plc::V0_1_RECOVERY_WINDOW_HOURS;
+
 #[derive(Debug, Parser)]
 pub(crate) struct Options {
+    /// Disable colored output.
+    #[arg(long, global = true)]
+    pub(crate) no_color: bool,
+
+    /// How to display timestamps. Defaults to `utc`.
+    #[arg(long, global = true, value_enum)]
+    pub(crate) time: Option<TimeFormat>,
+
+    /// Never prompt for input, read secrets only from the environment or files, and
+    /// report errors on stderr as JSON.
+    #[arg(long, global = true)]
+    pub(crate) non_interactive: bool,
+
+    /// Fail if any warnings are raised, e.g. in CI.
+    #[arg(long, global = true)]
+    pub(crate) deny_warnings: bool,
+
+    /// The PLC directory to query, e.g. a mirror of plc.directory. Defaults to
+    /// https://plc.directory.
+    #[arg(long, value_name = "URL", global = true)]
+    pub(crate) plc_directory: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) dns: DnsOptions,
+
     #[command(subcommand)]
     pub(crate) command: Command,
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TimeFormat {
+    /// RFC 3339 timestamps in UTC.
+    #[default]
+    Utc,
+    /// Timestamps in the local timezone.
+    Local,
+    /// Timestamps relative to now (e.g. "2 hours ago").
+    Relative,
+}
+
+/// Options for the DNS resolver used for handle resolution.
+#[derive(Debug, Args)]
+pub(crate) struct DnsOptions {
+    /// A DNS server to query for handle resolution, as an IP address with optional
+    /// port (may be given multiple times). Defaults to Google Public DNS.
+    #[arg(long = "dns-server", value_name = "ADDR", value_parser = parse_dns_server, global = true)]
+    pub(crate) servers: Vec<SocketAddr>,
+
+    /// Use the system's DNS resolver configuration instead of Google Public DNS.
+    #[arg(long = "dns-system", conflicts_with = "servers", global = true)]
+    pub(crate) system: bool,
+
+    /// Timeout in seconds for each DNS query.
+    #[arg(long = "dns-timeout", value_name = "SECONDS", global = true)]
+    pub(crate) timeout: Option<u64>,
+}
+
+fn parse_dns_server(s: &str) -> Result<SocketAddr, String> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid DNS server address: {s}"))
+}
+
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
     #[command(subcommand)]
     Auth(Auth),
+    Compare(Compare),
+    #[command(subcommand)]
+    Config(ConfigCmd),
+    #[command(subcommand)]
+    Did(DidCmd),
+    #[command(subcommand)]
+    Directory(Directory),
+    Explain(Explain),
+    #[command(subcommand)]
+    Fleet(Fleet),
+    #[command(subcommand)]
+    Handle(Handle),
+    #[command(subcommand)]
+    Hooks(Hooks),
+    #[command(subcommand)]
+    Incident(Incident),
+    #[command(subcommand)]
+    Journal(Journal),
     #[command(subcommand)]
     Keys(Keys),
     #[command(subcommand)]
     Ops(Ops),
+    #[command(subcommand)]
+    Pds(Pds),
+    Pin(Pin),
+    #[command(subcommand)]
+    Policy(Policy),
+    #[command(subcommand)]
+    Profile(Profile),
+    SelfTest(SelfTest),
+    #[cfg(feature = "server")]
+    ServeResolver(ServeResolver),
+    VerifyDid(VerifyDid),
 }
 
 /// Manage authentication
@@ -24,16 +121,505 @@ pub(crate) enum Auth {
 }
 
 /// Log in a user
+///
+/// If no app password is given, it is read from the `PLC_APP_PASSWORD` environment
+/// variable, or prompted for.
 #[derive(Debug, Args, ZeroizeOnDrop)]
 pub(crate) struct Login {
     pub(crate) user: String,
-    pub(crate) app_password: String,
+    pub(crate) app_password: Option<String>,
+
+    /// Read the app password from this file.
+    #[arg(long, value_name = "PATH", conflicts_with = "app_password")]
+    #[zeroize(skip)]
+    pub(crate) app_password_file: Option<PathBuf>,
+}
+
+/// View or change the defaults in `config.toml`.
+#[derive(Debug, Subcommand)]
+pub(crate) enum ConfigCmd {
+    Show(ShowConfig),
+    Set(SetConfig),
+    Unset(UnsetConfig),
+}
+
+/// A default that can be set in `config.toml`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum ConfigDefault {
+    /// The PLC directory to query.
+    PlcDirectory,
+    /// How to display timestamps: `utc`, `local`, or `relative`.
+    Time,
+    /// The rotation key in the keystore to sign updates with, as a `did:key`.
+    Key,
+}
+
+/// Prints the path to the config file and the defaults it sets.
+#[derive(Debug, Args)]
+pub(crate) struct ShowConfig {}
+
+/// Sets a default, so that it no longer needs to be passed to every command.
+#[derive(Debug, Args)]
+pub(crate) struct SetConfig {
+    #[arg(value_enum)]
+    pub(crate) name: ConfigDefault,
+    pub(crate) value: String,
+}
+
+/// Removes a default.
+#[derive(Debug, Args)]
+pub(crate) struct UnsetConfig {
+    #[arg(value_enum)]
+    pub(crate) name: ConfigDefault,
+}
+
+/// Work with DIDs.
+#[derive(Debug, Subcommand)]
+pub(crate) enum DidCmd {
+    Preview(PreviewDid),
+    Grind(GrindDid),
+}
+
+/// Shows the DID that a prepared genesis operation would create.
+#[derive(Debug, Args)]
+pub(crate) struct PreviewDid {
+    /// Path to a JSON file containing the signed genesis operation, or `-` for stdin.
+    pub(crate) operation: PathBuf,
+
+    /// The operation is encoded as DAG-CBOR instead of JSON. Non-canonical encodings
+    /// are rejected.
+    #[arg(long)]
+    pub(crate) cbor: bool,
+}
+
+/// Searches for a genesis operation whose DID starts with a chosen prefix.
+///
+/// This is for test and demo identities only. Each attempt signs the genesis operation
+/// with a newly generated rotation key, so a prefix of n characters takes around 32^n
+/// attempts; the winning key is saved to the keystore.
+#[derive(Debug, Args)]
+pub(crate) struct GrindDid {
+    /// The prefix to search for, after `did:plc:` (up to 5 characters from a-z and
+    /// 2-7).
+    #[arg(long)]
+    pub(crate) prefix: String,
+
+    /// Give up after this many attempts.
+    #[arg(long, default_value_t = 1_000_000)]
+    pub(crate) max_attempts: u64,
+
+    /// The algorithm for the generated rotation key.
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) algorithm: KeyAlgorithm,
+
+    /// The primary handle for the new DID.
+    #[arg(long)]
+    pub(crate) handle: Option<HandleStr>,
+
+    /// The PDS endpoint for the new DID.
+    #[arg(long, value_name = "URL")]
+    pub(crate) pds: Option<String>,
+
+    /// The ATProto signing key for the new DID, as a `did:key`.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) signing_key: Option<String>,
+
+    /// Write the signed genesis operation to this file instead of stdout.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Submit the genesis operation to the directory, creating the DID.
+    #[arg(long)]
+    pub(crate) submit: bool,
+}
+
+/// Work with the PLC directory as a whole.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Directory {
+    Export(ExportDirectory),
+    ExportSubset(ExportSubset),
+    Audit(AuditDirectory),
+    CheckHistory(CheckHistory),
+    Status(DirectoryStatus),
+}
+
+/// Writes operations from the directory's export to stdout, as JSON lines.
+#[derive(Debug, Args)]
+pub(crate) struct ExportDirectory {
+    /// Only export operations created after this time (RFC 3339).
+    #[arg(long, value_parser = parse_datetime)]
+    pub(crate) after: Option<Datetime>,
+
+    /// Stop after exporting this many operations.
+    #[arg(long)]
+    pub(crate) limit: Option<usize>,
+}
+
+/// Writes the complete audit logs of a list of DIDs, in the same format as `export`.
+///
+/// Each DID's log is validated first, so that the subset can be audited on its own:
+/// for example, to share an investigation dataset or seed a test environment.
+#[derive(Debug, Args)]
+pub(crate) struct ExportSubset {
+    /// A file listing one DID per line. Blank lines and lines starting with `#` are
+    /// ignored.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) dids: PathBuf,
+
+    /// Only export operations created after this time (RFC 3339).
+    ///
+    /// The earlier operations are still validated, but the subset no longer starts
+    /// with each DID's genesis operation.
+    #[arg(long, value_parser = parse_datetime)]
+    pub(crate) after: Option<Datetime>,
+
+    /// Only export operations created before this time (RFC 3339).
+    #[arg(long, value_parser = parse_datetime)]
+    pub(crate) before: Option<Datetime>,
+
+    /// Write the subset to this file instead of stdout.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) out: Option<PathBuf>,
+}
+
+/// Audits every DID with operations in a window of the directory's export, without
+/// needing a local copy of the directory.
+///
+/// DIDs whose history in the window is incomplete or fails validation are re-checked
+/// against their full audit log.
+#[derive(Debug, Args)]
+pub(crate) struct AuditDirectory {
+    /// Only audit operations created after this time (RFC 3339).
+    #[arg(long, value_parser = parse_datetime)]
+    pub(crate) after: Option<Datetime>,
+
+    /// The number of operations to read from the export.
+    #[arg(long, default_value_t = 10_000)]
+    pub(crate) limit: usize,
+
+    /// The number of operations to hold in memory while grouping them by DID. A DID
+    /// whose operations are further apart than this in the export is audited once for
+    /// each part.
+    #[arg(long, default_value_t = 10_000)]
+    pub(crate) buffer: usize,
+}
+
+/// Checks that the directory still serves the history it served before, for a random
+/// sample of the DIDs whose audit logs are cached locally.
+///
+/// Run this periodically (e.g. from cron) to detect a directory rewriting or removing
+/// operations. Evidence of any change is saved in the data directory.
+#[derive(Debug, Args)]
+pub(crate) struct CheckHistory {
+    /// The number of cached DIDs to re-fetch.
+    #[arg(long, default_value_t = 20)]
+    pub(crate) sample: usize,
+}
+
+/// Probes the directory's health: request latency, export freshness, TLS certificate
+/// expiry, and rate limits.
+///
+/// Exits with an error if any request fails, so this can be used for monitoring.
+#[derive(Debug, Args)]
+pub(crate) struct DirectoryStatus {
+    /// Also probe this mirror. May be given multiple times.
+    #[arg(long = "mirror", value_name = "URL")]
+    pub(crate) mirrors: Vec<String>,
+
+    /// The DID to resolve and fetch the audit log of.
+    #[arg(long, default_value = "did:plc:z72i7hdynmk6r22z27h6tvur")]
+    pub(crate) did: Did,
+
+    /// How long to wait for each request, in seconds, before recording it as failed.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub(crate) timeout: u64,
+
+    /// Print the results as JSON.
+    #[arg(long)]
+    pub(crate) json: bool,
+}
+
+fn parse_datetime(s: &str) -> Result<Datetime, String> {
+    s.parse().map_err(|e| format!("invalid datetime: {e}"))
+}
+
+/// Manage handles.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Handle {
+    #[cfg(feature = "server")]
+    ServeWellKnown(ServeWellKnown),
+    Prove(ProveHandle),
+    Set(SetHandle),
+}
+
+/// Serves `/.well-known/atproto-did` for a DID, for handle verification.
+#[cfg(feature = "server")]
+#[derive(Debug, Args)]
+pub(crate) struct ServeWellKnown {
+    pub(crate) did: Did,
+
+    /// The address to listen on, e.g. `:8080` or `127.0.0.1:8080`.
+    #[arg(long, default_value = ":8080", value_parser = parse_listen_addr)]
+    pub(crate) listen: SocketAddr,
+
+    /// Also print the DNS TXT record that would verify this handle.
+    #[arg(long)]
+    pub(crate) handle: Option<HandleStr>,
+}
+
+/// Serves a read-only, caching DID resolver backed by a PLC directory.
+///
+/// This serves `/:did` and `/:did/data` like plc.directory, for apps that need a
+/// resolver without running a full mirror.
+#[cfg(feature = "server")]
+#[derive(Debug, Args)]
+pub(crate) struct ServeResolver {
+    /// The address to listen on, e.g. `:8080` or `127.0.0.1:8080`.
+    #[arg(long, default_value = ":8080", value_parser = parse_listen_addr)]
+    pub(crate) listen: SocketAddr,
+
+    /// The directory to resolve DIDs with. Defaults to `--plc-directory`.
+    #[arg(long, value_name = "URL")]
+    pub(crate) upstream: Option<String>,
+
+    /// How long to cache each response, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    pub(crate) cache_ttl: u64,
+
+    /// The number of requests each client IP may make per minute.
+    #[arg(long, value_name = "REQUESTS", default_value_t = 600)]
+    pub(crate) rate_limit: u32,
+}
+
+/// Shows how to prove that a DID controls its handle, then waits until the proof
+/// can be verified.
+#[derive(Debug, Args)]
+pub(crate) struct ProveHandle {
+    pub(crate) did: Did,
+
+    /// The handle to prove. Defaults to the DID's current handle.
+    #[arg(long)]
+    pub(crate) handle: Option<HandleStr>,
+
+    /// How often to check for the proof, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub(crate) interval: u64,
+
+    /// How long to wait for the proof before giving up, in seconds. Pass 0 to check
+    /// once without waiting.
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    pub(crate) timeout: u64,
+}
+
+/// Changes a user's primary handle, via an operation signed by their PDS or by a
+/// rotation key from the local keystore, then checks that the handle resolves back to
+/// the DID.
+#[derive(Debug, Args)]
+pub(crate) struct SetHandle {
+    pub(crate) user: String,
+
+    /// The new primary handle.
+    pub(crate) handle: HandleStr,
+
+    /// Sign with this rotation key from the keystore, as a `did:key`, instead of
+    /// asking the PDS.
+    #[arg(long, value_parser = parse_did_key, conflicts_with = "token")]
+    pub(crate) key: Option<String>,
+
+    /// The token the PDS emailed to the account. Arguments are visible to other
+    /// processes, so with `--non-interactive` set `PLC_EMAIL_TOKEN` instead.
+    #[arg(long)]
+    pub(crate) token: Option<String>,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+#[cfg(feature = "server")]
+fn parse_listen_addr(s: &str) -> Result<SocketAddr, String> {
+    match s.strip_prefix(':') {
+        Some(port) => port
+            .parse()
+            .map(|port| SocketAddr::new(IpAddr::from([0, 0, 0, 0]), port))
+            .ok(),
+        None => s.parse().ok(),
+    }
+    .ok_or_else(|| format!("invalid listen address: {s}"))
+}
+
+/// Manage the hooks configured in the `[hooks]` table of `config.toml`.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Hooks {
+    Test(TestHooks),
+}
+
+/// Runs the pre-sign hooks against a signed operation, without signing or submitting
+/// anything.
+#[derive(Debug, Args)]
+pub(crate) struct TestHooks {
+    /// Path to a JSON file containing the signed operation, or `-` for stdin.
+    pub(crate) operation: PathBuf,
+
+    /// The operation is encoded as DAG-CBOR instead of JSON.
+    #[arg(long)]
+    pub(crate) cbor: bool,
+
+    /// The DID the operation updates, passed to hooks as `PLC_DID`. Not needed for
+    /// genesis operations.
+    #[arg(long)]
+    pub(crate) did: Option<Did>,
+}
+
+/// Respond to a compromised identity.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Incident {
+    Start(StartIncident),
+}
+
+/// Walks through responding to a compromised identity: auditing its log, nullifying
+/// the operations a compromised rotation key signed, removing that key, and reporting
+/// a timeline.
+///
+/// Progress is saved after each step; run the command again to resume.
+#[derive(Debug, Args)]
+pub(crate) struct StartIncident {
+    pub(crate) user: String,
+
+    /// The rotation key to respond with, as a `did:key`. Defaults to the
+    /// highest-priority key in the keystore that can nullify the earliest operation.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) key: Option<String>,
+
+    /// The earliest malicious operation. Defaults to the earliest operation that can be
+    /// nullified and was not signed by a key held by you or your PDS.
+    #[arg(long, value_name = "CID")]
+    pub(crate) cid: Option<String>,
+
+    /// Take each step without asking.
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// Write the timeline to this file instead of stdout.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) report: Option<PathBuf>,
+
+    /// The CID of the operation being submitted, if the confirmation policy requires
+    /// it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Inspect the local journal of operations this tool has signed or submitted.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Journal {
+    List(ListJournal),
+    Show(ShowJournal),
+    Export(ExportJournal),
+}
+
+/// Lists journal entries, oldest first.
+#[derive(Debug, Args)]
+pub(crate) struct ListJournal {
+    /// Only list entries for this DID.
+    #[arg(long)]
+    pub(crate) did: Option<Did>,
+}
+
+/// Shows a journal entry in full, including the operation.
+#[derive(Debug, Args)]
+pub(crate) struct ShowJournal {
+    /// The entry's number, as shown by `journal list`.
+    pub(crate) index: usize,
+}
+
+/// Exports the journal as JSON lines.
+#[derive(Debug, Args)]
+pub(crate) struct ExportJournal {
+    /// Write to this path instead of stdout.
+    #[arg(long)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+/// Inspect the confirmation policy for dangerous operations.
+///
+/// The policy is configured in the `[policy]` table of `config.toml`.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Policy {
+    Check(CheckPolicy),
+}
+
+/// Shows what the policy requires before a signed operation can be submitted.
+#[derive(Debug, Args)]
+pub(crate) struct CheckPolicy {
+    /// Path to a JSON file containing the signed operation, or `-` for stdin.
+    pub(crate) operation: PathBuf,
+
+    /// The operation is encoded as DAG-CBOR instead of JSON.
+    #[arg(long)]
+    pub(crate) cbor: bool,
+
+    /// The handle or DID the operation updates. Without this, an update is assumed to
+    /// change the rotation keys.
+    #[arg(long)]
+    pub(crate) user: Option<String>,
+}
+
+/// Move local state (config, pins, key labels, journal, fleet) between machines.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Profile {
+    Export(ExportProfile),
+    Import(ImportProfile),
+}
+
+/// Bundles local state into a portable profile.
+#[derive(Debug, Args)]
+pub(crate) struct ExportProfile {
+    /// Write to this path instead of stdout.
+    #[arg(long)]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Also include the PDS session and the keystore. The profile is then encrypted
+    /// with a passphrase, read from `PLC_PROFILE_PASSPHRASE` or prompted for.
+    #[arg(long)]
+    pub(crate) include_secrets: bool,
+
+    /// The fleet configuration to include, if it exists.
+    #[arg(long, default_value = "fleet.toml")]
+    pub(crate) fleet: PathBuf,
+}
+
+/// Restores local state from a profile.
+#[derive(Debug, Args)]
+pub(crate) struct ImportProfile {
+    /// Path to the profile, or `-` for stdin.
+    pub(crate) input: PathBuf,
+
+    /// Replace existing files instead of refusing to import.
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// Import the profile's hooks without asking. Hooks run arbitrary commands, so
+    /// only pass this for profiles you trust.
+    #[arg(long)]
+    pub(crate) allow_hooks: bool,
+
+    /// Where to write the fleet configuration, if the profile has one.
+    #[arg(long, default_value = "fleet.toml")]
+    pub(crate) fleet: PathBuf,
 }
 
 /// Manage keys for a DID.
 #[derive(Debug, Subcommand)]
 pub(crate) enum Keys {
     List(ListKeys),
+    AddRotation(AddRotationKey),
+    Generate(GenerateKey),
+    Import(ImportKey),
+    Export(ExportKey),
+    RemoveRotation(RemoveRotationKey),
+    #[command(subcommand)]
+    Known(KnownKeys),
 }
 
 /// Lists keys for a user
@@ -42,11 +628,173 @@ pub(crate) struct ListKeys {
     pub(crate) user: String,
 }
 
+/// Adds a rotation key to a user's DID, via an operation signed by their PDS.
+///
+/// The PDS emails the account a token to authorize signing, which is read from
+/// `--token`, `PLC_EMAIL_TOKEN`, or prompted for.
+#[derive(Debug, Args)]
+pub(crate) struct AddRotationKey {
+    pub(crate) user: String,
+
+    /// The key to add, as a `did:key`.
+    #[arg(value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    /// Where to insert the key in the priority order. Keys earlier in the list can
+    /// override operations signed by later keys.
+    #[arg(long, default_value_t = 0)]
+    pub(crate) index: usize,
+
+    /// Mark the key as temporary, to be removed at this time. PLC has no native
+    /// expiry, so `plc` reminds you to remove the key once it has expired.
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime)]
+    pub(crate) expires: Option<Datetime>,
+
+    /// The token the PDS emailed to the account. Arguments are visible to other
+    /// processes, so with `--non-interactive` set `PLC_EMAIL_TOKEN` instead.
+    #[arg(long)]
+    pub(crate) token: Option<String>,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Generates a new keypair, stored encrypted in the local keystore, for use as a
+/// rotation key.
+///
+/// The private key is encrypted with a passphrase, read from `PLC_KEY_PASSPHRASE` or
+/// prompted for.
+#[derive(Debug, Args)]
+pub(crate) struct GenerateKey {
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) algorithm: KeyAlgorithm,
+
+    /// A label for the key, shown wherever the key appears.
+    #[arg(long)]
+    pub(crate) label: Option<String>,
+}
+
+/// Imports an existing private key into the local keystore.
+///
+/// The key can be hex-encoded, a multibase string (as exported by `goat`), or a PEM
+/// file. It is encrypted with a passphrase, read from `PLC_KEY_PASSPHRASE` or
+/// prompted for.
+#[derive(Debug, Args)]
+pub(crate) struct ImportKey {
+    /// Path to a file containing the private key, or `-` for stdin.
+    pub(crate) input: PathBuf,
+
+    /// The key's curve. Required for hex-encoded keys; other formats say.
+    #[arg(long, value_enum)]
+    pub(crate) algorithm: Option<KeyAlgorithm>,
+
+    /// A label for the key, shown wherever the key appears.
+    #[arg(long)]
+    pub(crate) label: Option<String>,
+}
+
+/// Exports a private key from the local keystore, unencrypted.
+#[derive(Debug, Args)]
+pub(crate) struct ExportKey {
+    /// The key, as a `did:key`.
+    #[arg(value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) format: KeyFormat,
+
+    /// Write to this path instead of stdout.
+    #[arg(long)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+/// Removes a rotation key from a user's DID, via an operation signed by their PDS.
+#[derive(Debug, Args)]
+pub(crate) struct RemoveRotationKey {
+    pub(crate) user: String,
+
+    /// The key to remove, as its index in `keys list` or a `did:key`.
+    #[arg(value_parser = parse_rotation_key)]
+    pub(crate) key: RotationKeyRef,
+
+    /// Remove the key without asking, even if it is the last one not held by the PDS.
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// The token the PDS emailed to the account. Arguments are visible to other
+    /// processes, so with `--non-interactive` set `PLC_EMAIL_TOKEN` instead.
+    #[arg(long)]
+    pub(crate) token: Option<String>,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum RotationKeyRef {
+    Index(usize),
+    Key(String),
+}
+
+fn parse_rotation_key(s: &str) -> Result<RotationKeyRef, String> {
+    match s.parse() {
+        Ok(index) => Ok(RotationKeyRef::Index(index)),
+        Err(_) => parse_did_key(s).map(RotationKeyRef::Key),
+    }
+}
+
+/// Manage labels for well-known keys.
+#[derive(Debug, Subcommand)]
+pub(crate) enum KnownKeys {
+    List(ListKnownKeys),
+    Add(AddKnownKey),
+    Remove(RemoveKnownKey),
+}
+
+/// Lists the built-in and user-defined key labels.
+#[derive(Debug, Args)]
+pub(crate) struct ListKnownKeys {}
+
+/// Labels a key, so it is shown by name instead of as raw hex.
+#[derive(Debug, Args)]
+pub(crate) struct AddKnownKey {
+    /// The key, as a `did:key`.
+    #[arg(value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    /// A label for whoever holds the key.
+    pub(crate) label: String,
+}
+
+/// Removes a user-defined key label.
+#[derive(Debug, Args)]
+pub(crate) struct RemoveKnownKey {
+    /// The key, as a `did:key`.
+    #[arg(value_parser = parse_did_key)]
+    pub(crate) key: String,
+}
+
+fn parse_did_key(s: &str) -> Result<String, String> {
+    atrium_crypto::did::parse_did_key(s)
+        .map(|_| s.to_string())
+        .map_err(|e| format!("invalid did:key: {e}"))
+}
+
 /// Inspect operations for a DID.
 #[derive(Debug, Subcommand)]
 pub(crate) enum Ops {
     List(ListOps),
     Audit(AuditOps),
+    Report(ReportOps),
+    Graph(GraphOps),
+    Format(FormatOp),
+    CompareSources(CompareSourcesOps),
+    Submit(SubmitOp),
+    Recover(RecoverOp),
+    Tombstone(TombstoneOp),
+    VerifyEntry(VerifyEntryOp),
 }
 
 /// Lists operations for a user's DID.
@@ -59,4 +807,274 @@ pub(crate) struct ListOps {
 #[derive(Debug, Args)]
 pub(crate) struct AuditOps {
     pub(crate) user: String,
+
+    /// Revalidate the audit log, even if it is unchanged since it was last found to be
+    /// valid.
+    #[arg(long)]
+    pub(crate) no_cache: bool,
+}
+
+/// Compares a DID's audit log as served by several directories, e.g. plc.directory
+/// and a mirror.
+#[derive(Debug, Args)]
+pub(crate) struct CompareSourcesOps {
+    pub(crate) did: Did,
+
+    /// Comma-separated directory URLs or hostnames. The first is the reference that
+    /// the others are compared against.
+    #[arg(long, value_delimiter = ',', num_args = 1.., required = true)]
+    pub(crate) sources: Vec<String>,
+}
+
+/// Signs an operation for a user's DID with a rotation key from the local keystore,
+/// and submits it to the directory.
+///
+/// The operation is either read from a template, or built from the DID's current
+/// state and the flags below.
+#[derive(Debug, Args)]
+pub(crate) struct SubmitOp {
+    pub(crate) user: String,
+
+    /// The rotation key to sign with, as a `did:key`. It must be in the keystore.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    /// Path to a JSON operation without `sig`, or `-` for stdin. If an update has no
+    /// `prev`, it follows the DID's latest operation.
+    #[arg(long, conflicts_with_all = ["handle", "pds", "signing_key"])]
+    pub(crate) template: Option<PathBuf>,
+
+    /// Change the primary handle.
+    #[arg(long)]
+    pub(crate) handle: Option<HandleStr>,
+
+    /// Change the PDS endpoint.
+    #[arg(long, value_name = "URL")]
+    pub(crate) pds: Option<String>,
+
+    /// Change the ATProto signing key, as a `did:key`.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) signing_key: Option<String>,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Recovers a user's DID by nullifying recent operations, with an operation signed by
+/// a higher-priority rotation key from the local keystore.
+#[derive(Debug, Args)]
+#[command(after_long_help = format!(
+    "An operation can be nullified within {V0_1_RECOVERY_WINDOW_HOURS} hours of its creation by \
+     a conflicting operation signed with a higher-priority rotation key. The DID is restored \
+     to its state from before the nullified operations."
+))]
+pub(crate) struct RecoverOp {
+    pub(crate) user: String,
+
+    /// The rotation key to sign with, as a `did:key`. It must be in the keystore.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    /// The earliest operation to nullify. Defaults to the earliest operation that the
+    /// key can nullify.
+    #[arg(long, value_name = "CID")]
+    pub(crate) cid: Option<String>,
+
+    /// Nullify the operations without asking.
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Permanently deactivates a user's DID, with an operation signed by a rotation key
+/// from the local keystore.
+#[derive(Debug, Args)]
+#[command(after_long_help = format!(
+    "This can only be undone by nullifying the tombstone within {V0_1_RECOVERY_WINDOW_HOURS} \
+     hours, with a higher-priority rotation key than the one that signed it."
+))]
+pub(crate) struct TombstoneOp {
+    pub(crate) user: String,
+
+    /// The rotation key to sign with, as a `did:key`. It must be in the keystore.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) key: String,
+
+    /// The DID being deactivated, instead of retyping it when prompted.
+    #[arg(long, value_name = "DID")]
+    pub(crate) confirm_did: Option<String>,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Verifies a single audit log entry, such as one from `directory export`: its CID,
+/// its signature, and (for a genesis operation) its DID.
+#[derive(Debug, Args)]
+pub(crate) struct VerifyEntryOp {
+    /// Path to a JSON file containing the entry, or `-` for stdin.
+    pub(crate) entry: PathBuf,
+
+    /// Path to a JSON file containing the entry for the operation it follows. If not
+    /// given, it is fetched from the directory.
+    #[arg(long)]
+    pub(crate) prev: Option<PathBuf>,
+}
+
+/// Converts a signed operation into another format, for submission by other tools.
+#[derive(Debug, Args)]
+pub(crate) struct FormatOp {
+    /// Path to a JSON file containing the signed operation, or `-` for stdin.
+    pub(crate) operation: PathBuf,
+
+    /// The operation is encoded as DAG-CBOR instead of JSON.
+    #[arg(long)]
+    pub(crate) cbor: bool,
+
+    /// The format to write to stdout.
+    #[arg(long, value_enum)]
+    pub(crate) format: OpFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum OpFormat {
+    /// The operation as JSON, for submitting directly to the directory.
+    Json,
+    /// The input to `com.atproto.identity.submitPlcOperation`, for submitting via the
+    /// user's PDS.
+    Xrpc,
+}
+
+/// Manage a DID's PDS.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Pds {
+    Migrate(MigratePds),
+}
+
+/// Points a user's DID at a new PDS, via an operation signed by their current PDS or
+/// by a rotation key from the local keystore.
+#[derive(Debug, Args)]
+pub(crate) struct MigratePds {
+    pub(crate) user: String,
+
+    /// The new PDS endpoint, e.g. `https://pds.example.com`.
+    #[arg(value_name = "URL")]
+    pub(crate) endpoint: String,
+
+    /// Sign with this rotation key from the keystore, as a `did:key`, instead of
+    /// asking the current PDS.
+    #[arg(long, value_parser = parse_did_key, conflicts_with = "token")]
+    pub(crate) key: Option<String>,
+
+    /// The token the current PDS emailed to the account. Arguments are visible to other
+    /// processes, so with `--non-interactive` set `PLC_EMAIL_TOKEN` instead.
+    #[arg(long)]
+    pub(crate) token: Option<String>,
+
+    /// Print the changes without signing or submitting anything.
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
+    /// The operation's CID, if the confirmation policy requires it.
+    #[arg(long, value_name = "CID")]
+    pub(crate) confirm_cid: Option<String>,
+}
+
+/// Compares the DID configurations of two users.
+#[derive(Debug, Args)]
+pub(crate) struct Compare {
+    pub(crate) a: String,
+    pub(crate) b: String,
+}
+
+/// Explains in plain language who controls a user's DID.
+#[derive(Debug, Args)]
+pub(crate) struct Explain {
+    pub(crate) user: String,
+}
+
+/// Manage a fleet of DIDs with shared configuration invariants.
+#[derive(Debug, Subcommand)]
+pub(crate) enum Fleet {
+    Check(CheckFleet),
+    Enforce(EnforceFleet),
+}
+
+/// Checks every account in a fleet against its policy.
+#[derive(Debug, Args)]
+pub(crate) struct CheckFleet {
+    /// Path to the fleet configuration.
+    #[arg(long, default_value = "fleet.toml")]
+    pub(crate) config: PathBuf,
+}
+
+/// Brings every account in a fleet into line with its policy, by signing and
+/// submitting an operation for each account that violates it.
+#[derive(Debug, Args)]
+pub(crate) struct EnforceFleet {
+    /// Path to the fleet configuration.
+    #[arg(long, default_value = "fleet.toml")]
+    pub(crate) config: PathBuf,
+
+    /// Sign with this rotation key from the keystore, as a `did:key`, for accounts
+    /// that it is a rotation key of. Other accounts are signed by their PDS.
+    #[arg(long, value_parser = parse_did_key)]
+    pub(crate) key: Option<String>,
+
+    /// Submit each operation without asking.
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+/// Generates a report of a user's DID history.
+#[derive(Debug, Args)]
+pub(crate) struct ReportOps {
+    pub(crate) user: String,
+
+    /// Write a self-contained HTML report to this path.
+    #[arg(long)]
+    pub(crate) html: PathBuf,
+}
+
+/// Draws the graph of a DID's operations, including nullified forks and tombstones.
+#[derive(Debug, Args)]
+pub(crate) struct GraphOps {
+    pub(crate) did: Did,
+
+    /// Write a self-contained SVG image to this path.
+    #[arg(long)]
+    pub(crate) svg: PathBuf,
+}
+
+/// Pins a user's current rotation keys, so that later changes to them are flagged.
+///
+/// Once pinned, every command that looks up the user warns if their rotation keys
+/// have changed. Pinning again accepts the current keys.
+#[derive(Debug, Args)]
+pub(crate) struct Pin {
+    pub(crate) user: String,
+
+    /// Remove the pin instead.
+    #[arg(long)]
+    pub(crate) remove: bool,
+}
+
+/// Checks that the local environment can run `plc` correctly.
+#[derive(Debug, Args)]
+pub(crate) struct SelfTest {
+    /// Also check that local keys can be generated and used for signing.
+    #[arg(long)]
+    pub(crate) keys: bool,
+}
+
+/// Verifies that DIDs are derived from their genesis operations.
+#[derive(Debug, Args)]
+pub(crate) struct VerifyDid {
+    #[arg(required = true)]
+    pub(crate) dids: Vec<Did>,
 }
@@ -1,19 +1,45 @@
-use crate::{cli::Login, data::State, error::Error, remote::pds};
+use std::env;
+
+use tokio::fs;
+use zeroize::Zeroizing;
+
+use crate::{cli::Login, context::AppContext, data::State, error::Error, remote::pds};
 
 impl Login {
-    pub(crate) async fn run(&self) -> Result<(), Error> {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let app_password = self.app_password(ctx.non_interactive).await?;
+
         // Fetch the user's current state.
-        let client = reqwest::Client::new();
-        let state = State::resolve(&self.user, &client).await?;
+        let state = State::resolve(&self.user, ctx).await?;
 
         // Get the endpoint we will log into.
         let endpoint = state.endpoint().ok_or(Error::DidDocumentHasNoPds)?;
 
         let agent = pds::Agent::new(endpoint.into());
-        agent.login(&self.user, &self.app_password).await?;
+        agent.login(&self.user, &app_password).await?;
 
         println!("Logged in as @{}", state.handle().unwrap_or(&self.user));
 
         Ok(())
     }
+
+    async fn app_password(&self, non_interactive: bool) -> Result<Zeroizing<String>, Error> {
+        if let Some(path) = &self.app_password_file {
+            let password = fs::read_to_string(path)
+                .await
+                .map_err(Error::AppPasswordReadFailed)?;
+            return Ok(Zeroizing::new(password.trim_end().to_string()));
+        }
+
+        match (&self.app_password, env::var("PLC_APP_PASSWORD")) {
+            // Arguments are visible to other processes, and end up in shell history.
+            (Some(_), _) if non_interactive => Err(Error::SecretOnCommandLine),
+            (Some(password), _) => Ok(Zeroizing::new(password.clone())),
+            (None, Ok(password)) => Ok(Zeroizing::new(password)),
+            (None, Err(_)) if non_interactive => Err(Error::AppPasswordRequired),
+            (None, Err(_)) => rpassword::prompt_password("App password: ")
+                .map(Zeroizing::new)
+                .map_err(Error::AppPasswordReadFailed),
+        }
+    }
 }
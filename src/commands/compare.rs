@@ -0,0 +1,66 @@
+use diff::Diff;
+
+use super::ops::update_items;
+use crate::{
+    cli::Compare,
+    context::AppContext,
+    data::{Key, State},
+    error::Error,
+    known_keys::KnownKeys,
+    output::{heading, index_width, print_items, valid},
+};
+
+impl Compare {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let a = State::resolve(&self.a, ctx).await?;
+        let b = State::resolve(&self.b, ctx).await?;
+        let known_keys = KnownKeys::load().await?;
+
+        println!(
+            "{}",
+            heading(format!(
+                "Changes from {} ({}) to {} ({}):",
+                self.a,
+                a.did().as_str(),
+                self.b,
+                b.did().as_str(),
+            ))
+        );
+        let items = update_items(&a.inner_data().diff(b.inner_data()));
+        if items.is_empty() {
+            println!("{}", valid("The configurations are identical"));
+        } else {
+            print_items(&items);
+        }
+
+        // Rotation keys are compared by position above; also show who holds them, so
+        // that differing keys from the same custodian are easy to spot.
+        let custody = |state: &State| {
+            state
+                .inner_data()
+                .rotation_keys
+                .iter()
+                .map(|key| {
+                    Key::did(key)
+                        .ok()
+                        .and_then(|k| known_keys.label(&k).map(String::from))
+                        .unwrap_or_else(|| "Unknown".into())
+                })
+                .collect::<Vec<_>>()
+        };
+        let (custody_a, custody_b) = (custody(&a), custody(&b));
+        if custody_a != custody_b {
+            println!();
+            println!("{}", heading("Rotation key custody:"));
+            for (user, custody) in [(&self.a, custody_a), (&self.b, custody_b)] {
+                println!("- {user}:");
+                let width = index_width(custody.len());
+                for (i, label) in custody.iter().enumerate() {
+                    println!("  - {:width$} {label}", format!("[{i}]"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
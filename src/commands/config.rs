@@ -0,0 +1,82 @@
+use clap::ValueEnum;
+
+use crate::{
+    cli::{ConfigDefault, SetConfig, ShowConfig, UnsetConfig},
+    config::{self, Config, CONFIG_FILE},
+    context::AppContext,
+    error::Error,
+    local,
+    output::{advisory, label_width},
+};
+
+impl ShowConfig {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        if let Some(path) = local::config_file(CONFIG_FILE) {
+            println!("Config file: {}", path.display());
+        }
+
+        let defaults = &ctx.config.defaults;
+        let values = [
+            (ConfigDefault::PlcDirectory, defaults.plc_directory.clone()),
+            (
+                ConfigDefault::Time,
+                defaults.time.and_then(|time| {
+                    time.to_possible_value()
+                        .map(|value| value.get_name().to_string())
+                }),
+            ),
+            (ConfigDefault::Key, defaults.key.clone()),
+        ];
+        let width = label_width(values.iter().map(|(name, _)| name_of(*name)));
+        for (name, value) in values {
+            match value {
+                Some(value) => println!("  {:width$}  {value}", name_of(name)),
+                None => println!("  {:width$}  {}", name_of(name), advisory("not set")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SetConfig {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let name = name_of(self.name);
+        if matches!(self.name, ConfigDefault::Key)
+            && atrium_crypto::did::parse_did_key(&self.value).is_err()
+        {
+            return Err(Error::ConfigInvalidDefault(name.into()));
+        }
+
+        config::set_default(name, Some(&self.value)).await?;
+        println!("Set {name} to {}", self.value);
+        warn_if_invalid().await;
+        Ok(())
+    }
+}
+
+impl UnsetConfig {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let name = name_of(self.name);
+        config::set_default(name, None).await?;
+        println!("Removed the default for {name}");
+        warn_if_invalid().await;
+        Ok(())
+    }
+}
+
+/// Tells the user if the rest of the config file still doesn't load.
+async fn warn_if_invalid() {
+    if let Err(e) = Config::load().await {
+        println!("{}", advisory(format!("{e:?}")));
+    }
+}
+
+/// Returns the name of the default, as used both on the command line and in
+/// `config.toml`.
+fn name_of(name: ConfigDefault) -> &'static str {
+    match name {
+        ConfigDefault::PlcDirectory => "plc-directory",
+        ConfigDefault::Time => "time",
+        ConfigDefault::Key => "key",
+    }
+}
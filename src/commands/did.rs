@@ -0,0 +1,223 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use tokio::fs;
+
+use crate::{
+    cli::{GrindDid, PreviewDid},
+    commands::submit::{self, Signer},
+    context::AppContext,
+    data::{PlcData, State},
+    error::Error,
+    keystore::{KeyAlgorithm, SecretKey, StoredKey},
+    output::advisory,
+    remote::plc::{SignedOperation, UnsignedOperation},
+    util,
+    warning::Warning,
+};
+
+/// The longest prefix `did grind` searches for. Each character multiplies the expected
+/// number of attempts by 32, so longer prefixes would take hours.
+const MAX_PREFIX_LEN: usize = 5;
+
+impl PreviewDid {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let op = read_operation(&self.operation, self.cbor).await?;
+
+        match op.derived_did() {
+            None => println!("This is not a genesis operation; it does not create a DID"),
+            Some(did) => {
+                println!("DID: {}", did.as_str());
+                match op.genesis_signer() {
+                    Some(index) => println!("Signed by rotation key [{index}]"),
+                    None => ctx.warn(Warning::GenesisUnsigned),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl GrindDid {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        check_prefix(&self.prefix)?;
+        eprintln!(
+            "{}",
+            advisory("Vanity DIDs are for test and demo identities only"),
+        );
+
+        let mut data = PlcData {
+            rotation_keys: vec![],
+            verification_methods: Default::default(),
+            also_known_as: vec![],
+            services: Default::default(),
+        };
+        if let Some(handle) = &self.handle {
+            data.set_handle(handle.as_str());
+        }
+        if let Some(pds) = &self.pds {
+            data.set_endpoint(pds.clone());
+        }
+        if let Some(key) = &self.signing_key {
+            data.verification_methods
+                .insert("atproto".into(), key.clone());
+        }
+
+        // The rotation key each attempt generates isn't known yet, so the hooks see the
+        // operation without it, before any attempt is signed.
+        ctx.config
+            .hooks
+            .pre_sign(None, &UnsignedOperation::genesis(data.clone()).to_json())
+            .await?;
+
+        // Ask for the passphrase up front, rather than after a long search.
+        let passphrase = util::passphrase("Key", "PLC_KEY_PASSPHRASE", ctx.non_interactive, true)?;
+
+        let cancel = ctx.cancel_on_ctrl_c();
+        let prefix = self.prefix.clone();
+        let (algorithm, max_attempts) = (self.algorithm, self.max_attempts);
+        let (key, op, attempts) = tokio::task::spawn_blocking(move || {
+            grind(data, &prefix, algorithm, max_attempts, || {
+                cancel.is_cancelled()
+            })
+        })
+        .await
+        .expect("grinding doesn't panic")?;
+        let did = op.derived_did().expect("is a genesis operation");
+        eprintln!("Found {} after {attempts} attempts", did.as_str());
+
+        let stored = StoredKey::new(
+            &key,
+            Some(format!("Rotation key for {}", did.as_str())),
+            passphrase,
+        );
+        stored.save().await?;
+        eprintln!("Saved rotation key {} to the keystore", stored.did);
+
+        let json = serde_json::to_string_pretty(&op).expect("can serialize");
+        match &self.output {
+            Some(path) => fs::write(path, json + "\n")
+                .await
+                .map_err(Error::OutputWriteFailed)?,
+            None => println!("{json}"),
+        }
+
+        if self.submit {
+            // The genesis operation creates the state it is submitted for.
+            let state = State::new(did, op.data().expect("is a genesis operation"));
+            submit::submit(ctx, &state, &op, Signer::Key(stored.did), None).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_prefix(prefix: &str) -> Result<(), Error> {
+    if prefix.is_empty() || prefix.len() > MAX_PREFIX_LEN {
+        return Err(Error::GrindPrefixInvalid(format!(
+            "must be 1 to {MAX_PREFIX_LEN} characters"
+        )));
+    }
+    if !prefix
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+    {
+        return Err(Error::GrindPrefixInvalid(
+            "DIDs only contain the characters a-z and 2-7".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Signs genesis operations for `data` with newly generated rotation keys (added as
+/// rotation key [0]) until one creates a DID starting with `prefix`.
+///
+/// Returns the key, the operation, and the number of attempts it took.
+fn grind(
+    data: PlcData,
+    prefix: &str,
+    algorithm: KeyAlgorithm,
+    max_attempts: u64,
+    cancelled: impl Fn() -> bool,
+) -> Result<(SecretKey, SignedOperation, u64), Error> {
+    let did_prefix = format!("did:plc:{prefix}");
+    for attempt in 1..=max_attempts {
+        if cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let key = SecretKey::generate(algorithm);
+        let mut data = data.clone();
+        data.rotation_keys.insert(0, key.did());
+        let op = UnsignedOperation::genesis(data).sign(&key);
+        let did = op.derived_did().expect("is a genesis operation");
+        if did.as_str().starts_with(&did_prefix) {
+            return Ok((key, op, attempt));
+        }
+    }
+    Err(Error::GrindAttemptsExhausted(max_attempts))
+}
+
+/// Reads a signed operation from `path` (or stdin if `path` is `-`), encoded as JSON or
+/// DAG-CBOR.
+pub(super) async fn read_operation(path: &Path, cbor: bool) -> Result<SignedOperation, Error> {
+    let data = if path.as_os_str() == "-" {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path).await
+    }
+    .map_err(Error::InputReadFailed)?;
+
+    if cbor {
+        SignedOperation::from_dag_cbor_strict(&data)
+    } else {
+        serde_json::from_slice(&data).map_err(Error::InvalidOperation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_prefix, grind};
+    use crate::{data::PlcData, error::Error, keystore::KeyAlgorithm};
+
+    #[test]
+    fn prefixes() {
+        assert!(check_prefix("abc").is_ok());
+        assert!(check_prefix("z2345").is_ok());
+        assert!(check_prefix("").is_err());
+        assert!(check_prefix("abcdef").is_err());
+        assert!(check_prefix("ABC").is_err());
+        assert!(check_prefix("a1").is_err());
+    }
+
+    #[test]
+    fn grinding() {
+        let data = PlcData {
+            rotation_keys: vec!["did:key:zQ3shP5gBEz9Z7xYqHVGWsoSQeoxbDBkZzXbTfvXKqMX9SEqn".into()],
+            verification_methods: Default::default(),
+            also_known_as: vec![],
+            services: Default::default(),
+        };
+
+        // A single character is found in 32 attempts on average.
+        let (key, op, attempts) =
+            grind(data.clone(), "a", KeyAlgorithm::P256, 10_000, || false).unwrap();
+        assert!(op.derived_did().unwrap().as_str().starts_with("did:plc:a"));
+        assert_eq!(op.genesis_signer(), Some(0));
+        assert_eq!(
+            op.data().unwrap().rotation_keys,
+            [key.did(), data.rotation_keys[0].clone()],
+        );
+        assert!(attempts >= 1);
+
+        assert!(matches!(
+            grind(data.clone(), "a", KeyAlgorithm::P256, 0, || false),
+            Err(Error::GrindAttemptsExhausted(0)),
+        ));
+        assert!(matches!(
+            grind(data, "a", KeyAlgorithm::P256, 10, || true),
+            Err(Error::Cancelled),
+        ));
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{SecondsFormat, Utc};
+use futures_util::{future::join_all, StreamExt, TryStreamExt};
+use rand_core::{OsRng, RngCore};
+use tokio::fs;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cli::{AuditDirectory, CheckHistory, DirectoryStatus, ExportDirectory, ExportSubset},
+    context::AppContext,
+    error::Error,
+    local,
+    output::{advisory, format_time, heading, label_width, valid, violation},
+    remote::plc::{self, probe, AuditLog, ExportGroups, HistoryChange, LogEntry},
+};
+
+impl ExportDirectory {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let cancel = ctx.cancel_on_ctrl_c();
+        let entries = ctx
+            .directory
+            .export_stream(self.after.clone())
+            .take(self.limit.unwrap_or(usize::MAX));
+        futures_util::pin_mut!(entries);
+
+        let mut stdout = io::stdout().lock();
+        let mut last: Option<Datetime> = None;
+        loop {
+            let entry = tokio::select! {
+                // Stop before starting more work once cancelled.
+                biased;
+                () = cancel.cancelled() => {
+                    stdout.flush().map_err(Error::OutputWriteFailed)?;
+                    if let Some(last) = last {
+                        eprintln!(
+                            "Cancelled; to resume, pass --after {}",
+                            last.as_ref().to_rfc3339_opts(SecondsFormat::Millis, true),
+                        );
+                    }
+                    return Err(Error::Cancelled);
+                }
+                entry = entries.try_next() => entry?,
+            };
+            let Some(entry) = entry else { break };
+
+            last = Some(entry.created_at().clone());
+            serde_json::to_writer(&mut stdout, &entry)
+                .map_err(io::Error::from)
+                .and_then(|()| writeln!(stdout))
+                .map_err(Error::OutputWriteFailed)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ExportSubset {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let cancel = ctx.cancel_on_ctrl_c();
+        let dids = read_did_list(&self.dids).await?;
+
+        let mut entries = vec![];
+        for did in &dids {
+            let log = tokio::select! {
+                biased;
+                () = cancel.cancelled() => return Err(Error::Cancelled),
+                log = ctx.directory.download_audit_log(did) => log?,
+            };
+            // Only complete, valid chains keep the subset audit-valid.
+            if !log.starts_with_genesis() || log.validate().is_err() {
+                return Err(Error::SubsetLogInvalid(did.as_str().into()));
+            }
+            entries.extend(
+                log.entries()
+                    .iter()
+                    .filter(|entry| self.in_window(entry))
+                    .cloned(),
+            );
+        }
+
+        // Match the order of the directory's export. The sort is stable, so each DID's
+        // operations stay in log order.
+        entries.sort_by(|a, b| a.created_at().as_ref().cmp(b.created_at().as_ref()));
+        let mut subset = vec![];
+        for entry in &entries {
+            serde_json::to_writer(&mut subset, entry).expect("can serialize");
+            subset.push(b'\n');
+        }
+
+        match &self.out {
+            Some(path) => fs::write(path, subset)
+                .await
+                .map_err(Error::OutputWriteFailed)?,
+            None => io::stdout()
+                .lock()
+                .write_all(&subset)
+                .map_err(Error::OutputWriteFailed)?,
+        }
+        eprintln!(
+            "Exported {} operations for {} DIDs",
+            entries.len(),
+            dids.len(),
+        );
+
+        Ok(())
+    }
+
+    /// Returns `true` if `entry` was created within the `--after` and `--before` bounds.
+    fn in_window(&self, entry: &LogEntry) -> bool {
+        let created_at = entry.created_at().as_ref();
+        self.after
+            .as_ref()
+            .map_or(true, |after| created_at > after.as_ref())
+            && self
+                .before
+                .as_ref()
+                .map_or(true, |before| created_at < before.as_ref())
+    }
+}
+
+/// Reads a list of DIDs, one per line, skipping blank lines, comments, and duplicates.
+async fn read_did_list(path: &Path) -> Result<Vec<Did>, Error> {
+    let list = fs::read_to_string(path)
+        .await
+        .map_err(Error::InputReadFailed)?;
+
+    let mut seen = HashSet::new();
+    let mut dids = vec![];
+    for line in list.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let did = Did::new(line.into()).map_err(|_| Error::DidListInvalid(line.into()))?;
+        if seen.insert(did.clone()) {
+            dids.push(did);
+        }
+    }
+    Ok(dids)
+}
+
+impl AuditDirectory {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let cancel = ctx.cancel_on_ctrl_c();
+        let entries = ctx
+            .directory
+            .export_stream(self.after.clone())
+            .take(self.limit);
+        futures_util::pin_mut!(entries);
+
+        // DIDs are audited as the export arrives, so memory use is bounded by the buffer
+        // rather than by the number of operations read.
+        let mut groups = ExportGroups::new(self.buffer);
+        let mut operations = 0;
+        let mut audited = 0;
+        let mut valid_count = 0;
+        let mut failed = false;
+        let mut audit = |problems: Vec<String>, did: &Did| {
+            audited += 1;
+            if problems.is_empty() {
+                valid_count += 1;
+            } else {
+                failed = true;
+                println!("{} {}", violation("[FAIL]"), heading(did.as_str()));
+                for problem in problems {
+                    println!("  - {}", violation(problem));
+                }
+            }
+        };
+
+        loop {
+            let entry = tokio::select! {
+                biased;
+                () = cancel.cancelled() => return Err(Error::Cancelled),
+                entry = entries.try_next() => entry?,
+            };
+            let Some(entry) = entry else { break };
+            operations += 1;
+            if let Some(exported) = groups.push(entry) {
+                audit(
+                    audit_fragment(ctx, &cancel, &exported).await?,
+                    exported.did(),
+                );
+            }
+        }
+        for exported in groups.finish() {
+            audit(
+                audit_fragment(ctx, &cancel, &exported).await?,
+                exported.did(),
+            );
+        }
+
+        println!(
+            "Audited {audited} DIDs ({operations} operations): {} valid",
+            valid(valid_count),
+        );
+
+        if failed {
+            Err(Error::DirectoryAuditFailed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns the problems with a DID's operations from the export.
+async fn audit_fragment(
+    ctx: &AppContext,
+    cancel: &CancellationToken,
+    exported: &AuditLog,
+) -> Result<Vec<String>, Error> {
+    // The window may start or end partway through a DID's history (and the export
+    // records whether each operation is nullified as of now, which can depend on
+    // operations outside the window). If the exported fragment doesn't validate on
+    // its own, fall back to the full audit log.
+    if exported.starts_with_genesis() && exported.validate().is_ok() {
+        return Ok(vec![]);
+    }
+
+    let full = tokio::select! {
+        biased;
+        () = cancel.cancelled() => return Err(Error::Cancelled),
+        full = ctx.directory.download_audit_log(exported.did()) => full,
+    };
+
+    Ok(match full {
+        Err(e) => vec![format!("failed to fetch audit log: {e:?}")],
+        Ok(full) => {
+            let mut problems = match full.validate() {
+                Ok(()) => vec![],
+                Err(errors) => errors.into_iter().map(|e| e.to_string()).collect(),
+            };
+            if !full.contains_all(exported) {
+                problems.push("the export contains operations missing from the audit log".into());
+            }
+            problems
+        }
+    })
+}
+
+impl CheckHistory {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let cancel = ctx.cancel_on_ctrl_c();
+
+        let mut dids = plc::seen_dids().await;
+        if dids.is_empty() {
+            println!("No audit logs have been seen yet; audit some DIDs first");
+            return Ok(());
+        }
+
+        // Pick a uniformly random sample, so a directory can't predict which DIDs we
+        // will check.
+        let sample = self.sample.min(dids.len());
+        for i in 0..sample {
+            let j = i + (OsRng.next_u64() % (dids.len() - i) as u64) as usize;
+            dids.swap(i, j);
+        }
+        dids.truncate(sample);
+
+        let mut changed = false;
+        for did in &dids {
+            let Some(previous) = plc::seen_history(did).await else {
+                continue;
+            };
+            // Only extend the seen history once we know the current log doesn't
+            // rewrite it.
+            let current = tokio::select! {
+                biased;
+                () = cancel.cancelled() => return Err(Error::Cancelled),
+                current = ctx.directory.download_audit_log(did) => current,
+            };
+
+            let current = match current {
+                Ok(current) => current,
+                Err(e) => {
+                    println!(
+                        "{} {}: failed to fetch audit log: {e:?}",
+                        violation("[FAIL]"),
+                        heading(did.as_str()),
+                    );
+                    continue;
+                }
+            };
+
+            let changes = current.history_changes(&previous);
+            if changes.is_empty() {
+                plc::record_seen_history(did, current.entries()).await;
+                continue;
+            }
+
+            changed = true;
+            println!("{} {}", violation("[CHANGED]"), heading(did.as_str()));
+            for change in &changes {
+                println!("  - {}", violation(change));
+            }
+            // The seen history is left as it was, so the change is reported again until
+            // it is investigated.
+            let path = save_evidence(did, &previous, &current, &changes).await?;
+            println!("  Evidence saved to {}", path.display());
+        }
+
+        println!(
+            "Checked {} DIDs: {}",
+            dids.len(),
+            if changed {
+                violation("history was rewritten!")
+            } else {
+                valid("history unchanged")
+            },
+        );
+
+        if changed {
+            Err(Error::DirectoryHistoryChanged)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl DirectoryStatus {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let urls = Some(ctx.directory_url.clone())
+            .into_iter()
+            .chain(self.mirrors.iter().map(|mirror| {
+                if mirror.contains("://") {
+                    mirror.clone()
+                } else {
+                    format!("https://{mirror}")
+                }
+            }))
+            .collect::<Vec<_>>();
+
+        let statuses = join_all(
+            urls.iter()
+                .map(|url| probe::probe(url, &self.did, Duration::from_secs(self.timeout))),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&statuses).expect("can serialize")
+            );
+        } else {
+            for status in &statuses {
+                print_status(status);
+            }
+        }
+
+        if statuses.iter().all(|status| status.is_healthy()) {
+            Ok(())
+        } else {
+            Err(Error::DirectoryProbeFailed)
+        }
+    }
+}
+
+fn print_status(status: &probe::DirectoryStatus) {
+    println!("{}", heading(&status.url));
+    let width = label_width(status.checks.iter().map(|check| check.name));
+    for check in &status.checks {
+        let latency = check
+            .latency_ms
+            .map(|ms| format!(" in {ms} ms"))
+            .unwrap_or_default();
+        match &check.error {
+            None => println!("  {:width$}  {}{latency}", check.name, valid("ok")),
+            Some(e) => println!("  {:width$}  {}{latency}", check.name, violation(e)),
+        }
+    }
+    match &status.latest_export {
+        Some(created_at) => println!("  Latest export: {}", format_time(created_at)),
+        None => println!(
+            "  Latest export: {}",
+            advisory("no operations in the last minute")
+        ),
+    }
+    if let Some(expires) = status.tls_expires {
+        println!(
+            "  TLS expires:   {}",
+            expires.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    }
+    for (header, value) in &status.rate_limit {
+        println!("  {header}: {value}");
+    }
+    println!();
+}
+
+/// Saves both copies of a DID's audit log, so that a rewritten history can be
+/// demonstrated later.
+async fn save_evidence(
+    did: &Did,
+    previous: &AuditLog,
+    current: &AuditLog,
+    changes: &[HistoryChange],
+) -> Result<PathBuf, Error> {
+    let now = Utc::now();
+    let path = local::data_file(format!(
+        "evidence/{}-{}.json",
+        did.as_str().replace(':', "_"),
+        now.timestamp(),
+    ))
+    .ok_or_else(|| Error::EvidenceSaveFailed(io::ErrorKind::NotFound.into()))?;
+
+    let evidence = serde_json::json!({
+        "did": did.as_str(),
+        "detectedAt": now.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "changes": changes.iter().map(|change| change.to_string()).collect::<Vec<_>>(),
+        "previous": previous.entries(),
+        "current": current.entries(),
+    });
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(Error::EvidenceSaveFailed)?;
+    }
+    local::write_atomic(
+        &path,
+        serde_json::to_vec_pretty(&evidence).expect("can serialize"),
+    )
+    .await
+    .map_err(Error::EvidenceSaveFailed)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use snapbox::dir::DirRoot;
+
+    use crate::{
+        cli::{AuditDirectory, ExportSubset},
+        context::AppContext,
+        error::Error,
+        remote::plc::{mock::MockDirectory, testing::TestLog, ExportGroups, LogEntry},
+    };
+
+    #[tokio::test]
+    async fn audit_directory() {
+        let valid =
+            TestLog::with_genesis().apply_update(|update| update.change_handle("bob.example.com"));
+        let invalid = TestLog::with_genesis()
+            .apply_update(|update| update.change_pds("https://pds.example.com").invalid_sig());
+
+        let mut audit = AuditDirectory {
+            after: None,
+            limit: 100,
+            buffer: 100,
+        };
+
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&valid));
+        assert!(audit.run(&ctx).await.is_ok());
+        // Fragments that don't start with the genesis operation are checked against
+        // the full audit log.
+        audit.buffer = 1;
+        assert!(audit.run(&ctx).await.is_ok());
+
+        let ctx =
+            AppContext::for_tests(MockDirectory::default().with_log(&valid).with_log(&invalid));
+        assert!(matches!(
+            audit.run(&ctx).await,
+            Err(Error::DirectoryAuditFailed),
+        ));
+    }
+
+    #[tokio::test]
+    async fn export_subset() {
+        let alice = TestLog::with_genesis()
+            .apply_update(|update| update.change_handle("alice.example.com"));
+        let bob = TestLog::with_genesis();
+        let invalid = TestLog::with_genesis()
+            .apply_update(|update| update.change_pds("https://pds.example.com").invalid_sig());
+        let ctx = AppContext::for_tests(
+            MockDirectory::default()
+                .with_log(&alice)
+                .with_log(&bob)
+                .with_log(&invalid),
+        );
+
+        // Removed when dropped, even if the test fails.
+        let root = DirRoot::mutable_temp().unwrap();
+        let dir = root.path().unwrap();
+        let export = |list: String| {
+            std::fs::write(dir.join("dids.txt"), list).unwrap();
+            ExportSubset {
+                dids: dir.join("dids.txt"),
+                after: None,
+                before: None,
+                out: Some(dir.join("subset.jsonl")),
+            }
+        };
+
+        let (alice_did, bob_did) = (alice.did(), bob.did());
+        export(format!(
+            "# Investigation\n{}\n\n{}\n{}\n",
+            alice_did.as_str(),
+            bob_did.as_str(),
+            alice_did.as_str(),
+        ))
+        .run(&ctx)
+        .await
+        .unwrap();
+        let subset = std::fs::read_to_string(dir.join("subset.jsonl")).unwrap();
+        let mut groups = ExportGroups::new(usize::MAX);
+        for line in subset.lines() {
+            assert!(groups
+                .push(serde_json::from_str::<LogEntry>(line).unwrap())
+                .is_none());
+        }
+        let logs = groups.finish();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs.iter().map(|log| log.entries().len()).sum::<usize>(), 3);
+        assert!(logs.iter().all(|log| log.validate().is_ok()));
+
+        // Time bounds drop the operations outside them.
+        let alice_log = alice.audit_log();
+        let genesis_at = alice_log.entries()[0].created_at();
+        let mut bounded = export(format!("{}\n", alice_did.as_str()));
+        bounded.after = Some(genesis_at.clone());
+        bounded.run(&ctx).await.unwrap();
+        let subset = std::fs::read_to_string(dir.join("subset.jsonl")).unwrap();
+        assert_eq!(subset.lines().count(), 1);
+        bounded.after = None;
+        bounded.before = Some(alice_log.entries()[1].created_at().clone());
+        bounded.run(&ctx).await.unwrap();
+        let subset = std::fs::read_to_string(dir.join("subset.jsonl")).unwrap();
+        assert_eq!(subset.lines().count(), 1);
+
+        assert!(matches!(
+            export(format!("{}\n", invalid.did().as_str()))
+                .run(&ctx)
+                .await,
+            Err(Error::SubsetLogInvalid(_)),
+        ));
+        assert!(matches!(
+            export("alice.example.com\n".into()).run(&ctx).await,
+            Err(Error::DidListInvalid(_)),
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+use atrium_api::types::string::Datetime;
+use chrono::Utc;
+
+use crate::{
+    cli::Explain,
+    context::AppContext,
+    data::{AccountStatus, Key, State},
+    error::Error,
+    known_keys::KnownKeys,
+    output::{advisory, format_time, heading, index_width, violation},
+    remote::{
+        pds::{self, ServerKeys},
+        plc,
+    },
+};
+
+impl Explain {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+        let audit_log = ctx.directory.audit_log(state.did()).await?;
+
+        // Matching keys against those the PDS recommends lets us guess who holds them,
+        // but requires authentication.
+        let server_keys = pds::get_server_keys_if_logged_in(&state).await?;
+        let known_keys = KnownKeys::load().await?;
+        let custodian = |key: &Key| match (&server_keys, known_keys.label(key)) {
+            (Some(keys), _) if keys.contains_rotation(key) || keys.is_signing(key) => {
+                format!("held by the PDS ({:?})", key.algorithm)
+            }
+            (_, Some(label)) => format!("held by {label} ({:?})", key.algorithm),
+            (Some(_), None) => format!(
+                "held by someone other than the PDS ({:?}): {}",
+                key.algorithm,
+                hex::encode(&key.public_key),
+            ),
+            (None, None) => format!(
+                "unknown holder ({:?}): {}",
+                key.algorithm,
+                hex::encode(&key.public_key),
+            ),
+        };
+
+        println!("{}", heading(format!("Account {}", state.did().as_str())));
+        if let Some(handle) = state.handle() {
+            println!(
+                "Known as @{handle}, hosted at {}.",
+                state.endpoint().unwrap_or("no PDS")
+            );
+        }
+        // The account status is only context; the DID is controlled by the directory.
+        match state.account_status().await {
+            Ok(None | Some(AccountStatus::Active)) => (),
+            Ok(Some(AccountStatus::Inactive(status))) => println!(
+                "{}",
+                advisory(format!(
+                    "The PDS reports that this account is {}. This is separate from the \
+                     DID, which remains active in the PLC directory; the account can be \
+                     reactivated, or moved to another PDS.",
+                    status.as_deref().unwrap_or("inactive"),
+                ))
+            ),
+            Ok(Some(AccountStatus::NotHosted)) => println!(
+                "{}",
+                advisory(
+                    "The PDS doesn't host an account for this DID. The DID remains active \
+                     in the PLC directory, and can be pointed at a PDS that does."
+                )
+            ),
+            Err(e) => println!(
+                "{}",
+                advisory(format!("The account's status is unknown: {e:?}."))
+            ),
+        }
+        if server_keys.is_none() {
+            println!(
+                "{}",
+                advisory(format!(
+                    "Not currently authenticated to {}; can't tell which keys the PDS holds",
+                    self.user
+                ))
+            );
+        }
+        println!();
+
+        // Who can change this identity.
+        let rotation_keys = state.rotation_keys();
+        println!("{}", heading("Who can change this identity?"));
+        println!(
+            "Anyone holding one of these {} rotation keys can change this identity. Keys \
+             listed first take priority if conflicting changes are made.",
+            rotation_keys.len(),
+        );
+        let width = index_width(rotation_keys.len());
+        for (i, res) in rotation_keys.iter().enumerate() {
+            let index = format!("[{i}]");
+            match res {
+                Ok(key) => println!("- {index:width$} {}", custodian(key)),
+                Err(e) => println!("- {index:width$} {}", violation(format!("Invalid: {e}"))),
+            }
+        }
+        println!();
+
+        // Who can recover it.
+        println!("{}", heading("Who can recover it?"));
+        match audit_log.authorship().last() {
+            None => println!("{}", violation("The audit log is empty")),
+            Some(last) => explain_recovery(last, &server_keys, &known_keys),
+        }
+        println!();
+
+        // What the signing key is used for.
+        println!("{}", heading("What is the signing key used for?"));
+        match state.signing_key() {
+            None => println!(
+                "{}",
+                advisory("There is no signing key, so this account can't publish any data.")
+            ),
+            Some(Ok(key)) => {
+                println!(
+                    "The signing key ({}) signs the account's repository, i.e. everything it \
+                     posts. It cannot change the identity itself.",
+                    custodian(&key),
+                );
+            }
+            Some(Err(e)) => println!("{}", violation(format!("Invalid signing key: {e}"))),
+        }
+
+        Ok(())
+    }
+}
+
+/// Explains who can override the most recent operation, and until when.
+fn explain_recovery(
+    last: &plc::Authorship,
+    server_keys: &Option<ServerKeys>,
+    known_keys: &KnownKeys,
+) {
+    let Some(signer) = last.signer else {
+        println!(
+            "{}",
+            violation("The most recent change was not signed by an authorized rotation key")
+        );
+        return;
+    };
+
+    let deadline =
+        Datetime::new(*last.created_at.as_ref() + plc::SpecProfile::default().recovery_window);
+    let holder = |key: &str| {
+        if server_keys
+            .as_ref()
+            .is_some_and(|keys| Key::did(key).is_ok_and(|key| keys.contains_rotation(&key)))
+        {
+            ", held by the PDS".to_string()
+        } else if let Some(label) = known_keys.label_for_did(key) {
+            format!(", held by {label}")
+        } else {
+            String::new()
+        }
+    };
+
+    println!(
+        "The most recent change ({}) was signed by rotation key [{signer}]{}.",
+        format_time(&last.created_at),
+        holder(&last.authorized_keys[signer]),
+    );
+
+    if signer == 0 {
+        println!("It was signed by the highest-priority key, so it cannot be overridden.");
+    } else if *deadline.as_ref() < Utc::now() {
+        println!(
+            "The recovery window closed at {}, so it can no longer be overridden.",
+            format_time(&deadline),
+        );
+    } else {
+        println!(
+            "Until {}, it can be overridden by a change signed by any of these \
+             higher-priority keys:",
+            format_time(&deadline),
+        );
+        let width = index_width(signer);
+        for (i, key) in last.authorized_keys[..signer].iter().enumerate() {
+            println!("- {:width$} {key}{}", format!("[{i}]"), holder(key));
+        }
+    }
+}
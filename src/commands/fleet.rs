@@ -0,0 +1,277 @@
+use std::io::{self, BufRead, Write};
+
+use diff::Diff;
+
+use super::{ops::update_items, submit};
+use crate::{
+    cli::{CheckFleet, EnforceFleet},
+    context::AppContext,
+    data::State,
+    error::Error,
+    fleet::{Account, Fleet},
+    output::{heading, print_items, valid, violation},
+};
+
+impl CheckFleet {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let cancel = ctx.cancel_on_ctrl_c();
+        let fleet = Fleet::load(&self.config).await?;
+
+        let mut failed = false;
+        for (checked, account) in fleet.accounts.iter().enumerate() {
+            let resolved = tokio::select! {
+                // Stop before starting more work once cancelled.
+                biased;
+                () = cancel.cancelled() => {
+                    eprintln!(
+                        "Cancelled after checking {checked} of {} accounts",
+                        fleet.accounts.len(),
+                    );
+                    return Err(Error::Cancelled);
+                }
+                resolved = State::resolve(&account.user, ctx) => resolved,
+            };
+
+            let violations = match resolved {
+                Ok(state) => fleet
+                    .check(account, &state)
+                    .into_iter()
+                    .map(|v| v.to_string())
+                    .collect(),
+                Err(e) => vec![format!("{e:?}")],
+            };
+
+            if violations.is_empty() {
+                println!("{} {}", valid("[ok]  "), account.user);
+            } else {
+                failed = true;
+                println!("{} {}", violation("[FAIL]"), heading(&account.user));
+                for v in violations {
+                    println!("  - {}", violation(v));
+                }
+            }
+        }
+
+        if failed {
+            Err(Error::FleetCheckFailed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl EnforceFleet {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        if ctx.non_interactive && !self.force {
+            return Err(Error::FleetEnforceNeedsForce);
+        }
+        let cancel = ctx.cancel_on_ctrl_c();
+        let fleet = Fleet::load(&self.config).await?;
+
+        let mut failed = false;
+        for (enforced, account) in fleet.accounts.iter().enumerate() {
+            if cancel.is_cancelled() {
+                eprintln!(
+                    "Cancelled after enforcing {enforced} of {} accounts",
+                    fleet.accounts.len(),
+                );
+                return Err(Error::Cancelled);
+            }
+
+            match self.enforce(ctx, &fleet, account).await {
+                Ok(false) => println!("{} {}", valid("[ok]  "), account.user),
+                Ok(true) => println!("{} {}", valid("[fixed]"), account.user),
+                Err(Error::Cancelled) => {
+                    failed = true;
+                    println!("{} {}", violation("[skipped]"), account.user);
+                }
+                Err(e) => {
+                    failed = true;
+                    println!("{} {}", violation("[FAIL]"), heading(&account.user));
+                    println!("  - {}", violation(format!("{e:?}")));
+                }
+            }
+        }
+
+        if failed {
+            Err(Error::FleetCheckFailed)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Submits an operation that makes `account` satisfy its policy, returning `false`
+    /// if it already did.
+    async fn enforce(
+        &self,
+        ctx: &AppContext,
+        fleet: &Fleet,
+        account: &Account,
+    ) -> Result<bool, Error> {
+        let state = State::resolve(&account.user, ctx).await?;
+        let violations = fleet.check(account, &state);
+        if violations.is_empty() {
+            return Ok(false);
+        }
+        let data = fleet.corrected(account, &state);
+
+        println!("{}", heading(format!("{}:", account.user)));
+        for v in &violations {
+            println!("  - {}", violation(v));
+        }
+        println!("Changes to the current state:");
+        print_items(&update_items(&state.inner_data().diff(&data)));
+        if !self.force {
+            print!("Submit this change? [y/N] ");
+            io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(Error::InputReadFailed)?;
+            if !line.trim().eq_ignore_ascii_case("y") {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        // Only sign with the key for accounts it can sign for.
+        let key = self
+            .key
+            .as_deref()
+            .filter(|key| state.inner_data().rotation_keys.iter().any(|k| k == key));
+        let (op, signer) = submit::sign_update(ctx, &state, data, key, None).await?;
+        submit::submit(ctx, &state, &op, signer, None).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atrium_api::types::string::Datetime;
+    use chrono::Utc;
+
+    use crate::{
+        cli::{CheckFleet, EnforceFleet},
+        context::AppContext,
+        error::Error,
+        keystore::{key_file, KeyAlgorithm, SecretKey, StoredKey},
+        remote::plc::{mock::MockDirectory, testing::TestLog},
+    };
+
+    #[tokio::test]
+    async fn check_fleet() {
+        let ok = TestLog::with_genesis();
+        let moved = TestLog::with_genesis()
+            .apply_update(|update| update.change_pds("https://pds.example.com"));
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&ok).with_log(&moved));
+
+        let config = std::env::temp_dir().join(format!("plc-fleet-{}.toml", std::process::id()));
+        let check = |users: &[&TestLog]| {
+            let mut fleet = "[policy]\npds = \"https://bsky.social\"\n".to_string();
+            for log in users {
+                fleet += &format!("[[account]]\nuser = \"{}\"\n", log.did().as_str());
+            }
+            std::fs::write(&config, fleet).unwrap();
+            CheckFleet {
+                config: config.clone(),
+            }
+        };
+
+        assert!(check(&[&ok]).run(&ctx).await.is_ok());
+        assert!(matches!(
+            check(&[&ok, &moved]).run(&ctx).await,
+            Err(Error::FleetCheckFailed),
+        ));
+
+        ctx.cancel();
+        assert!(matches!(
+            check(&[&ok]).run(&ctx).await,
+            Err(Error::Cancelled),
+        ));
+
+        std::fs::remove_file(&config).unwrap();
+    }
+
+    #[tokio::test]
+    async fn enforce_fleet() {
+        let ok = TestLog::with_genesis();
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&ok));
+
+        let config =
+            std::env::temp_dir().join(format!("plc-fleet-enforce-{}.toml", std::process::id()));
+        std::fs::write(
+            &config,
+            format!(
+                "[policy]\npds = \"https://bsky.social\"\n[[account]]\nuser = \"{}\"\n",
+                ok.did().as_str(),
+            ),
+        )
+        .unwrap();
+        let enforce = EnforceFleet {
+            config: config.clone(),
+            key: None,
+            force: true,
+        };
+
+        // Accounts that already satisfy the policy are left alone.
+        assert!(enforce.run(&ctx).await.is_ok());
+
+        std::fs::remove_file(&config).unwrap();
+    }
+
+    #[tokio::test]
+    async fn enforce_fleet_fixes_accounts() {
+        let moved = TestLog::with_genesis()
+            .apply_update(|update| update.change_pds("https://pds.example.com"));
+        let directory = MockDirectory::default().with_log(&moved);
+        let submissions = directory.submissions();
+        let ctx = AppContext::for_tests(directory);
+
+        // Hold one of the account's rotation keys in the keystore.
+        let key = SecretKey::parse(
+            &hex::encode(moved.rotation_key(0)),
+            Some(KeyAlgorithm::P256),
+        )
+        .unwrap();
+        std::env::set_var("PLC_KEY_PASSPHRASE", "hunter2");
+        StoredKey::new(&key, None, "hunter2".to_string().into())
+            .save()
+            .await
+            .unwrap();
+
+        let config =
+            std::env::temp_dir().join(format!("plc-fleet-enforce-fix-{}.toml", std::process::id()));
+        std::fs::write(
+            &config,
+            format!(
+                "[policy]\npds = \"https://bsky.social\"\n[[account]]\nuser = \"{}\"\n",
+                moved.did().as_str(),
+            ),
+        )
+        .unwrap();
+        let enforce = EnforceFleet {
+            config: config.clone(),
+            key: Some(key.did()),
+            force: true,
+        };
+
+        let res = enforce.run(&ctx).await;
+        std::fs::remove_file(&config).unwrap();
+        std::fs::remove_file(key_file(&key.did()).unwrap()).unwrap();
+        res.unwrap();
+
+        // The account was moved back to the policy's PDS, by an operation signed with
+        // the keystore key that follows its latest operation.
+        let submissions = submissions.lock().unwrap();
+        let [(did, op)] = submissions.as_slice() else {
+            panic!("expected one submission, got {}", submissions.len());
+        };
+        assert_eq!(did, &moved.did());
+        assert_eq!(op.prev(), Some(&moved.cid_for(1)));
+        assert_eq!(op.data().unwrap().endpoint(), Some("https://bsky.social"),);
+        assert!(moved
+            .audit_log()
+            .validate_next(op, Datetime::new(Utc::now().fixed_offset()))
+            .is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use atrium_api::types::string::Did;
+
+use crate::{
+    cli::{ProveHandle, SetHandle},
+    commands::submit,
+    context::AppContext,
+    data::State,
+    error::Error,
+    output::{advisory, heading, valid, violation},
+};
+
+#[cfg(feature = "server")]
+use {
+    crate::cli::ServeWellKnown,
+    axum::{http::header::CONTENT_TYPE, routing::get, Router},
+    tokio::net::TcpListener,
+};
+
+#[cfg(feature = "server")]
+impl ServeWellKnown {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let did = self.did.as_str().to_string();
+
+        if let Some(handle) = &self.handle {
+            println!(
+                "To verify @{} via DNS, create this TXT record:",
+                handle.as_str()
+            );
+            println!("  _atproto.{} TXT \"did={}\"", handle.as_str(), did);
+            println!();
+        }
+
+        let app = Router::new().route(
+            "/.well-known/atproto-did",
+            get(move || async move { ([(CONTENT_TYPE, "text/plain")], did) }),
+        );
+
+        let listener = TcpListener::bind(self.listen)
+            .await
+            .map_err(Error::ServerBindFailed)?;
+        println!(
+            "Serving /.well-known/atproto-did for {} on {}",
+            self.did.as_str(),
+            self.listen,
+        );
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(ctx.cancel_on_ctrl_c().cancelled_owned())
+            .await
+            .map_err(Error::ServerFailed)
+    }
+}
+
+impl ProveHandle {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = ctx.directory.resolve(&self.did).await?;
+        let handle = match &self.handle {
+            Some(handle) => handle.as_str().to_string(),
+            None => state.handle().ok_or(Error::DidHasNoHandle)?.to_string(),
+        };
+        let did = self.did.as_str();
+
+        println!("{}", heading(format!("To prove that {did} is @{handle}:")));
+        println!();
+        println!("Either create this DNS TXT record:");
+        println!("  _atproto.{handle} TXT \"did={did}\"");
+        println!();
+        println!("Or serve this file with `Content-Type: text/plain`:");
+        println!("  https://{handle}/.well-known/atproto-did");
+        println!("containing exactly:");
+        println!("  {did}");
+        println!();
+
+        // Handles are verified bidirectionally, so the DID must also claim the handle.
+        if state.handle() != Some(handle.as_str()) {
+            println!(
+                "{}",
+                advisory(format!(
+                    "The DID document's primary handle is not @{handle}; update it before the handle will work."
+                ))
+            );
+            println!();
+        }
+
+        let cancel = ctx.cancel_on_ctrl_c();
+        let deadline = Instant::now() + Duration::from_secs(self.timeout);
+        let mut state = Some(state);
+        let mut last = None;
+        loop {
+            let (dns, https) = tokio::join!(
+                ctx.resolver.resolve_dns_txt(&handle),
+                ctx.resolver.resolve_https_well_known(&handle),
+            );
+            let dns = Check::new(dns, &self.did);
+            let https = Check::new(https, &self.did);
+            // The DID document may be updated while we wait.
+            let state = match state.take() {
+                Some(state) => state,
+                None => ctx.directory.resolve(&self.did).await?,
+            };
+            let claimed = state.handle() == Some(handle.as_str());
+
+            let summary = format!(
+                "- DNS TXT: {dns}\n- HTTPS well-known: {https}\n- DID document: {}",
+                if claimed {
+                    valid(format!("claims @{handle}"))
+                } else {
+                    violation(format!("does not claim @{handle}"))
+                },
+            );
+            if last.as_ref() != Some(&summary) {
+                println!("{summary}");
+                last = Some(summary);
+            }
+
+            if claimed && (dns.verified || https.verified) {
+                println!();
+                println!("@{handle} is {} for {did}", valid("verified"));
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::HandleProofTimedOut);
+            }
+            tokio::select! {
+                biased;
+                () = cancel.cancelled() => return Err(Error::Cancelled),
+                () = tokio::time::sleep(Duration::from_secs(self.interval)) => (),
+            }
+        }
+    }
+}
+
+impl SetHandle {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+        let handle = self.handle.as_str();
+        if state.handle() == Some(handle) {
+            return Err(Error::HandleAlreadySet(handle.into()));
+        }
+
+        let mut data = state.inner_data().clone();
+        data.set_handle(handle);
+
+        let (op, signer) = submit::sign_update(
+            ctx,
+            &state,
+            data,
+            self.key.as_deref(),
+            self.token.as_deref(),
+        )
+        .await?;
+        submit::submit(ctx, &state, &op, signer, self.confirm_cid.as_deref()).await?;
+        println!("Changed the primary handle to @{handle}");
+
+        // Handles are verified bidirectionally, so the change only takes effect once the
+        // handle also points to the DID.
+        let did = state.did();
+        let (dns, https) = tokio::join!(
+            ctx.resolver.resolve_dns_txt(handle),
+            ctx.resolver.resolve_https_well_known(handle),
+        );
+        let dns = Check::new(dns, did);
+        let https = Check::new(https, did);
+        println!();
+        println!("- DNS TXT: {dns}");
+        println!("- HTTPS well-known: {https}");
+        if dns.verified || https.verified {
+            println!("@{handle} is {} for {}", valid("verified"), did.as_str());
+        } else {
+            println!(
+                "{}",
+                advisory(format!(
+                    "@{handle} does not resolve to {} yet; run `plc handle prove {} --handle {handle}` to set it up.",
+                    did.as_str(),
+                    did.as_str(),
+                )),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of checking one handle resolution method.
+struct Check {
+    verified: bool,
+    description: String,
+}
+
+impl Check {
+    fn new<E: fmt::Display>(res: Result<Did, E>, expected: &Did) -> Self {
+        match res {
+            Ok(did) if &did == expected => Self {
+                verified: true,
+                description: valid("verified").to_string(),
+            },
+            Ok(did) => Self {
+                verified: false,
+                description: violation(format!("points to {}", did.as_str())).to_string(),
+            },
+            Err(e) => Self {
+                verified: false,
+                description: advisory(e).to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{
+    cli::TestHooks, commands::did::read_operation, context::AppContext, error::Error, output::valid,
+};
+
+impl TestHooks {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let op = read_operation(&self.operation, self.cbor).await?;
+        let did = self.did.clone().or_else(|| op.derived_did());
+
+        if ctx.config.hooks.pre_sign.is_empty() {
+            println!("No pre-sign hooks are configured");
+            return Ok(());
+        }
+
+        ctx.config
+            .hooks
+            .pre_sign(
+                did.as_ref(),
+                &serde_json::to_value(&op).expect("can serialize"),
+            )
+            .await?;
+        println!(
+            "All {} pre-sign hooks {} the operation",
+            ctx.config.hooks.pre_sign.len(),
+            valid("accepted"),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use atrium_api::types::string::{Datetime, Did};
+use chrono::Utc;
+use tokio::fs;
+
+use super::{
+    ops::signer_label,
+    submit::{self, Signer},
+};
+use crate::{
+    cli::{RecoverOp, StartIncident},
+    context::AppContext,
+    data::{Key, State},
+    error::Error,
+    incident::{Incident, Step},
+    keystore::StoredKey,
+    known_keys::KnownKeys,
+    output::{advisory, format_time, heading, valid, violation},
+    remote::{
+        pds,
+        plc::{SpecProfile, UnsignedOperation, ValidatedEntry, ValidatedLog},
+    },
+};
+
+impl StartIncident {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        if ctx.non_interactive && !self.force {
+            return Err(Error::IncidentNeedsForce);
+        }
+        let state = State::resolve(&self.user, ctx).await?;
+        let did = state.did().clone();
+        let profile = SpecProfile::default();
+
+        let mut incident = match Incident::load(&did).await? {
+            Some(incident) => {
+                println!(
+                    "Resuming the incident response for {} started {}",
+                    did.as_str(),
+                    format_time(&incident.started),
+                );
+                incident
+            }
+            None => {
+                let key = match &self.key {
+                    Some(key) => key.clone(),
+                    None => {
+                        let log = ctx.directory.audit_log(&did).await?;
+                        let validated = log.validated().map_err(|_| Error::AuditLogInvalid)?;
+                        choose_key(&validated, &state, &profile).await?
+                    }
+                };
+                let incident = Incident::new(key);
+                incident.save(&did).await?;
+                incident
+            }
+        };
+        println!("Responding with rotation key {}", incident.key);
+        for step in &incident.steps {
+            println!("- {step}: {}", valid("done"), step = step.step);
+        }
+
+        if !incident.is_done(Step::Audit) {
+            println!();
+            println!("{}", heading(format!("{}:", Step::Audit)));
+            let log = ctx.directory.fetch_audit_log(&did).await?;
+            let validated = match log.validated() {
+                Ok(validated) => validated,
+                Err(errors) => {
+                    for e in errors {
+                        println!("- {}", violation(e));
+                    }
+                    return Err(Error::AuditLogInvalid);
+                }
+            };
+
+            let now = Utc::now().fixed_offset();
+            let suspicious = validated.nullifiable_by(&incident.key, &profile, &now);
+            let outcome = if suspicious.is_empty() {
+                format!("No operations can be nullified with {}", incident.key)
+            } else {
+                let server_keys = pds::get_server_keys_if_logged_in(&state).await?;
+                let known_keys = KnownKeys::load().await?;
+
+                // Keys that the user or their PDS holds signed legitimate changes, so
+                // they are never treated as compromised.
+                let mut trusted = BTreeSet::new();
+                for entry in &suspicious {
+                    let Some(signer) = entry.signer else { continue };
+                    let key = &entry.authorized_keys[signer];
+                    if *key == incident.key
+                        || server_keys.as_ref().is_some_and(|keys| {
+                            Key::did(key).is_ok_and(|key| keys.contains_rotation(&key))
+                        })
+                        || known_keys.label_for_did(key).is_some()
+                        || StoredKey::load(key).await.is_ok()
+                    {
+                        trusted.insert(key.clone());
+                    }
+                }
+                let is_trusted = |entry: &ValidatedEntry| {
+                    entry
+                        .signer
+                        .is_some_and(|signer| trusted.contains(&entry.authorized_keys[signer]))
+                };
+
+                println!("Operations that can still be nullified:");
+                for entry in &suspicious {
+                    println!(
+                        "- {} ({}): {}{}",
+                        format_time(&entry.created_at),
+                        entry.cid.as_ref(),
+                        signer_label(
+                            entry.signer,
+                            &entry.authorized_keys,
+                            server_keys.as_ref(),
+                            &known_keys,
+                        ),
+                        if is_trusted(entry) { " (trusted)" } else { "" },
+                    );
+                }
+
+                let first = match &self.cid {
+                    Some(cid) => Some(
+                        suspicious
+                            .iter()
+                            .find(|entry| entry.cid.as_ref().to_string() == *cid)
+                            .ok_or_else(|| Error::OperationNotRecoverable(cid.clone()))?,
+                    ),
+                    None => suspicious.iter().find(|entry| !is_trusted(entry)),
+                };
+                match first {
+                    None => "Every operation that can be nullified was signed by a trusted key"
+                        .to_string(),
+                    Some(first) => {
+                        let malicious = suspicious
+                            .iter()
+                            .skip_while(|entry| entry.cid != first.cid)
+                            .collect::<Vec<_>>();
+                        let compromised = malicious
+                            .iter()
+                            .filter(|entry| !is_trusted(entry))
+                            .filter_map(|entry| {
+                                entry
+                                    .signer
+                                    .map(|signer| entry.authorized_keys[signer].clone())
+                            })
+                            .collect::<BTreeSet<_>>();
+
+                        println!();
+                        println!(
+                            "The earliest malicious operation is {}; it and the {} after it will be nullified.",
+                            first.cid.as_ref(),
+                            malicious.len() - 1,
+                        );
+                        if compromised.is_empty() {
+                            println!("None of them were signed by an untrusted key.");
+                        } else {
+                            println!("These rotation keys will be treated as compromised:");
+                            for key in &compromised {
+                                println!("- {key}");
+                            }
+                        }
+                        if malicious.iter().any(|entry| is_trusted(entry)) {
+                            println!(
+                                "{}",
+                                advisory("Some of these operations were signed by trusted keys; use --cid to choose a later operation instead.")
+                            );
+                        }
+                        if !self.force {
+                            print!("Is this correct? [y/N] ");
+                            io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+                            let mut line = String::new();
+                            io::stdin()
+                                .lock()
+                                .read_line(&mut line)
+                                .map_err(Error::InputReadFailed)?;
+                            if !line.trim().eq_ignore_ascii_case("y") {
+                                return Err(Error::Cancelled);
+                            }
+                        }
+
+                        incident.nullify_from = Some(first.cid.as_ref().to_string());
+                        incident.compromised_keys = compromised.into_iter().collect();
+                        if incident.compromised_keys.is_empty() {
+                            format!("Found {} operations to nullify", malicious.len())
+                        } else {
+                            format!(
+                                "Found {} operations to nullify, signed by {}",
+                                malicious.len(),
+                                incident.compromised_keys.join(", "),
+                            )
+                        }
+                    }
+                }
+            };
+            println!("{outcome}");
+            incident.complete(&did, Step::Audit, outcome).await?;
+        }
+
+        if !incident.is_done(Step::Recover) {
+            println!();
+            println!("{}", heading(format!("{}:", Step::Recover)));
+            let outcome = match &incident.nullify_from {
+                Some(cid) => {
+                    let recover = RecoverOp {
+                        user: did.as_str().into(),
+                        key: incident.key.clone(),
+                        cid: Some(cid.clone()),
+                        force: self.force,
+                        confirm_cid: self.confirm_cid.clone(),
+                    };
+                    recover.run(ctx).await?;
+                    "Nullified the operations".to_string()
+                }
+                None => {
+                    println!("There is nothing to nullify");
+                    "There was nothing to nullify".to_string()
+                }
+            };
+            incident.complete(&did, Step::Recover, outcome).await?;
+        }
+
+        if !incident.is_done(Step::RotateKeys) {
+            println!();
+            println!("{}", heading(format!("{}:", Step::RotateKeys)));
+            let outcome = self.rotate_keys(ctx, &did, &incident).await?;
+            println!("{outcome}");
+            incident.complete(&did, Step::RotateKeys, outcome).await?;
+        }
+
+        println!();
+        println!("{}", heading(format!("{}:", Step::Report)));
+        let timeline = timeline(ctx, &state, &incident, &profile).await?;
+        match &self.report {
+            Some(path) => {
+                fs::write(path, &timeline)
+                    .await
+                    .map_err(Error::OutputWriteFailed)?;
+                println!("Wrote the timeline to {}", path.display());
+            }
+            None => print!("{timeline}"),
+        }
+        Incident::close(&did).await?;
+
+        println!();
+        println!("Incident response {}", valid("complete"));
+        Ok(())
+    }
+
+    /// Removes the compromised rotation keys that the DID still has.
+    async fn rotate_keys(
+        &self,
+        ctx: &AppContext,
+        did: &Did,
+        incident: &Incident,
+    ) -> Result<String, Error> {
+        let state = State::resolve(did.as_str(), ctx).await?;
+        let mut data = state.inner_data().clone();
+        let remove = incident
+            .compromised_keys
+            .iter()
+            .filter(|key| **key != incident.key && data.rotation_keys.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        if remove.is_empty() {
+            return Ok("No compromised rotation keys remain".into());
+        }
+
+        for key in &remove {
+            println!("- Remove {key}");
+        }
+        let server_keys = pds::get_server_keys_if_logged_in(&state).await?;
+        if server_keys.is_some_and(|keys| {
+            remove
+                .iter()
+                .any(|key| Key::did(key).is_ok_and(|key| keys.contains_rotation(&key)))
+        }) {
+            println!(
+                "{}",
+                advisory("Your PDS holds one of these keys, so it will no longer be able to change your identity.")
+            );
+        }
+        if !self.force {
+            print!("Remove these rotation keys? [y/N] ");
+            io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(Error::InputReadFailed)?;
+            if !line.trim().eq_ignore_ascii_case("y") {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        data.rotation_keys.retain(|key| !remove.contains(key));
+        let head = ctx
+            .directory
+            .audit_log(did)
+            .await?
+            .head()
+            .cloned()
+            .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+        let op = submit::sign_locally(
+            ctx,
+            &state,
+            UnsignedOperation::update(head, data),
+            &state.inner_data().rotation_keys,
+            &incident.key,
+        )
+        .await?;
+        submit::submit(
+            ctx,
+            &state,
+            &op,
+            Signer::Key(incident.key.clone()),
+            self.confirm_cid.as_deref(),
+        )
+        .await?;
+
+        Ok(format!("Removed rotation keys {}", remove.join(", ")))
+    }
+}
+
+/// Chooses the highest-priority rotation key in the keystore that can nullify the
+/// earliest operation, or if none can nullify anything, the highest-priority current
+/// rotation key in the keystore.
+async fn choose_key(
+    validated: &ValidatedLog,
+    state: &State,
+    profile: &SpecProfile,
+) -> Result<String, Error> {
+    let mut seen = BTreeSet::new();
+    let mut local = vec![];
+    for key in validated
+        .entries
+        .iter()
+        .flat_map(|entry| entry.authorized_keys.iter())
+        .chain(&state.inner_data().rotation_keys)
+    {
+        if seen.insert(key) && StoredKey::load(key).await.is_ok() {
+            local.push(key);
+        }
+    }
+
+    let now = Utc::now().fixed_offset();
+    let position = |cid| validated.entries.iter().position(|entry| &entry.cid == cid);
+    let recovery_key = local
+        .iter()
+        .filter_map(|key| {
+            let earliest = *validated.nullifiable_by(key, profile, &now).first()?;
+            let authority = earliest.authorized_keys.iter().position(|k| k == *key);
+            Some(((position(&earliest.cid), authority), *key))
+        })
+        .min()
+        .map(|(_, key)| key);
+
+    recovery_key
+        .or_else(|| {
+            state
+                .inner_data()
+                .rotation_keys
+                .iter()
+                .find(|key| local.contains(key))
+        })
+        .cloned()
+        .ok_or(Error::IncidentNoLocalKey)
+}
+
+/// Describes the incident and the operations around it, in time order.
+async fn timeline(
+    ctx: &AppContext,
+    state: &State,
+    incident: &Incident,
+    profile: &SpecProfile,
+) -> Result<String, Error> {
+    let log = ctx.directory.audit_log(state.did()).await?;
+    let validated = log.validated().map_err(|_| Error::AuditLogInvalid)?;
+    let server_keys = pds::get_server_keys_if_logged_in(state).await?;
+    let known_keys = KnownKeys::load().await?;
+
+    // Only operations that could have been nullified when the incident started are
+    // relevant, along with everything since.
+    let since = *incident.started.as_ref() - profile.recovery_window;
+    let mut events = validated
+        .entries
+        .iter()
+        .filter(|entry| *entry.created_at.as_ref() >= since)
+        .map(|entry| {
+            (
+                entry.created_at.clone(),
+                format!(
+                    "Operation {} was created, {}{}",
+                    entry.cid.as_ref(),
+                    signer_label(
+                        entry.signer,
+                        &entry.authorized_keys,
+                        server_keys.as_ref(),
+                        &known_keys,
+                    ),
+                    if entry.nullified.is_some() {
+                        " (since nullified)"
+                    } else {
+                        ""
+                    },
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    events.push((incident.started.clone(), "Incident response started".into()));
+    events.extend(incident.steps.iter().map(|step| {
+        (
+            step.completed.clone(),
+            format!("{} step completed: {}", step.step, step.outcome),
+        )
+    }));
+    events.push((
+        Datetime::new(Utc::now().fixed_offset()),
+        "Incident response completed".into(),
+    ));
+    events.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+
+    let mut timeline = format!("Incident timeline for {}\n", state.did().as_str());
+    for (time, event) in events {
+        timeline += &format!("- {}: {event}\n", format_time(&time));
+    }
+    Ok(timeline)
+}
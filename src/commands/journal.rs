@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+use tokio::fs;
+
+use crate::{
+    cli::{ExportJournal, ListJournal, ShowJournal},
+    context::AppContext,
+    error::Error,
+    journal::{self, Action, Outcome},
+    output::{format_time, index_width, valid, violation},
+};
+
+impl ListJournal {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let entries = journal::load().await?;
+        let width = index_width(entries.len());
+
+        let mut listed = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            if self
+                .did
+                .as_ref()
+                .is_some_and(|did| did.as_str() != entry.did)
+            {
+                continue;
+            }
+            listed += 1;
+
+            let action = match entry.action {
+                Action::Signed => "Signed",
+                Action::Submitted => "Submitted",
+            };
+            let outcome = match &entry.outcome {
+                Outcome::Succeeded => valid("ok").to_string(),
+                Outcome::Failed(e) => violation(format!("failed: {e}")).to_string(),
+            };
+            println!(
+                "{:width$} {} {action} {} for {} with {} ({outcome})",
+                format!("[{i}]"),
+                format_time(&entry.time),
+                entry.cid,
+                entry.did,
+                entry.signer,
+            );
+        }
+
+        if listed == 0 {
+            println!("No journal entries");
+        }
+
+        Ok(())
+    }
+}
+
+impl ShowJournal {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let entry = journal::load()
+            .await?
+            .into_iter()
+            .nth(self.index)
+            .ok_or(Error::JournalEntryNotFound(self.index))?;
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&entry).expect("can serialize")
+        );
+        Ok(())
+    }
+}
+
+impl ExportJournal {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let data = journal::load()
+            .await?
+            .iter()
+            .map(|entry| serde_json::to_string(entry).expect("can serialize") + "\n")
+            .collect::<String>();
+
+        match &self.output {
+            Some(path) => fs::write(path, data).await,
+            None => io::stdout().write_all(data.as_bytes()),
+        }
+        .map_err(Error::OutputWriteFailed)
+    }
+}
@@ -1,10 +1,32 @@
-use crate::{cli::ListKeys, data::State, error::Error, remote::pds};
+use std::io::{self, BufRead, Read, Write};
 
-impl ListKeys {
-    pub(crate) async fn run(&self) -> Result<(), Error> {
-        let client = reqwest::Client::new();
+use chrono::Utc;
+use tokio::{fs, io::AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use crate::{
+    cli::{
+        AddKnownKey, AddRotationKey, ExportKey, GenerateKey, ImportKey, ListKeys, ListKnownKeys,
+        RemoveKnownKey, RemoveRotationKey, RotationKeyRef,
+    },
+    commands::submit,
+    context::AppContext,
+    data::{AccountStatus, Key, State},
+    delegations,
+    error::Error,
+    keystore::{SecretKey, StoredKey},
+    known_keys::{self, KnownKeys},
+    local,
+    output::{advisory, format_time, heading, index_width, label_width, violation},
+    remote::pds,
+    util,
+    warning::Warning,
+};
 
-        let state = State::resolve(&self.user, &client).await?;
+impl ListKeys {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+        let known_keys = KnownKeys::load().await?;
 
         let pds = state.endpoint().ok_or(Error::DidDocumentHasNoPds)?;
 
@@ -15,39 +37,44 @@ impl ListKeys {
             let server_keys = agent.get_recommended_server_keys().await?;
 
             match &server_keys.signing {
-                None => println!("WARNING: PDS did not recommend a signing key!"),
-                Some(Err(e)) => println!("WARNING: PDS recommended an invalid signing key! {}", e),
+                None => ctx.warn(Warning::PdsRecommendedNoSigningKey),
+                Some(Err(e)) => ctx.warn(Warning::PdsRecommendedInvalidSigningKey(e.to_string())),
                 Some(Ok(_)) => (),
             }
             for (i, res) in server_keys.rotation.iter().enumerate() {
                 if let Err(e) = res {
-                    println!(
-                        "WARNING: PDS recommended an invalid rotation key at position {i}! {}",
-                        e,
-                    );
+                    ctx.warn(Warning::PdsRecommendedInvalidRotationKey(i, e.to_string()));
                 }
             }
 
             Some(server_keys)
         } else {
             println!(
-                "Not currently authenticated to {}; can't fetch PDS keys",
-                self.user
+                "{}",
+                advisory(format!(
+                    "Not currently authenticated to {}; can't fetch PDS keys",
+                    self.user
+                ))
             );
             println!();
             None
         };
 
-        println!("Account {}", state.did().as_str());
+        println!("{}", heading(format!("Account {}", state.did().as_str())));
         if let Some(handle) = state.handle() {
             println!("- Primary handle: @{}", handle);
         } else {
-            println!("- Invalid handle");
+            println!("- {}", violation("Invalid handle"));
         }
         println!("- PDS: {}", pds);
+        match agent.get_repo_status(state.did()).await {
+            Ok(AccountStatus::Active) => println!("- Account status: Active"),
+            Ok(status) => println!("- Account status: {}", advisory(status)),
+            Err(e) => println!("- Account status: {}", advisory(format!("unknown ({e:?})"))),
+        }
 
         match state.signing_key() {
-            None => println!("- No signing key"),
+            None => println!("- {}", advisory("No signing key")),
             Some(Ok(k))
                 if server_keys
                     .as_ref()
@@ -57,18 +84,32 @@ impl ListKeys {
                 println!("- Signing key: PDS ({:?})", k.algorithm);
             }
             Some(Ok(k)) => {
-                println!(
-                    "- Signing key: Unknown ({:?}): {}",
-                    k.algorithm,
-                    hex::encode(&k.public_key)
-                );
+                if let Some(label) = known_keys.label(&k) {
+                    println!("- Signing key: {label} ({:?})", k.algorithm);
+                } else {
+                    println!(
+                        "- Signing key: Unknown ({:?}): {}",
+                        k.algorithm,
+                        hex::encode(&k.public_key)
+                    );
+                }
             }
-            Some(Err(e)) => println!("- Invalid signing key: {}", e),
+            Some(Err(e)) => println!("- {}", violation(format!("Invalid signing key: {e}"))),
         }
 
+        let temporary = delegations::for_did(state.did(), state.inner_data()).await?;
+        let now = Utc::now().fixed_offset();
+
         let rotation_keys = state.rotation_keys();
+        if let (Some(server_keys), Some(Ok(top))) = (&server_keys, rotation_keys.first()) {
+            if server_keys.contains_rotation(top) {
+                ctx.warn(Warning::PdsHoldsTopRotationKey);
+            }
+        }
+        let width = index_width(rotation_keys.len());
         println!("- {} rotation keys:", rotation_keys.len());
         for (i, res) in rotation_keys.iter().enumerate() {
+            let index = format!("[{i}]");
             match res {
                 Ok(k)
                     if server_keys
@@ -76,20 +117,248 @@ impl ListKeys {
                         .map(|keys| keys.contains_rotation(k))
                         .unwrap_or(false) =>
                 {
-                    println!("  - [{}] PDS ({:?})", i, k.algorithm);
+                    println!("  - {index:width$} PDS ({:?})", k.algorithm);
                 }
                 Ok(k) => {
-                    println!(
-                        "  - [{}] Unknown ({:?}): {}",
-                        i,
-                        k.algorithm,
-                        hex::encode(&k.public_key),
-                    );
+                    if let Some(label) = known_keys.label(k) {
+                        println!("  - {index:width$} {label} ({:?})", k.algorithm);
+                    } else {
+                        println!(
+                            "  - {index:width$} Unknown ({:?}): {}",
+                            k.algorithm,
+                            hex::encode(&k.public_key),
+                        );
+                    }
                 }
-                Err(e) => println!("  - [{}] Invalid: {}", i, e),
+                Err(e) => println!("  - {index:width$} {}", violation(format!("Invalid: {e}"))),
+            }
+            match temporary.get(&state.inner_data().rotation_keys[i]) {
+                Some(delegation) if delegation.is_expired(&now) => println!(
+                    "    {}",
+                    violation(format!(
+                        "Temporary key expired {}; remove it!",
+                        format_time(&delegation.expires),
+                    )),
+                ),
+                Some(delegation) => println!(
+                    "    {}",
+                    advisory(format!(
+                        "Temporary key, expires {}",
+                        format_time(&delegation.expires),
+                    )),
+                ),
+                None => (),
             }
         }
 
         Ok(())
     }
 }
+
+impl AddRotationKey {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        let mut data = state.inner_data().clone();
+        if data.rotation_keys.contains(&self.key) {
+            return Err(Error::RotationKeyAlreadyPresent(self.key.clone()));
+        }
+        let index = self.index.min(data.rotation_keys.len());
+        data.rotation_keys.insert(index, self.key.clone());
+
+        let (op, signer) = submit::sign_via_pds(ctx, &state, &data, self.token.as_deref()).await?;
+        submit::submit(ctx, &state, &op, signer, self.confirm_cid.as_deref()).await?;
+
+        println!("Added {} as rotation key [{index}]", self.key);
+        if let Some(expires) = &self.expires {
+            delegations::add(state.did(), self.key.clone(), expires.clone()).await?;
+            println!(
+                "{}",
+                advisory(format!(
+                    "The key is temporary; plc will remind you to remove it after {}",
+                    format_time(expires),
+                ))
+            );
+        }
+        Ok(())
+    }
+}
+
+impl GenerateKey {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let passphrase = util::passphrase("Key", "PLC_KEY_PASSPHRASE", ctx.non_interactive, true)?;
+        let key = StoredKey::new(
+            &SecretKey::generate(self.algorithm),
+            self.label.clone(),
+            passphrase,
+        );
+        key.save().await?;
+        if let Some(label) = &self.label {
+            known_keys::add(key.did.clone(), label.clone()).await?;
+        }
+
+        println!("Generated a new {:?} key:", self.algorithm);
+        println!("  {}", key.did);
+        println!();
+        println!("To add it as a rotation key, run:");
+        println!("  plc keys add-rotation <user> {}", key.did);
+        Ok(())
+    }
+}
+
+impl ImportKey {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let input = if self.input.as_os_str() == "-" {
+            let mut data = String::new();
+            io::stdin().read_to_string(&mut data).map(|_| data)
+        } else {
+            fs::read_to_string(&self.input).await
+        }
+        .map(Zeroizing::new)
+        .map_err(Error::InputReadFailed)?;
+
+        let secret = SecretKey::parse(&input, self.algorithm)?;
+        let passphrase = util::passphrase("Key", "PLC_KEY_PASSPHRASE", ctx.non_interactive, true)?;
+        let key = StoredKey::new(&secret, self.label.clone(), passphrase);
+        key.save().await?;
+        if let Some(label) = &self.label {
+            known_keys::add(key.did.clone(), label.clone()).await?;
+        }
+
+        println!("Imported {:?} key {}", key.algorithm, key.did);
+        Ok(())
+    }
+}
+
+impl ExportKey {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let key = StoredKey::load(&self.key).await?;
+        let passphrase = util::passphrase("Key", "PLC_KEY_PASSPHRASE", ctx.non_interactive, false)?;
+        let encoded = key.decrypt(passphrase)?.encode(self.format);
+
+        match &self.output {
+            // Don't replace an existing file, or let anyone else read the key.
+            Some(path) => match local::create_private(path).await {
+                Ok(mut file) => {
+                    let data = Zeroizing::new(format!("{}\n", encoded.as_str()));
+                    file.write_all(data.as_bytes()).await
+                }
+                Err(e) => Err(e),
+            },
+            None => writeln!(io::stdout().lock(), "{}", encoded.as_str()),
+        }
+        .map_err(Error::OutputWriteFailed)?;
+
+        eprintln!(
+            "{}",
+            advisory("This is an unencrypted private key; anyone who sees it controls the key!")
+        );
+        Ok(())
+    }
+}
+
+impl RemoveRotationKey {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        let mut data = state.inner_data().clone();
+        let index = match &self.key {
+            RotationKeyRef::Index(i) if *i < data.rotation_keys.len() => *i,
+            RotationKeyRef::Index(i) => return Err(Error::RotationKeyNotFound(format!("[{i}]"))),
+            RotationKeyRef::Key(key) => data
+                .rotation_keys
+                .iter()
+                .position(|k| k == key)
+                .ok_or_else(|| Error::RotationKeyNotFound(key.clone()))?,
+        };
+        if data.rotation_keys.len() == 1 {
+            return Err(Error::RotationKeyIsLast);
+        }
+        let key = data.rotation_keys.remove(index);
+
+        // Warn if this would leave the PDS as the only party able to recover the DID.
+        // The removal doesn't need the PDS, so if we can't ask it which keys it holds,
+        // every key is assumed to be self-controlled.
+        let server_keys = match pds::get_server_keys_if_logged_in(&state).await {
+            Ok(server_keys) => server_keys,
+            Err(e) => {
+                ctx.warn(Warning::PdsServerKeysUnknown(format!("{e:?}")));
+                None
+            }
+        };
+        let self_controlled = |k: &String| {
+            !server_keys
+                .as_ref()
+                .zip(Key::did(k).ok())
+                .is_some_and(|(keys, k)| keys.contains_rotation(&k))
+        };
+        let last_recovery_key =
+            self_controlled(&key) && !data.rotation_keys.iter().any(self_controlled);
+
+        if last_recovery_key {
+            ctx.warn(Warning::LastSelfControlledRotationKey(key.clone()));
+        }
+        ctx.report_warnings()?;
+        if !self.force {
+            if ctx.non_interactive {
+                return Err(Error::RotationKeyRemovalNeedsForce);
+            } else {
+                print!("Remove rotation key [{index}] {key}? [y/N] ");
+                io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+                let mut line = String::new();
+                io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .map_err(Error::InputReadFailed)?;
+                if !line.trim().eq_ignore_ascii_case("y") {
+                    return Err(Error::RotationKeyRemovalCancelled);
+                }
+            }
+        }
+
+        let (op, signer) = submit::sign_via_pds(ctx, &state, &data, self.token.as_deref()).await?;
+        submit::submit(ctx, &state, &op, signer, self.confirm_cid.as_deref()).await?;
+        delegations::remove(state.did(), &key).await?;
+
+        println!("Removed rotation key [{index}] {key}");
+        Ok(())
+    }
+}
+
+impl ListKnownKeys {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let (builtin, user) = known_keys::datasets().await?;
+
+        for (name, dataset) in [("Built-in", builtin), ("User-defined", user)] {
+            println!("{}", heading(format!("{name} key labels:")));
+            let width = label_width(dataset.keys().map(|s| s.as_str())) + 1;
+            for (key, label) in &dataset {
+                println!("- {:width$} {label}", format!("{key}:"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AddKnownKey {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        known_keys::add(self.key.clone(), self.label.clone()).await?;
+        println!("Labelled {} as {}", self.key, self.label);
+        Ok(())
+    }
+}
+
+impl RemoveKnownKey {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        if known_keys::remove(&self.key).await? {
+            println!("Removed label for {}", self.key);
+        } else {
+            println!(
+                "{}",
+                advisory(format!("{} has no user-defined label", self.key))
+            );
+        }
+        Ok(())
+    }
+}
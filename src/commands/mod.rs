@@ -1,3 +1,22 @@
 mod auth;
+mod compare;
+mod config;
+mod did;
+mod directory;
+mod explain;
+mod fleet;
+mod handle;
+mod hooks;
+mod incident;
+mod journal;
 mod keys;
 mod ops;
+mod pds;
+mod pin;
+mod policy;
+mod profile;
+#[cfg(feature = "server")]
+mod resolver;
+mod self_test;
+mod submit;
+mod verify;
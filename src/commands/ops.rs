@@ -1,151 +1,407 @@
 use crate::{
     cli::{AuditOps, ListOps},
-    data::{PlcData, State},
+    context::AppContext,
+    data::{Key, PlcData, PlcDataDiff, State},
     error::Error,
-    remote::plc,
+    known_keys::KnownKeys,
+    local,
+    output::{
+        advisory, format_time, heading, index_width, label_width, print_items, valid, violation,
+        Item,
+    },
+    remote::{
+        pds::{self, ServerKeys},
+        plc,
+    },
 };
 
-impl ListOps {
-    pub(crate) async fn run(&self) -> Result<(), Error> {
-        let client = reqwest::Client::new();
-
-        let state = State::resolve(&self.user, &client).await?;
+mod compare_sources;
+mod format;
+mod graph;
+mod recover;
+mod report;
+mod submit;
+mod tombstone;
+mod verify_entry;
 
-        let log = plc::get_ops_log(state.did(), &client).await?;
+impl ListOps {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
 
-        let print_state = |data: &PlcData| {
-            println!("- Rotation keys:");
-            for (i, key) in data.rotation_keys.iter().enumerate() {
-                println!("  - [{i}] {key}");
-            }
-            println!("- Verification methods:");
-            for (id, value) in &data.verification_methods {
-                println!("  - {id}: {value}");
-            }
-            println!("- Also-known-as:");
-            for (i, aka) in data.also_known_as.iter().enumerate() {
-                println!("  - [{i}] {aka}");
-            }
-            println!("- Services:");
-            for (id, service) in &data.services {
-                println!("  - {id}: {} = {}", service.r#type, service.endpoint);
-            }
+        // We fetch the audit log instead of the operation log, because only the former
+        // includes the time at which each operation was created.
+        let audit_log = ctx.directory.audit_log(state.did()).await?;
+        let log = plc::OperationsLog::from_audit_log(&audit_log)?;
+        let authorship = audit_log.authorship();
+        // The PDS keys only annotate the listing, so don't fail if the PDS does.
+        let server_keys = pds::get_server_keys_if_logged_in(&state)
+            .await
+            .ok()
+            .flatten();
+        let known_keys = KnownKeys::load().await?;
+        let signer = |i: usize| {
+            authorship
+                .get(i)
+                .map(|authorship| {
+                    signer_label(
+                        authorship.signer,
+                        &authorship.authorized_keys,
+                        server_keys.as_ref(),
+                        &known_keys,
+                    )
+                })
+                .unwrap_or_default()
         };
 
-        println!("Account {}", state.did().as_str());
+        println!("{}", heading(format!("Account {}", state.did().as_str())));
         println!();
-        println!("Initial state:");
-        print_state(&log.create);
+        println!(
+            "{} (created {}; {})",
+            heading("Initial state:"),
+            format_time(&log.created_at),
+            signer(0),
+        );
+        print_items(&state_items(&log.create));
 
-        for (i, update) in log.updates.iter().enumerate() {
+        for (i, (created_at, update)) in log.updates.iter().enumerate() {
             println!();
-            println!("Update {}:", i + 1);
+            println!(
+                "{} (created {}; {})",
+                heading(format!("Update {}:", i + 1)),
+                format_time(created_at),
+                signer(i + 1),
+            );
+            print_items(&update_items(update));
+        }
 
-            for rkey in &update.rotation_keys.0 {
-                match rkey {
-                    diff::VecDiffType::Inserted { index, changes } => {
-                        if *index == 0 {
-                            println!("- Inserted before rotation key [{index}]:");
-                        } else {
-                            println!("- Inserted after rotation key [{}]:", index - 1);
-                        }
-                        for change in changes.iter().flatten() {
-                            println!("  - {change}");
-                        }
-                    }
-                    diff::VecDiffType::Altered { index, changes } => {
-                        for (i, change) in changes.iter().enumerate() {
-                            if let Some(value) = change {
-                                println!("- Changed rotation key [{}] to {}", index + i, value);
-                            }
-                        }
-                    }
-                    diff::VecDiffType::Removed { index, len } => {
-                        for i in *index..(index + len) {
-                            println!("- Removed rotation key [{i}]");
-                        }
+        println!();
+        if let Some(tombstoned) = &log.tombstoned {
+            println!(
+                "{} {} ({}; {})",
+                heading("Current state:"),
+                advisory("Tombstoned"),
+                format_time(tombstoned),
+                signer(log.updates.len() + 1),
+            );
+        } else {
+            println!("{}", heading("Current state:"));
+            print_items(&state_items(state.inner_data()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes which rotation key signed an operation, and who holds it if known.
+pub(super) fn signer_label(
+    signer: Option<usize>,
+    authorized_keys: &[String],
+    server_keys: Option<&ServerKeys>,
+    known_keys: &KnownKeys,
+) -> String {
+    match signer {
+        None => violation("not signed by an authorized rotation key").to_string(),
+        Some(i) => {
+            let key = &authorized_keys[i];
+            let pds = match server_keys {
+                None => "",
+                Some(keys) => match Key::did(key) {
+                    Ok(key) if keys.contains_rotation(&key) => ", a current PDS key",
+                    _ => ", not a current PDS key",
+                },
+            };
+            let holder = known_keys
+                .label_for_did(key)
+                .map(|label| format!(", held by {label}"))
+                .unwrap_or_default();
+            format!("signed by rotation key [{i}]{pds}{holder}")
+        }
+    }
+}
+
+/// Describes the given DID state.
+fn state_items(data: &PlcData) -> Vec<Item> {
+    let width = index_width(data.rotation_keys.len());
+    let rotation_keys = data
+        .rotation_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| format!("{:width$} {key}", format!("[{i}]")))
+        .collect();
+
+    let width = label_width(data.verification_methods.keys().map(|s| s.as_str())) + 1;
+    let verification_methods = data
+        .verification_methods
+        .iter()
+        .map(|(id, value)| format!("{:width$} {value}", format!("{id}:")))
+        .collect();
+
+    let width = index_width(data.also_known_as.len());
+    let also_known_as = data
+        .also_known_as
+        .iter()
+        .enumerate()
+        .map(|(i, aka)| format!("{:width$} {aka}", format!("[{i}]")))
+        .collect();
+
+    let width = label_width(data.services.keys().map(|s| s.as_str())) + 1;
+    let services = data
+        .services
+        .iter()
+        .map(|(id, service)| {
+            format!(
+                "{:width$} {} = {}",
+                format!("{id}:"),
+                service.r#type,
+                service.endpoint,
+            )
+        })
+        .collect();
+
+    vec![
+        Item::new("Rotation keys:", rotation_keys),
+        Item::new("Verification methods:", verification_methods),
+        Item::new("Also-known-as:", also_known_as),
+        Item::new("Services:", services),
+    ]
+}
+
+/// Describes the changes made by an update operation.
+pub(super) fn update_items(update: &PlcDataDiff) -> Vec<Item> {
+    let mut items = vec![];
+
+    vec_diff_items(&mut items, &update.rotation_keys.0, "rotation key ");
+
+    for (key, change) in &update.verification_methods.altered {
+        if let Some(value) = change {
+            items.push(Item::leaf(format!(
+                "Changed verification method {key} to {value}"
+            )));
+        }
+    }
+    for key in &update.verification_methods.removed {
+        items.push(Item::leaf(format!("Removed verification method {key}")));
+    }
+
+    vec_diff_items(&mut items, &update.also_known_as.0, "Also-known-as");
+
+    for (id, change) in &update.services.altered {
+        if let Some(value) = &change.r#type {
+            items.push(Item::leaf(format!("Changed service {id} type to {value}")));
+        }
+        if let Some(value) = &change.endpoint {
+            items.push(Item::leaf(format!(
+                "Changed service {id} endpoint to {value}"
+            )));
+        }
+    }
+    for id in &update.services.removed {
+        items.push(Item::leaf(format!("Removed service {id}")));
+    }
+
+    items
+}
+
+/// Describes the changes to a list-valued field, where elements are referred to as
+/// `{name}[{index}]`.
+fn vec_diff_items(items: &mut Vec<Item>, changes: &[diff::VecDiffType<String>], name: &str) {
+    for change in changes {
+        match change {
+            diff::VecDiffType::Inserted { index, changes } => {
+                let text = if *index == 0 {
+                    format!("Inserted before {name}[{index}]:")
+                } else {
+                    format!("Inserted after {name}[{}]:", index - 1)
+                };
+                items.push(Item::new(text, changes.iter().flatten().cloned().collect()));
+            }
+            diff::VecDiffType::Altered { index, changes } => {
+                for (i, change) in changes.iter().enumerate() {
+                    if let Some(value) = change {
+                        items.push(Item::leaf(format!(
+                            "Changed {name}[{}] to {}",
+                            index + i,
+                            value
+                        )));
                     }
                 }
             }
-
-            for (key, change) in &update.verification_methods.altered {
-                if let Some(value) = change {
-                    println!("- Changed verification method {key} to {value}");
+            diff::VecDiffType::Removed { index, len } => {
+                for i in *index..(index + len) {
+                    items.push(Item::leaf(format!("Removed {name}[{i}]")));
                 }
             }
-            for key in &update.verification_methods.removed {
-                println!("- Removed verification method {key}");
-            }
+        }
+    }
+}
+
+impl AuditOps {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        // Always download the log, so a directory can't hide changes behind our cache.
+        let log = ctx.directory.fetch_audit_log(state.did()).await?;
 
-            for aka in &update.also_known_as.0 {
-                match aka {
-                    diff::VecDiffType::Inserted { index, changes } => {
-                        if *index == 0 {
-                            println!("- Inserted before Also-known-as[{index}]:");
+        let mut cache = local::AuditCache::load().await;
+        let cached = !self.no_cache && cache.is_valid(&log);
+
+        let mut failed = false;
+        let validated = if cached {
+            println!(
+                "Audit log for {} is {} (unchanged since last audit)",
+                self.user,
+                valid("valid!"),
+            );
+            None
+        } else {
+            match log.validated() {
+                Err(errors) => {
+                    println!("Audit log for {} is {}:", self.user, violation("invalid"));
+                    for entry in log.entries() {
+                        let entry_errors = errors
+                            .iter()
+                            .filter(|e| e.cid() == Some(entry.cid()))
+                            .collect::<Vec<_>>();
+                        if entry_errors.is_empty() {
+                            println!("- {} {}", valid("[ok]  "), entry.cid().as_ref());
                         } else {
-                            println!("- Inserted after Also-known-as[{}]:", index - 1);
-                        }
-                        for change in changes.iter().flatten() {
-                            println!("  - {change}");
-                        }
-                    }
-                    diff::VecDiffType::Altered { index, changes } => {
-                        for (i, change) in changes.iter().enumerate() {
-                            if let Some(value) = change {
-                                println!("- Changed Also-known-as[{}] to {}", index + i, value);
+                            println!("- {} {}", violation("[FAIL]"), entry.cid().as_ref());
+                            for e in entry_errors {
+                                println!("  - {}", violation(e));
                             }
                         }
                     }
-                    diff::VecDiffType::Removed { index, len } => {
-                        for i in *index..(index + len) {
-                            println!("- Removed Also-known-as[{i}]");
-                        }
+                    for e in errors.iter().filter(|e| e.cid().is_none()) {
+                        println!("- {}", violation(e));
                     }
+                    failed = true;
+                    None
+                }
+                Ok(validated) => {
+                    cache.record_valid(&log).await;
+                    println!("Audit log for {} is {}", self.user, valid("valid!"));
+                    Some(validated)
                 }
             }
+        };
 
-            for (id, change) in &update.services.altered {
-                if let Some(value) = &change.r#type {
-                    println!("- Changed service {id} type to {value}");
-                }
-                if let Some(value) = &change.endpoint {
-                    println!("- Changed service {id} endpoint to {value}");
+        for (cid, r#type) in log.unknown_operations() {
+            println!(
+                "- {}",
+                advisory(format!(
+                    "Entry {} has unknown operation type {type}; it was not validated",
+                    cid.as_ref(),
+                )),
+            );
+        }
+
+        let nullified = log.entries().iter().filter(|e| e.is_nullified()).count();
+        println!();
+        println!("{}", heading("Summary:"));
+        println!("- Active entries:    {}", log.entries().len() - nullified);
+        println!("- Nullified entries: {nullified}");
+
+        // The PDS keys only annotate the audit, so don't fail if the PDS does.
+        let server_keys = pds::get_server_keys_if_logged_in(&state)
+            .await
+            .ok()
+            .flatten();
+        let known_keys = KnownKeys::load().await?;
+        println!();
+        match validated {
+            // We validated the log just now, so we can describe every operation.
+            Some(validated) => {
+                println!("{}", heading("Operations:"));
+                for entry in validated.entries {
+                    println!(
+                        "- {} ({}): {}",
+                        format_time(&entry.created_at),
+                        entry.cid.as_ref(),
+                        signer_label(
+                            entry.signer,
+                            &entry.authorized_keys,
+                            server_keys.as_ref(),
+                            &known_keys,
+                        ),
+                    );
+                    match &entry.nullified {
+                        Some(plc::Nullification::Overridden { by, authority }) => println!(
+                            "  - {} by {}{}",
+                            advisory("Nullified"),
+                            by.as_ref(),
+                            authority
+                                .map(|i| format!(" (signed by rotation key [{i}])"))
+                                .unwrap_or_default(),
+                        ),
+                        Some(plc::Nullification::PrevNullified { prev }) => println!(
+                            "  - {} because {} was nullified",
+                            advisory("Nullified"),
+                            prev.as_ref(),
+                        ),
+                        None => (),
+                    }
+                    match &entry.state {
+                        Some(data) => println!(
+                            "  - Resulting state: {} on {}",
+                            data.handle()
+                                .map(|handle| format!("@{handle}"))
+                                .unwrap_or_else(|| "no handle".into()),
+                            data.endpoint().unwrap_or("no PDS"),
+                        ),
+                        None => println!("  - {}", advisory("Tombstoned")),
+                    }
                 }
             }
-            for id in &update.services.removed {
-                println!("- Removed service {id}");
+            None => {
+                println!("{}", heading("Active operations:"));
+                for authorship in log.authorship() {
+                    println!(
+                        "- {}: {}",
+                        format_time(&authorship.created_at),
+                        signer_label(
+                            authorship.signer,
+                            &authorship.authorized_keys,
+                            server_keys.as_ref(),
+                            &known_keys,
+                        ),
+                    );
+                }
             }
         }
 
-        println!();
-        if log.deactivated {
-            println!("Current state: Deactivated");
+        if failed {
+            Err(Error::LogAuditFailed)
         } else {
-            println!("Current state:");
-            print_state(state.inner_data());
+            Ok(())
         }
-
-        Ok(())
     }
 }
 
-impl AuditOps {
-    pub(crate) async fn run(&self) -> Result<(), Error> {
-        let client = reqwest::Client::new();
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::AuditOps,
+        context::AppContext,
+        error::Error,
+        remote::plc::{mock::MockDirectory, testing::TestLog},
+    };
 
-        let state = State::resolve(&self.user, &client).await?;
+    #[tokio::test]
+    async fn failed_audit() {
+        let log = TestLog::with_genesis()
+            .apply_update(|update| update.change_pds("https://pds.example.com").invalid_sig());
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&log));
 
-        let log = plc::get_audit_log(state.did(), &client).await?;
+        let errors = log.audit_log().validate().unwrap_err();
+        assert!(errors.iter().all(|e| e.cid() == Some(&log.cid_for(1))));
 
-        if let Err(errors) = log.validate() {
-            println!("Audit log for {} is invalid:", self.user);
-            for e in errors {
-                println!("- {}", e);
-            }
-        } else {
-            println!("Audit log for {} is valid!", self.user);
+        let res = AuditOps {
+            user: log.did().as_str().into(),
+            no_cache: true,
         }
-
-        Ok(())
+        .run(&ctx)
+        .await;
+        assert!(matches!(res, Err(Error::LogAuditFailed)));
+        assert_eq!(res.unwrap_err().exit_code(), 3);
     }
 }
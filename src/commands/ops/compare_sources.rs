@@ -0,0 +1,68 @@
+use futures_util::future::join_all;
+
+use crate::{
+    cli::CompareSourcesOps,
+    context::AppContext,
+    error::Error,
+    output::{advisory, heading, label_width, valid, violation},
+    remote::plc::{Directory, PlcDirectoryClient},
+};
+
+impl CompareSourcesOps {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let sources = self
+            .sources
+            .iter()
+            .map(|source| {
+                if source.contains("://") {
+                    source.clone()
+                } else {
+                    format!("https://{source}")
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Other sources' caches may not agree with the configured directory, so we
+        // bypass the local cache entirely.
+        let logs = join_all(sources.iter().map(|source| async move {
+            PlcDirectoryClient::new(source)
+                .download_audit_log(&self.did)
+                .await
+        }))
+        .await;
+        let reference = logs[0].as_ref().ok();
+
+        let width = label_width(sources.iter().map(|s| s.as_str()));
+        println!(
+            "{}",
+            heading(format!("{:width$}  Entries  Head CID", "Source"))
+        );
+        for (i, (source, log)) in sources.iter().zip(&logs).enumerate() {
+            let log = match log {
+                Ok(log) => log,
+                Err(e) => {
+                    println!("{source:width$}  {}", violation(format!("{e:?}")));
+                    continue;
+                }
+            };
+            let head = log
+                .head()
+                .map(|cid| cid.as_ref().to_string())
+                .unwrap_or_else(|| "-".into());
+            let divergence = match reference {
+                _ if i == 0 => advisory("(reference)").to_string(),
+                None => String::new(),
+                Some(reference) => match reference.divergence(log) {
+                    None => valid("matches").to_string(),
+                    Some(entry) => violation(format!("diverges at entry {entry}")).to_string(),
+                },
+            };
+            println!(
+                "{source:width$}  {:7}  {head}  {divergence}",
+                log.entries().len()
+            );
+        }
+
+        Ok(())
+    }
+}
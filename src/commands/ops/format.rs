@@ -0,0 +1,23 @@
+use crate::{
+    cli::{FormatOp, OpFormat},
+    commands::did::read_operation,
+    context::AppContext,
+    error::Error,
+};
+
+impl FormatOp {
+    pub(crate) async fn run(&self, _ctx: &AppContext) -> Result<(), Error> {
+        let op = read_operation(&self.operation, self.cbor).await?;
+
+        let output = match self.format {
+            OpFormat::Json => serde_json::to_value(&op).expect("can serialize"),
+            OpFormat::Xrpc => op.to_submit_plc_operation(),
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output).expect("can serialize")
+        );
+
+        Ok(())
+    }
+}
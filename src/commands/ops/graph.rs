@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio::fs;
+
+use crate::{
+    cli::GraphOps,
+    context::AppContext,
+    error::Error,
+    known_keys::KnownKeys,
+    output::{
+        format_time,
+        svg::{Graph, Node, NodeStyle},
+    },
+    remote::plc::{ValidatedEntry, ValidatedLog},
+};
+
+impl GraphOps {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let log = ctx.directory.audit_log(&self.did).await?;
+        let validated = log.validated().map_err(|_| Error::AuditLogInvalid)?;
+        let known_keys = KnownKeys::load().await?;
+
+        let mut graph = Graph::new(format!("Operations for {}", self.did.as_str()));
+        for (entry, (parent, column)) in validated.entries.iter().zip(layout(&validated)) {
+            graph.add(Node {
+                lines: vec![
+                    entry.cid.as_ref().to_string(),
+                    format_time(&entry.created_at),
+                    signer(entry, &known_keys),
+                ],
+                parent,
+                column,
+                style: match (&entry.nullified, &entry.state) {
+                    (Some(_), _) => NodeStyle::Nullified,
+                    (None, None) => NodeStyle::Tombstone,
+                    (None, Some(_)) => NodeStyle::Active,
+                },
+            });
+        }
+
+        fs::write(&self.svg, graph.render())
+            .await
+            .map_err(Error::OutputWriteFailed)?;
+        println!("Wrote graph to {}", self.svg.display());
+        Ok(())
+    }
+}
+
+fn signer(entry: &ValidatedEntry, known_keys: &KnownKeys) -> String {
+    let kind = if entry.state.is_none() {
+        "Tombstone"
+    } else if entry.prev.is_none() {
+        "Genesis"
+    } else {
+        "Update"
+    };
+    match entry.signer {
+        None => format!("{kind}, unknown signer"),
+        Some(i) => {
+            let holder = known_keys
+                .label_for_did(&entry.authorized_keys[i])
+                .map(|label| format!(" ({label})"))
+                .unwrap_or_default();
+            format!("{kind}, signed by rotation key [{i}]{holder}")
+        }
+    }
+}
+
+/// Returns the parent and column of each entry.
+///
+/// The active chain is drawn in the first column, and each nullified fork gets a column
+/// of its own.
+fn layout(log: &ValidatedLog) -> Vec<(Option<usize>, usize)> {
+    let mut rows = HashMap::new();
+    let mut continued = HashSet::new();
+    let mut columns: Vec<usize> = vec![];
+    let mut next_column = 1;
+
+    log.entries
+        .iter()
+        .enumerate()
+        .map(|(row, entry)| {
+            rows.insert(&entry.cid, row);
+            let parent = entry.prev.as_ref().and_then(|prev| rows.get(prev).copied());
+            let column = match parent {
+                _ if entry.nullified.is_none() => 0,
+                // Continue the parent's fork, unless another entry already has.
+                Some(parent) if columns[parent] != 0 && continued.insert(parent) => columns[parent],
+                _ => {
+                    next_column += 1;
+                    next_column - 1
+                }
+            };
+            columns.push(column);
+            (parent, column)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::layout;
+    use crate::remote::plc::testing::TestLog;
+
+    #[test]
+    fn forks_get_their_own_columns() {
+        let log = TestLog::with_genesis()
+            .apply_update(|update| update.rotate_rotation_key(2))
+            .apply_update(|update| {
+                update
+                    .change_handle("bob.example.com")
+                    .signed_with_key(2)
+                    .nullified()
+            })
+            .apply_update(|update| {
+                update
+                    .change_handle("carol.example.com")
+                    .with_prev_op(2)
+                    .signed_with_key(2)
+                    .nullified()
+            })
+            .apply_update(|update| {
+                update
+                    .change_handle("dave.example.com")
+                    .with_prev_op(1)
+                    .signed_with_key(1)
+                    .nullified()
+            })
+            .apply_update(|update| {
+                update
+                    .change_handle("erin.example.com")
+                    .with_prev_op(1)
+                    .signed_with_key(0)
+            });
+        let validated = log.audit_log().validated().unwrap();
+
+        assert_eq!(
+            layout(&validated),
+            [
+                (None, 0),
+                (Some(0), 0),
+                (Some(1), 1),
+                (Some(2), 1),
+                (Some(1), 2),
+                (Some(1), 0),
+            ],
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{self, BufRead, Write};
+
+use atrium_api::types::string::Datetime;
+use chrono::Utc;
+use diff::Diff;
+
+use super::{signer_label, update_items};
+use crate::{
+    cli::RecoverOp,
+    commands::submit::{sign_locally, submit, Signer},
+    context::AppContext,
+    data::State,
+    error::Error,
+    known_keys::KnownKeys,
+    output::{format_time, heading, print_items, valid, violation},
+    remote::{
+        pds,
+        plc::{SpecProfile, UnsignedOperation},
+    },
+};
+
+impl RecoverOp {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        // Don't trust a cached log to tell us what is still within the recovery window.
+        let log = ctx.directory.fetch_audit_log(state.did()).await?;
+        let validated = log.validated().map_err(|_| Error::AuditLogInvalid)?;
+
+        let profile = SpecProfile::default();
+        let candidates = validated.nullifiable_by(&self.key, &profile, &Utc::now().fixed_offset());
+        let target = match &self.cid {
+            Some(cid) => candidates
+                .iter()
+                .find(|entry| entry.cid.as_ref().to_string() == *cid)
+                .ok_or_else(|| Error::OperationNotRecoverable(cid.clone()))?,
+            None => candidates.first().ok_or(Error::NoRecoverableOperations)?,
+        };
+        let prev = target
+            .prev
+            .as_ref()
+            .expect("only non-genesis operations can be nullified");
+        let restored = validated
+            .entries
+            .iter()
+            .find(|entry| &entry.cid == prev)
+            .and_then(|entry| entry.state.clone())
+            .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+        let authority = target
+            .authorized_keys
+            .iter()
+            .position(|k| k == &self.key)
+            .expect("checked by nullifiable_by");
+
+        let server_keys = pds::get_server_keys_if_logged_in(&state).await?;
+        let known_keys = KnownKeys::load().await?;
+        println!(
+            "{}",
+            heading(format!(
+                "Operations that rotation key [{authority}] will nullify:"
+            )),
+        );
+        for entry in validated
+            .entries
+            .iter()
+            .skip_while(|entry| entry.cid != target.cid)
+            .filter(|entry| entry.nullified.is_none())
+        {
+            println!(
+                "- {} ({}): {}",
+                format_time(&entry.created_at),
+                entry.cid.as_ref(),
+                signer_label(
+                    entry.signer,
+                    &entry.authorized_keys,
+                    server_keys.as_ref(),
+                    &known_keys,
+                ),
+            );
+        }
+        println!();
+        println!("{}", heading("Changes to the current state:"));
+        print_items(&update_items(&state.inner_data().diff(&restored)));
+        println!();
+        println!(
+            "The directory must receive the recovery operation by {}.",
+            format_time(&Datetime::new(
+                *target.created_at.as_ref() + profile.recovery_window
+            )),
+        );
+
+        if !self.force {
+            if ctx.non_interactive {
+                return Err(Error::RecoveryNeedsForce);
+            }
+            print!("{} [y/N] ", violation("Nullify these operations?"));
+            io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(Error::InputReadFailed)?;
+            if !line.trim().eq_ignore_ascii_case("y") {
+                return Err(Error::RecoveryCancelled);
+            }
+        }
+
+        let op = UnsignedOperation::update(prev.clone(), restored);
+        let op = sign_locally(ctx, &state, op, &target.authorized_keys, &self.key).await?;
+        submit(
+            ctx,
+            &state,
+            &op,
+            Signer::Key(self.key.clone()),
+            self.confirm_cid.as_deref(),
+        )
+        .await?;
+
+        println!("Recovery {}", valid("complete"));
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use diff::Diff;
+use tokio::fs;
+
+use super::{state_items, update_items};
+use crate::{
+    cli::ReportOps,
+    context::AppContext,
+    data::{PlcData, State},
+    error::Error,
+    output::{format_time, html::Page},
+    remote::plc,
+};
+
+impl ReportOps {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        // Fetch the audit log once, and derive the operations log from it.
+        let audit_log = ctx.directory.audit_log(state.did()).await?;
+        let log = plc::OperationsLog::from_audit_log(&audit_log)?;
+
+        let mut page = Page::new(format!("Account {}", state.did().as_str()));
+
+        page.heading("Audit findings", None);
+        match audit_log.validate() {
+            Ok(()) => page.paragraph("The audit log is valid.", Some("valid")),
+            Err(errors) => {
+                page.paragraph("The audit log is invalid:", Some("violation"));
+                for e in errors {
+                    page.paragraph(&e.to_string(), Some("violation"));
+                }
+            }
+        }
+
+        page.heading("Key lineage", None);
+        page.table(
+            &["Key", "Role", "Added", "Removed"],
+            &key_lineage(&log)
+                .into_iter()
+                .map(|entry| {
+                    vec![
+                        entry.key,
+                        entry.role,
+                        entry.added,
+                        entry.removed.unwrap_or_default(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        page.heading("Initial state", Some(&format_time(&log.created_at)));
+        page.items(&state_items(&log.create));
+
+        for (i, (created_at, update)) in log.updates.iter().enumerate() {
+            page.heading(&format!("Update {}", i + 1), Some(&format_time(created_at)));
+            page.items(&update_items(update));
+        }
+
+        if let Some(tombstoned) = &log.tombstoned {
+            page.heading("Current state", Some(&format_time(tombstoned)));
+            page.paragraph("Tombstoned", Some("advisory"));
+        } else {
+            page.heading("Current state", None);
+            page.items(&state_items(state.inner_data()));
+        }
+
+        fs::write(&self.html, page.render())
+            .await
+            .map_err(Error::OutputWriteFailed)?;
+        println!("Wrote report to {}", self.html.display());
+
+        Ok(())
+    }
+}
+
+/// The period during which a key held a particular role.
+struct KeyLineage {
+    key: String,
+    role: String,
+    added: String,
+    removed: Option<String>,
+}
+
+/// Tracks when each rotation key and verification method was added and removed.
+fn key_lineage(log: &plc::OperationsLog) -> Vec<KeyLineage> {
+    let mut lineage: Vec<KeyLineage> = vec![];
+
+    let mut track = |data: &PlcData, time: String| {
+        let current = data
+            .rotation_keys
+            .iter()
+            .map(|key| (key.clone(), "Rotation key".to_string()))
+            .chain(
+                data.verification_methods
+                    .iter()
+                    .map(|(id, key)| (key.clone(), format!("Verification method {id}"))),
+            )
+            .collect::<Vec<_>>();
+
+        for entry in lineage.iter_mut().filter(|entry| entry.removed.is_none()) {
+            if !current
+                .iter()
+                .any(|(key, role)| key == &entry.key && role == &entry.role)
+            {
+                entry.removed = Some(time.clone());
+            }
+        }
+
+        for (key, role) in current {
+            if !lineage
+                .iter()
+                .any(|entry| entry.removed.is_none() && entry.key == key && entry.role == role)
+            {
+                lineage.push(KeyLineage {
+                    key,
+                    role,
+                    added: time.clone(),
+                    removed: None,
+                });
+            }
+        }
+    };
+
+    let mut state = log.create.clone();
+    track(&state, format_time(&log.created_at));
+    for (created_at, update) in &log.updates {
+        state.apply(update);
+        track(&state, format_time(created_at));
+    }
+    if let Some(tombstoned) = &log.tombstoned {
+        // A tombstoned DID has no keys.
+        track(
+            &PlcData {
+                rotation_keys: vec![],
+                verification_methods: Default::default(),
+                also_known_as: vec![],
+                services: Default::default(),
+            },
+            format_time(tombstoned),
+        );
+    }
+
+    lineage
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Read};
+
+use tokio::fs;
+
+use crate::{
+    cli::SubmitOp,
+    commands::submit::{sign_locally, submit, Signer},
+    context::AppContext,
+    data::State,
+    error::Error,
+    remote::plc::UnsignedOperation,
+};
+
+impl SubmitOp {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+        let head = ctx
+            .directory
+            .audit_log(state.did())
+            .await?
+            .head()
+            .cloned()
+            .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+
+        let op = match &self.template {
+            Some(path) => {
+                let data = if path.as_os_str() == "-" {
+                    let mut data = vec![];
+                    io::stdin().read_to_end(&mut data).map(|_| data)
+                } else {
+                    fs::read(path).await
+                }
+                .map_err(Error::InputReadFailed)?;
+                let template = serde_json::from_slice(&data).map_err(Error::InvalidOperation)?;
+                UnsignedOperation::from_template(template, &head)?
+            }
+            None => {
+                let mut data = state.inner_data().clone();
+                if let Some(handle) = &self.handle {
+                    data.set_handle(handle.as_str());
+                }
+                if let Some(pds) = &self.pds {
+                    data.set_endpoint(pds.clone());
+                }
+                if let Some(key) = &self.signing_key {
+                    data.verification_methods
+                        .insert("atproto".into(), key.clone());
+                }
+                if &data == state.inner_data() {
+                    return Err(Error::OperationMakesNoChanges);
+                }
+                UnsignedOperation::update(head, data)
+            }
+        };
+
+        let op = sign_locally(
+            ctx,
+            &state,
+            op,
+            &state.inner_data().rotation_keys,
+            &self.key,
+        )
+        .await?;
+        submit(
+            ctx,
+            &state,
+            &op,
+            Signer::Key(self.key.clone()),
+            self.confirm_cid.as_deref(),
+        )
+        .await
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    cli::TombstoneOp,
+    commands::submit::{sign_locally, submit, Signer},
+    context::AppContext,
+    data::State,
+    error::Error,
+    output::violation,
+    remote::plc::{SpecProfile, UnsignedOperation},
+};
+
+impl TombstoneOp {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+        let did = state.did().as_str();
+        let head = ctx
+            .directory
+            .audit_log(state.did())
+            .await?
+            .head()
+            .cloned()
+            .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+
+        println!(
+            "{}",
+            violation(format!(
+                "WARNING: This will permanently deactivate {did}{}! Unless the tombstone is \
+                 nullified by a higher-priority rotation key within {} hours, the identity \
+                 and everything tied to it will be gone for good.",
+                state
+                    .handle()
+                    .map(|handle| format!(" (@{handle})"))
+                    .unwrap_or_default(),
+                SpecProfile::default().recovery_window.num_hours(),
+            )),
+        );
+
+        let read_line = |prompt: &str| {
+            print!("{prompt}");
+            io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(Error::InputReadFailed)?;
+            Ok::<_, Error>(line.trim().to_string())
+        };
+        let confirmation = match &self.confirm_did {
+            Some(confirmation) => confirmation.clone(),
+            None if ctx.non_interactive => return Err(Error::TombstoneNeedsConfirmation),
+            None => {
+                if !read_line("Deactivate this DID? [y/N] ")?.eq_ignore_ascii_case("y") {
+                    return Err(Error::TombstoneCancelled);
+                }
+                read_line("Type the DID to confirm: ")?
+            }
+        };
+        if confirmation != did {
+            return Err(Error::TombstoneConfirmationMismatch);
+        }
+
+        let op = sign_locally(
+            ctx,
+            &state,
+            UnsignedOperation::tombstone(head),
+            &state.inner_data().rotation_keys,
+            &self.key,
+        )
+        .await?;
+        submit(
+            ctx,
+            &state,
+            &op,
+            Signer::Key(self.key.clone()),
+            self.confirm_cid.as_deref(),
+        )
+        .await?;
+
+        println!("{did} has been deactivated");
+        Ok(())
+    }
+}
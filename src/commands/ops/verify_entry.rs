@@ -0,0 +1,89 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use tokio::fs;
+
+use super::signer_label;
+use crate::{
+    cli::VerifyEntryOp,
+    context::AppContext,
+    error::Error,
+    known_keys::KnownKeys,
+    output::{advisory, valid, violation},
+    remote::plc::LogEntry,
+};
+
+impl VerifyEntryOp {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let entry = read_entry(&self.entry).await?;
+        if let Some(r#type) = entry.unknown_type() {
+            println!(
+                "{}",
+                advisory(format!(
+                    "Entry {} has unknown operation type {type}, so it can't be verified",
+                    entry.cid().as_ref(),
+                )),
+            );
+            return Err(Error::EntryVerificationFailed);
+        }
+
+        let prev = match (&self.prev, entry.prev()) {
+            (Some(path), _) => Some(read_entry(path).await?),
+            (None, Some(prev)) => ctx
+                .directory
+                .audit_log(entry.did())
+                .await?
+                .entries()
+                .iter()
+                .find(|e| e.cid() == prev)
+                .cloned(),
+            (None, None) => None,
+        };
+
+        match entry.verify(prev.as_ref()) {
+            Ok(authorship) => {
+                let known_keys = KnownKeys::load().await?;
+                println!(
+                    "Entry {} for {} is {}",
+                    entry.cid().as_ref(),
+                    entry.did().as_str(),
+                    valid("valid!"),
+                );
+                println!(
+                    "- {}",
+                    signer_label(
+                        authorship.signer,
+                        &authorship.authorized_keys,
+                        None,
+                        &known_keys,
+                    ),
+                );
+                Ok(())
+            }
+            Err(errors) => {
+                println!(
+                    "Entry {} for {} is {}:",
+                    entry.cid().as_ref(),
+                    entry.did().as_str(),
+                    violation("invalid"),
+                );
+                for e in errors {
+                    println!("- {}", violation(e));
+                }
+                Err(Error::EntryVerificationFailed)
+            }
+        }
+    }
+}
+
+async fn read_entry(path: &Path) -> Result<LogEntry, Error> {
+    let data = if path.as_os_str() == "-" {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path).await
+    }
+    .map_err(Error::InputReadFailed)?;
+
+    serde_json::from_slice(&data).map_err(Error::InvalidLogEntry)
+}
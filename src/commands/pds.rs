@@ -0,0 +1,80 @@
+use diff::Diff;
+use reqwest::Url;
+
+use super::{ops::update_items, submit};
+use crate::{
+    cli::MigratePds,
+    context::AppContext,
+    data::State,
+    error::Error,
+    output::{heading, print_items},
+};
+
+impl MigratePds {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let endpoint = parse_endpoint(&self.endpoint)?;
+        let state = State::resolve(&self.user, ctx).await?;
+        if state.endpoint() == Some(endpoint.as_str()) {
+            return Err(Error::PdsAlreadySet(endpoint));
+        }
+
+        let mut data = state.inner_data().clone();
+        data.set_endpoint(endpoint.clone());
+
+        println!("{}", heading("Changes:"));
+        print_items(&update_items(&state.inner_data().diff(&data)));
+        if self.dry_run {
+            return Ok(());
+        }
+        println!();
+        let (op, signer) = submit::sign_update(
+            ctx,
+            &state,
+            data,
+            self.key.as_deref(),
+            self.token.as_deref(),
+        )
+        .await?;
+        submit::submit(ctx, &state, &op, signer, self.confirm_cid.as_deref()).await?;
+
+        println!(
+            "{} now points to the PDS at {endpoint}",
+            state.did().as_str()
+        );
+        Ok(())
+    }
+}
+
+/// Parses a PDS endpoint, which must be the bare origin of an HTTP(S) server.
+fn parse_endpoint(s: &str) -> Result<String, Error> {
+    let url = Url::parse(s).map_err(|e| Error::PdsEndpointInvalid(e.to_string()))?;
+    if !matches!(url.scheme(), "https" | "http") {
+        return Err(Error::PdsEndpointInvalid("must be an HTTP(S) URL".into()));
+    }
+    if url.path() != "/" || url.query().is_some() || url.fragment().is_some() {
+        return Err(Error::PdsEndpointInvalid(
+            "must not have a path, query, or fragment".into(),
+        ));
+    }
+    Ok(url.origin().ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_endpoint;
+
+    #[test]
+    fn endpoints_are_normalized() {
+        assert_eq!(
+            parse_endpoint("https://PDS.example.com/").unwrap(),
+            "https://pds.example.com",
+        );
+        assert_eq!(
+            parse_endpoint("https://pds.example.com:8443").unwrap(),
+            "https://pds.example.com:8443",
+        );
+        assert!(parse_endpoint("pds.example.com").is_err());
+        assert!(parse_endpoint("ftp://pds.example.com").is_err());
+        assert!(parse_endpoint("https://pds.example.com/xrpc").is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{cli::Pin, context::AppContext, data::State, error::Error, pins};
+
+impl Pin {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let state = State::resolve(&self.user, ctx).await?;
+
+        if self.remove {
+            if pins::unpin(&state).await? {
+                println!("Removed pin for {}", state.did().as_str());
+            } else {
+                println!("{} was not pinned", state.did().as_str());
+            }
+        } else {
+            pins::pin(&state).await?;
+            println!(
+                "Pinned {} rotation keys for {}",
+                state.inner_data().rotation_keys.len(),
+                state.did().as_str(),
+            );
+        }
+
+        Ok(())
+    }
+}
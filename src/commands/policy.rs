@@ -0,0 +1,44 @@
+use crate::{
+    cli::CheckPolicy,
+    commands::did::read_operation,
+    context::AppContext,
+    data::State,
+    error::Error,
+    output::{advisory, valid, violation},
+    policy::Requirement,
+};
+
+impl CheckPolicy {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let op = read_operation(&self.operation, self.cbor).await?;
+
+        let state = match &self.user {
+            Some(user) => Some(State::resolve(user, ctx).await?),
+            None => None,
+        };
+        let kinds = op.kinds(state.as_ref().map(|state| state.inner_data()));
+
+        println!("CID: {}", op.cid().as_ref());
+        println!(
+            "Kind: {}",
+            kinds
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+
+        let requirement = ctx.config.policy.requirement(&kinds);
+        let text = requirement.to_string();
+        println!(
+            "Policy: {}",
+            match requirement {
+                Requirement::Allow => valid(text),
+                Requirement::Phrase | Requirement::ConfirmCid => advisory(text),
+                Requirement::Disable => violation(text),
+            }
+        );
+
+        Ok(())
+    }
+}
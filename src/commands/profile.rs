@@ -0,0 +1,114 @@
+use std::io::{self, BufRead, Read, Write};
+
+use tokio::fs;
+
+use crate::{
+    cli::{ExportProfile, ImportProfile},
+    context::AppContext,
+    error::Error,
+    output::{advisory, valid},
+    profile::Profile,
+    util,
+};
+
+impl ExportProfile {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let profile = Profile::collect(&self.fleet, self.include_secrets).await?;
+
+        let passphrase = if profile.has_secrets() {
+            Some(util::passphrase(
+                "Profile",
+                "PLC_PROFILE_PASSPHRASE",
+                ctx.non_interactive,
+                true,
+            )?)
+        } else {
+            None
+        };
+        let data = profile.to_bytes(passphrase);
+
+        match &self.output {
+            Some(path) => fs::write(path, &data).await,
+            None => io::stdout().lock().write_all(&data),
+        }
+        .map_err(Error::OutputWriteFailed)?;
+
+        eprintln!(
+            "Exported {} files{}",
+            profile.files.len() + usize::from(profile.fleet.is_some()),
+            if profile.has_secrets() {
+                " (encrypted, including secrets)"
+            } else {
+                ""
+            },
+        );
+        Ok(())
+    }
+}
+
+impl ImportProfile {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let data = if self.input.as_os_str() == "-" {
+            let mut data = vec![];
+            io::stdin().read_to_end(&mut data).map(|_| data)
+        } else {
+            fs::read(&self.input).await
+        }
+        .map_err(Error::ProfileReadFailed)?;
+
+        let passphrase = if Profile::is_encrypted(&data) {
+            Some(util::passphrase(
+                "Profile",
+                "PLC_PROFILE_PASSPHRASE",
+                ctx.non_interactive,
+                false,
+            )?)
+        } else {
+            None
+        };
+        let profile = Profile::from_bytes(&data, passphrase)?;
+
+        // Hooks run arbitrary commands, so show them before they can run.
+        if let Some(hooks) = profile.hooks() {
+            println!("The profile's config file has hooks:");
+            for line in hooks.lines() {
+                println!("  {line}");
+            }
+            if !self.allow_hooks {
+                // A profile read from stdin leaves nothing to answer the prompt with.
+                if ctx.non_interactive || self.input.as_os_str() == "-" {
+                    return Err(Error::ProfileHooksNeedAllowHooks);
+                }
+                print!("Import these hooks? [y/N] ");
+                io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+                let mut line = String::new();
+                io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .map_err(Error::InputReadFailed)?;
+                if !line.trim().eq_ignore_ascii_case("y") {
+                    return Err(Error::Cancelled);
+                }
+            }
+        }
+
+        profile.apply(&self.fleet, self.force).await?;
+
+        for name in profile.files.keys() {
+            println!("- Restored {name}");
+        }
+        if profile.fleet.is_some() {
+            println!("- Restored fleet configuration to {}", self.fleet.display());
+        }
+        if profile.has_secrets() {
+            println!(
+                "{}",
+                advisory(
+                    "The profile included secrets; check the keystore and PDS session it restored"
+                )
+            );
+        }
+        println!("Profile {}", valid("imported"));
+        Ok(())
+    }
+}
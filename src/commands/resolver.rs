@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use atrium_api::types::string::Did;
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tokio::net::TcpListener;
+
+use crate::{
+    cli::ServeResolver,
+    context::AppContext,
+    error::Error,
+    remote::plc::{Directory, PlcDirectoryClient},
+};
+
+/// The most responses we cache before evicting expired ones.
+const MAX_CACHE_ENTRIES: usize = 100_000;
+
+/// The window over which each client's requests are counted.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+impl ServeResolver {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let directory: Arc<dyn Directory> = match &self.upstream {
+            Some(upstream) => Arc::new(PlcDirectoryClient::new(upstream)),
+            None => ctx.directory.clone(),
+        };
+        let resolver = Arc::new(Resolver {
+            directory,
+            ttl: Duration::from_secs(self.cache_ttl),
+            cache: Mutex::new(HashMap::new()),
+            limiter: Mutex::new(RateLimiter::new(self.rate_limit)),
+        });
+
+        let app = Router::new()
+            .route("/:did", get(did_document))
+            .route("/:did/data", get(did_data))
+            .with_state(resolver);
+
+        let listener = TcpListener::bind(self.listen)
+            .await
+            .map_err(Error::ServerBindFailed)?;
+        println!("Serving a caching DID resolver on {}", self.listen);
+
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(ctx.cancel_on_ctrl_c().cancelled_owned())
+        .await
+        .map_err(Error::ServerFailed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Endpoint {
+    Document,
+    Data,
+}
+
+struct Cached {
+    fetched: Instant,
+    value: serde_json::Value,
+}
+
+struct Resolver {
+    directory: Arc<dyn Directory>,
+    ttl: Duration,
+    cache: Mutex<HashMap<(Did, Endpoint), Cached>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl Resolver {
+    async fn get(&self, client: IpAddr, did: String, endpoint: Endpoint) -> Response {
+        if !self
+            .limiter
+            .lock()
+            .expect("not poisoned")
+            .allow(client, Instant::now())
+        {
+            return StatusCode::TOO_MANY_REQUESTS.into_response();
+        }
+
+        let did = match Did::new(did) {
+            Ok(did) if did.method() == "did:plc" => did,
+            _ => return StatusCode::BAD_REQUEST.into_response(),
+        };
+        let key = (did, endpoint);
+
+        if let Some(cached) = self.cache.lock().expect("not poisoned").get(&key) {
+            if cached.fetched.elapsed() < self.ttl {
+                return json(&cached.value);
+            }
+        }
+
+        let (did, _) = &key;
+        let value = match endpoint {
+            Endpoint::Document => self.directory.did_document(did).await,
+            Endpoint::Data => self
+                .directory
+                .resolve(did)
+                .await
+                .map(|state| serde_json::to_value(state).expect("can serialize")),
+        };
+
+        match value {
+            Ok(value) => {
+                let mut cache = self.cache.lock().expect("not poisoned");
+                if cache.len() >= MAX_CACHE_ENTRIES {
+                    cache.retain(|_, cached| cached.fetched.elapsed() < self.ttl);
+                }
+                if cache.len() < MAX_CACHE_ENTRIES {
+                    cache.insert(
+                        key,
+                        Cached {
+                            fetched: Instant::now(),
+                            value: value.clone(),
+                        },
+                    );
+                }
+                json(&value)
+            }
+            // Pass through the directory's verdict on unknown or tombstoned DIDs.
+            Err(Error::PlcDirectoryRequestFailed(e))
+                if matches!(e.status(), Some(StatusCode::NOT_FOUND | StatusCode::GONE)) =>
+            {
+                e.status().expect("checked").into_response()
+            }
+            Err(Error::DidTombstoned(_)) => StatusCode::GONE.into_response(),
+            Err(_) => StatusCode::BAD_GATEWAY.into_response(),
+        }
+    }
+}
+
+fn json(value: &serde_json::Value) -> Response {
+    (
+        [(CONTENT_TYPE, "application/json")],
+        serde_json::to_string(value).expect("can serialize"),
+    )
+        .into_response()
+}
+
+async fn did_document(
+    State(resolver): State<Arc<Resolver>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(did): Path<String>,
+) -> Response {
+    resolver.get(client.ip(), did, Endpoint::Document).await
+}
+
+async fn did_data(
+    State(resolver): State<Arc<Resolver>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(did): Path<String>,
+) -> Response {
+    resolver.get(client.ip(), did, Endpoint::Data).await
+}
+
+/// Limits each client to a fixed number of requests per [`RATE_LIMIT_WINDOW`].
+///
+/// IPv6 clients are grouped by their /64 prefix, as a single host can typically use
+/// any address within it.
+struct RateLimiter {
+    limit: u32,
+    /// The maximum number of clients to track at once.
+    capacity: usize,
+    clients: HashMap<IpAddr, (Instant, u32)>,
+}
+
+impl RateLimiter {
+    fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            capacity: MAX_CACHE_ENTRIES,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Records a request from `client` at `now`, returning whether it is allowed.
+    fn allow(&mut self, client: IpAddr, now: Instant) -> bool {
+        let client = match client {
+            IpAddr::V4(_) => client,
+            IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+                Some(addr) => IpAddr::V4(addr),
+                None => IpAddr::V6((u128::from(addr) & !(u128::MAX >> 64)).into()),
+            },
+        };
+
+        // Forget clients whose windows have ended, so the map doesn't grow unbounded.
+        if !self.clients.contains_key(&client) && self.clients.len() >= self.capacity {
+            self.clients
+                .retain(|_, (start, _)| now.duration_since(*start) < RATE_LIMIT_WINDOW);
+
+            // If every tracked client is still within its window, we can't make room
+            // without letting an existing client reset its count, so refuse the new one.
+            if self.clients.len() >= self.capacity {
+                return false;
+            }
+        }
+
+        let (start, count) = self.clients.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use axum::http::StatusCode;
+
+    use super::{Endpoint, RateLimiter, Resolver, RATE_LIMIT_WINDOW};
+    use crate::remote::plc::{mock::MockDirectory, testing::TestLog};
+
+    #[tokio::test]
+    async fn tombstoned_dids_are_gone() {
+        let active = TestLog::with_genesis();
+        let tombstoned = TestLog::with_genesis().apply_tombstone(|t| t);
+        let resolver = Resolver {
+            directory: Arc::new(
+                MockDirectory::default()
+                    .with_log(&active)
+                    .with_log(&tombstoned),
+            ),
+            ttl: Duration::from_secs(60),
+            cache: Mutex::new(HashMap::new()),
+            limiter: Mutex::new(RateLimiter::new(100)),
+        };
+        let client = IpAddr::from([192, 0, 2, 1]);
+
+        for endpoint in [Endpoint::Document, Endpoint::Data] {
+            let resp = resolver
+                .get(client, active.did().as_str().into(), endpoint)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let resp = resolver
+                .get(client, tombstoned.did().as_str().into(), endpoint)
+                .await;
+            assert_eq!(resp.status(), StatusCode::GONE);
+        }
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let alice = IpAddr::from([192, 0, 2, 1]);
+        let bob = IpAddr::from([192, 0, 2, 2]);
+        let start = Instant::now();
+
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.allow(alice, start));
+        assert!(limiter.allow(alice, start));
+        assert!(!limiter.allow(alice, start));
+
+        // Clients are limited independently.
+        assert!(limiter.allow(bob, start));
+
+        // The limit resets once the window has passed.
+        assert!(limiter.allow(alice, start + RATE_LIMIT_WINDOW));
+    }
+
+    #[test]
+    fn rate_limiter_groups_ipv6_by_prefix() {
+        let alice = IpAddr::from([0x2001, 0xdb8, 0, 1, 0, 0, 0, 1]);
+        let alice_alt = IpAddr::from([0x2001, 0xdb8, 0, 1, 0xffff, 0, 0, 2]);
+        let bob = IpAddr::from([0x2001, 0xdb8, 0, 2, 0, 0, 0, 1]);
+        let start = Instant::now();
+
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.allow(alice, start));
+        assert!(limiter.allow(alice_alt, start));
+        assert!(!limiter.allow(alice, start));
+        assert!(limiter.allow(bob, start));
+    }
+
+    #[test]
+    fn rate_limiter_full() {
+        let alice = IpAddr::from([192, 0, 2, 1]);
+        let bob = IpAddr::from([192, 0, 2, 2]);
+        let carol = IpAddr::from([192, 0, 2, 3]);
+        let start = Instant::now();
+
+        let mut limiter = RateLimiter {
+            capacity: 2,
+            ..RateLimiter::new(2)
+        };
+        assert!(limiter.allow(alice, start));
+        assert!(limiter.allow(bob, start + Duration::from_secs(1)));
+
+        // New clients are refused while every tracked client is within its window,
+        // but tracked clients are unaffected.
+        assert!(!limiter.allow(carol, start));
+        assert!(limiter.allow(alice, start));
+        assert_eq!(limiter.clients.len(), 2);
+
+        // Once a window has ended, its client is forgotten to make room.
+        assert!(limiter.allow(carol, start + RATE_LIMIT_WINDOW));
+        assert!(limiter.clients.contains_key(&bob));
+        assert!(!limiter.clients.contains_key(&alice));
+    }
+}
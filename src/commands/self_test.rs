@@ -0,0 +1,120 @@
+use atrium_crypto::keypair::{Did as _, P256Keypair, Secp256k1Keypair};
+use chrono::{TimeDelta, Utc};
+use rand_core::OsRng;
+use tokio::fs;
+
+use crate::{
+    cli::SelfTest,
+    context::AppContext,
+    error::Error,
+    local,
+    output::{valid, violation},
+};
+
+/// A handle with a long-lived `_atproto` DNS TXT record.
+const DNS_TEST_HANDLE: &str = "bsky.app";
+
+/// The maximum clock skew we tolerate.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+impl SelfTest {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let mut failed = false;
+        let mut report = |name: &str, res: Result<String, String>| match res {
+            Ok(msg) => println!("{}   {name}: {msg}", valid("[ok]")),
+            Err(msg) => {
+                failed = true;
+                println!("{} {name}: {msg}", violation("[FAIL]"));
+            }
+        };
+
+        // DNS TXT lookups.
+        report(
+            "DNS TXT lookup",
+            match ctx.resolver.resolve_dns_txt(DNS_TEST_HANDLE).await {
+                Ok(did) => Ok(format!("@{DNS_TEST_HANDLE} resolved to {}", did.as_str())),
+                Err(e) => Err(format!(
+                    "@{DNS_TEST_HANDLE} did not resolve ({e}); try --dns-server or --dns-system",
+                )),
+            },
+        );
+
+        // HTTPS egress to plc.directory, and clock skew against its `Date` header.
+        match ctx.directory.health().await {
+            Ok(server_time) => {
+                report("HTTPS to plc.directory", Ok("reachable".into()));
+
+                report(
+                    "Clock skew",
+                    match server_time {
+                        Some(server_time) => {
+                            let skew = Utc::now().signed_duration_since(server_time);
+                            if skew.abs() <= MAX_CLOCK_SKEW {
+                                Ok(format!("{}s", skew.num_seconds()))
+                            } else {
+                                Err(format!(
+                                    "local clock differs from plc.directory by {}s; check your system time",
+                                    skew.num_seconds(),
+                                ))
+                            }
+                        }
+                        None => Err("plc.directory did not return a valid Date header".into()),
+                    },
+                );
+            }
+            Err(e) => report(
+                "HTTPS to plc.directory",
+                Err(format!(
+                    "{e:?}; check your network connection and any proxy or firewall settings"
+                )),
+            ),
+        }
+
+        // Config directory writability.
+        report(
+            "Config directory",
+            match local::config_file(".self-test") {
+                None => Err("could not locate or create the config directory".into()),
+                Some(path) => match fs::write(&path, b"plc").await {
+                    Ok(()) => {
+                        let _ = fs::remove_file(&path).await;
+                        Ok(format!(
+                            "{} is writable",
+                            path.parent().unwrap_or(&path).display()
+                        ))
+                    }
+                    Err(e) => Err(format!("cannot write to {}: {e}", path.display())),
+                },
+            },
+        );
+
+        // Local key signing round-trips.
+        if self.keys {
+            let msg = b"plc self-test";
+
+            let p256 = P256Keypair::create(&mut OsRng);
+            report(
+                "P-256 signing",
+                p256.sign(msg)
+                    .and_then(|sig| atrium_crypto::verify::verify_signature(&p256.did(), msg, &sig))
+                    .map(|()| "round-trip succeeded".into())
+                    .map_err(|e| e.to_string()),
+            );
+
+            let k256 = Secp256k1Keypair::create(&mut OsRng);
+            report(
+                "secp256k1 signing",
+                k256.sign(msg)
+                    .and_then(|sig| atrium_crypto::verify::verify_signature(&k256.did(), msg, &sig))
+                    .map(|()| "round-trip succeeded".into())
+                    .map_err(|e| e.to_string()),
+            );
+        }
+
+        if failed {
+            Err(Error::SelfTestFailed)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! The steps every operation goes through between being proposed and being applied
+//! to the directory, shared by the commands that change a DID.
+
+use std::env;
+use std::fmt;
+
+use atrium_api::types::string::Datetime;
+use chrono::Utc;
+
+use crate::{
+    context::AppContext,
+    data::{PlcData, State},
+    error::Error,
+    journal::{self, Action, JournalEntry, Outcome},
+    keystore::StoredKey,
+    output::{advisory, valid, violation},
+    pins,
+    remote::{
+        pds,
+        plc::{AuditLog, SignedOperation, UnsignedOperation},
+    },
+    util,
+    warning::Warning,
+};
+
+/// Who signed an operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Signer {
+    /// A rotation key from the keystore, as a `did:key`.
+    Key(String),
+    /// The user's PDS, at this endpoint.
+    Pds(String),
+}
+
+impl fmt::Display for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signer::Key(key) => write!(f, "{key}"),
+            Signer::Pds(endpoint) => write!(f, "pds:{endpoint}"),
+        }
+    }
+}
+
+/// Has the user's PDS sign an operation that updates their DID to `data`.
+///
+/// The pre-sign hooks see the unsigned operation before anything is sent to the PDS.
+/// The PDS authorizes signing with a token it emails to the account; this uses
+/// `token` (or `PLC_EMAIL_TOKEN`), and otherwise requests a token and prompts for it.
+pub(super) async fn sign_via_pds(
+    ctx: &AppContext,
+    state: &State,
+    data: &PlcData,
+    token: Option<&str>,
+) -> Result<(SignedOperation, Signer), Error> {
+    let pds = state.endpoint().ok_or(Error::DidDocumentHasNoPds)?;
+    let head = ctx
+        .directory
+        .audit_log(state.did())
+        .await?
+        .head()
+        .cloned()
+        .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+
+    let mut proposed = serde_json::to_value(data).expect("can serialize");
+    proposed["type"] = "plc_operation".into();
+    proposed["prev"] = head.as_ref().to_string().into();
+    ctx.config
+        .hooks
+        .pre_sign(Some(state.did()), &proposed)
+        .await?;
+
+    let agent = pds::Agent::new(pds.into());
+    agent.resume_session(state.did()).await?;
+
+    let token = match (token, env::var("PLC_EMAIL_TOKEN")) {
+        // Arguments are visible to other processes, and end up in shell history.
+        (Some(_), _) if ctx.non_interactive => return Err(Error::SecretOnCommandLine),
+        (Some(token), _) => token.to_string(),
+        (None, Ok(token)) => token,
+        // Don't have the PDS email a token that we can't prompt for.
+        (None, Err(_)) if ctx.non_interactive => return Err(Error::EmailTokenRequired),
+        (None, Err(_)) => {
+            agent.request_plc_operation_signature().await?;
+            rpassword::prompt_password("Enter the token your PDS emailed to you: ")
+                .map_err(Error::InputReadFailed)?
+                .trim()
+                .to_string()
+        }
+    };
+
+    let op = agent.sign_plc_operation(token, data).await?;
+    let signer = Signer::Pds(pds.into());
+
+    // The PDS builds the operation itself, so make sure it signed what we asked for.
+    if op.data().as_ref() != Some(data) || op.prev() != Some(&head) {
+        let e = Error::PdsSignedUnexpectedOperation;
+        record(
+            Action::Signed,
+            state,
+            &op,
+            &signer,
+            Outcome::Failed(format!("{e:?}")),
+        )
+        .await?;
+        return Err(e);
+    }
+    record(Action::Signed, state, &op, &signer, Outcome::Succeeded).await?;
+
+    Ok((op, signer))
+}
+
+/// Signs an operation that updates `state` with a rotation key from the keystore.
+///
+/// `authorized_keys` are the rotation keys of the operation's `prev`, one of which
+/// must be `key`. The pre-sign hooks see the operation before it is signed. The key's
+/// passphrase is read from `PLC_KEY_PASSPHRASE`, or prompted for.
+pub(super) async fn sign_locally(
+    ctx: &AppContext,
+    state: &State,
+    op: UnsignedOperation,
+    authorized_keys: &[String],
+    key: &str,
+) -> Result<SignedOperation, Error> {
+    if !authorized_keys.iter().any(|k| k == key) {
+        return Err(Error::SigningKeyNotAuthorized(key.into()));
+    }
+    let stored = StoredKey::load(key).await?;
+
+    ctx.config
+        .hooks
+        .pre_sign(Some(state.did()), &op.to_json())
+        .await?;
+
+    let passphrase = util::passphrase("Key", "PLC_KEY_PASSPHRASE", ctx.non_interactive, false)?;
+    let op = op.sign(&stored.decrypt(passphrase)?);
+    record(
+        Action::Signed,
+        state,
+        &op,
+        &Signer::Key(key.into()),
+        Outcome::Succeeded,
+    )
+    .await?;
+
+    Ok(op)
+}
+
+/// Signs an operation that updates `state` to `data`, with `key` (or the configured
+/// default key) from the keystore if given and otherwise via the user's PDS.
+///
+/// Returns the signed operation along with a description of its signer.
+pub(super) async fn sign_update(
+    ctx: &AppContext,
+    state: &State,
+    data: PlcData,
+    key: Option<&str>,
+    token: Option<&str>,
+) -> Result<(SignedOperation, Signer), Error> {
+    // Fall back to the configured key, unless the user asked for the PDS to sign or the
+    // key can't sign for this DID.
+    let key = key.or_else(|| {
+        ctx.config.defaults.key.as_deref().filter(|key| {
+            token.is_none() && state.inner_data().rotation_keys.iter().any(|k| k == key)
+        })
+    });
+    match key {
+        Some(key) => {
+            let head = ctx
+                .directory
+                .audit_log(state.did())
+                .await?
+                .head()
+                .cloned()
+                .ok_or(Error::PlcDirectoryReturnedInvalidAuditLog)?;
+            let op = sign_locally(
+                ctx,
+                state,
+                UnsignedOperation::update(head, data),
+                &state.inner_data().rotation_keys,
+                key,
+            )
+            .await?;
+            Ok((op, Signer::Key(key.into())))
+        }
+        None => sign_via_pds(ctx, state, &data, token).await,
+    }
+}
+
+/// Submits a signed operation that updates `state`, once the confirmation policy is
+/// satisfied.
+///
+/// Operations signed by the user's PDS that keep the account on it are submitted
+/// through it, so that it learns of the change and announces it to the network. The
+/// PDS refuses operations that drop its keys or move the account elsewhere, so those
+/// (and anything it refuses) go to the directory directly, as does everything else.
+pub(super) async fn submit(
+    ctx: &AppContext,
+    state: &State,
+    op: &SignedOperation,
+    signer: Signer,
+    confirm_cid: Option<&str>,
+) -> Result<(), Error> {
+    check_appends(ctx, state, op).await?;
+
+    // Make sure the user has seen every warning before confirming.
+    ctx.report_warnings()?;
+    let kinds = op.kinds(Some(state.inner_data()));
+    ctx.config
+        .policy
+        .enforce(state.did(), op, &kinds, confirm_cid, ctx.non_interactive)?;
+
+    let res = match &signer {
+        Signer::Pds(pds) if keeps_pds(state, op) => match submit_via_pds(state, pds, op).await {
+            Err(Error::PdsPlcSubmissionFailed(atrium_xrpc::Error::XrpcResponse(e))) => {
+                println!(
+                    "{}",
+                    advisory(format!(
+                        "The PDS refused the operation ({e}); submitting it to the directory"
+                    )),
+                );
+                ctx.directory.submit(state.did(), op).await
+            }
+            res => res,
+        },
+        _ => ctx.directory.submit(state.did(), op).await,
+    };
+    let outcome = match &res {
+        Ok(()) => Outcome::Succeeded,
+        Err(e) => Outcome::Failed(format!("{e:?}")),
+    };
+    // The operation may already be live, so failing here would have the user retry a
+    // change that already happened.
+    if let Err(e) = record(Action::Submitted, state, op, &signer, outcome).await {
+        ctx.warn(Warning::SubmissionNotJournaled(
+            op.cid().as_ref().to_string(),
+            format!("{e:?}"),
+        ));
+    }
+    res?;
+
+    println!(
+        "Operation {} was {}",
+        op.cid().as_ref(),
+        valid("accepted by the directory"),
+    );
+
+    ctx.config
+        .hooks
+        .post_submit(
+            state.did(),
+            &serde_json::to_value(op).expect("can serialize"),
+        )
+        .await;
+    if let Some(data) = op.data() {
+        pins::update_if_pinned(state.did(), &data.rotation_keys).await?;
+    }
+
+    Ok(())
+}
+
+/// Checks that `op` is a valid next operation for the DID's audit log, so that an
+/// operation the directory would reject is never confirmed or submitted.
+async fn check_appends(ctx: &AppContext, state: &State, op: &SignedOperation) -> Result<(), Error> {
+    let log = match op.prev() {
+        // A genesis operation starts a new log.
+        None => AuditLog::empty(state.did().clone()),
+        Some(_) => ctx.directory.audit_log(state.did()).await?,
+    };
+    match log.validate_next(op, Datetime::new(Utc::now().fixed_offset())) {
+        Ok(()) => Ok(()),
+        Err(errors) => {
+            println!(
+                "Operation {} is {}:",
+                op.cid().as_ref(),
+                violation("invalid")
+            );
+            for e in errors {
+                println!("- {}", violation(e));
+            }
+            Err(Error::OperationInvalid)
+        }
+    }
+}
+
+/// Returns whether `op` keeps the account's PDS, signing key, and rotation keys, which
+/// the PDS requires of operations submitted through it.
+fn keeps_pds(state: &State, op: &SignedOperation) -> bool {
+    let (Some(data), current) = (op.data(), state.inner_data()) else {
+        return false;
+    };
+    data.endpoint() == current.endpoint()
+        && data.verification_methods.get("atproto") == current.verification_methods.get("atproto")
+        && current
+            .rotation_keys
+            .iter()
+            .all(|key| data.rotation_keys.contains(key))
+}
+
+async fn submit_via_pds(state: &State, pds: &str, op: &SignedOperation) -> Result<(), Error> {
+    let agent = pds::Agent::new(pds.into());
+    agent.resume_session(state.did()).await?;
+    agent.submit_plc_operation(op).await
+}
+
+async fn record(
+    action: Action,
+    state: &State,
+    op: &SignedOperation,
+    signer: &Signer,
+    outcome: Outcome,
+) -> Result<(), Error> {
+    journal::record(&JournalEntry {
+        time: Datetime::new(Utc::now().fixed_offset()),
+        action,
+        did: state.did().as_str().into(),
+        cid: op.cid().as_ref().to_string(),
+        signer: signer.to_string(),
+        outcome,
+        operation: serde_json::to_value(op).expect("can serialize"),
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::keeps_pds;
+    use crate::{
+        context::AppContext,
+        data::PlcData,
+        keystore::{KeyAlgorithm, SecretKey},
+        remote::plc::{mock::MockDirectory, testing::TestLog, UnsignedOperation},
+    };
+
+    #[tokio::test]
+    async fn pds_submission() {
+        let log = TestLog::with_genesis();
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&log));
+        let state = ctx.directory.resolve(&log.did()).await.unwrap();
+        let key = SecretKey::generate(KeyAlgorithm::P256);
+        let op = |change: &dyn Fn(&mut PlcData)| {
+            let mut data = state.inner_data().clone();
+            change(&mut data);
+            UnsignedOperation::update(log.cid_for(0), data).sign(&key)
+        };
+
+        assert!(keeps_pds(
+            &state,
+            &op(&|data| data.set_handle("bob.example.com"))
+        ));
+        assert!(keeps_pds(
+            &state,
+            &op(&|data| data.rotation_keys.push(key.did()))
+        ));
+
+        // The PDS refuses operations that drop its keys or move the account.
+        assert!(!keeps_pds(
+            &state,
+            &op(&|data| {
+                data.rotation_keys.pop();
+            })
+        ));
+        assert!(!keeps_pds(
+            &state,
+            &op(&|data| {
+                data.verification_methods
+                    .insert("atproto".into(), key.did());
+            })
+        ));
+        assert!(!keeps_pds(
+            &state,
+            &op(&|data| {
+                data.services.get_mut("atproto_pds").unwrap().endpoint =
+                    "https://pds.example.com".into();
+            })
+        ));
+    }
+}
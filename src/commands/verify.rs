@@ -0,0 +1,92 @@
+use crate::{
+    cli::VerifyDid,
+    context::AppContext,
+    error::Error,
+    output::{valid, violation},
+};
+
+impl VerifyDid {
+    pub(crate) async fn run(&self, ctx: &AppContext) -> Result<(), Error> {
+        let mut invalid = false;
+        let mut incomplete = false;
+        for did in &self.dids {
+            if did.method() != "did:plc" {
+                println!("{}: unsupported DID method {}", did.as_str(), did.method());
+                incomplete = true;
+                continue;
+            }
+
+            match ctx
+                .directory
+                .genesis_op(did)
+                .await
+                .map(|op| op.derived_did())
+            {
+                Ok(Some(derived)) if &derived == did => {
+                    println!(
+                        "{}: {}",
+                        did.as_str(),
+                        valid("derives from its genesis operation"),
+                    );
+                }
+                Ok(Some(derived)) => {
+                    invalid = true;
+                    println!(
+                        "{}: {}, genesis operation derives {}",
+                        did.as_str(),
+                        violation("INVALID"),
+                        derived.as_str(),
+                    );
+                }
+                Ok(None) => {
+                    invalid = true;
+                    println!(
+                        "{}: {}, first operation is not a creation operation",
+                        did.as_str(),
+                        violation("INVALID"),
+                    );
+                }
+                Err(e) => {
+                    incomplete = true;
+                    println!(
+                        "{}: failed to fetch genesis operation: {:?}",
+                        did.as_str(),
+                        e
+                    );
+                }
+            }
+        }
+
+        if invalid {
+            Err(Error::DidVerificationFailed)
+        } else if incomplete {
+            Err(Error::DidVerificationIncomplete)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::VerifyDid,
+        context::AppContext,
+        error::Error,
+        remote::plc::{mock::MockDirectory, testing::TestLog},
+    };
+
+    #[tokio::test]
+    async fn verify_did() {
+        let log = TestLog::with_genesis();
+        let unknown = TestLog::with_genesis();
+        let ctx = AppContext::for_tests(MockDirectory::default().with_log(&log));
+
+        let verify = |dids| VerifyDid { dids };
+        assert!(verify(vec![log.did()]).run(&ctx).await.is_ok());
+        assert!(matches!(
+            verify(vec![log.did(), unknown.did()]).run(&ctx).await,
+            Err(Error::DidVerificationIncomplete),
+        ));
+    }
+}
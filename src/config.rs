@@ -0,0 +1,177 @@
+//! The user's `config.toml`.
+
+use serde::Deserialize;
+use tokio::fs;
+use toml_edit::DocumentMut;
+
+use crate::{cli::TimeFormat, error::Error, hooks::Hooks, local, policy::Policy};
+
+pub(crate) const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Defaults for command-line flags.
+    #[serde(default)]
+    pub(crate) defaults: Defaults,
+    /// External commands to run around signing and submission.
+    #[serde(default)]
+    pub(crate) hooks: Hooks,
+    /// Guardrails for dangerous operations.
+    #[serde(default)]
+    pub(crate) policy: Policy,
+}
+
+/// Values used when the corresponding flag is not given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Defaults {
+    /// The PLC directory to query.
+    pub(crate) plc_directory: Option<String>,
+    /// How to display timestamps.
+    pub(crate) time: Option<TimeFormat>,
+    /// The rotation key in the keystore to sign updates with, as a `did:key`.
+    pub(crate) key: Option<String>,
+}
+
+impl Config {
+    /// Loads the config file, or the default configuration if there is none.
+    pub(crate) async fn load() -> Result<Self, Error> {
+        let Some(path) = local::config_file(CONFIG_FILE) else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(path).await {
+            Ok(data) => Self::parse(&data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::ConfigReadFailed(e)),
+        }
+    }
+
+    fn parse(data: &str) -> Result<Self, Error> {
+        toml::from_str(data).map_err(Error::ConfigInvalid)
+    }
+}
+
+/// Sets the default `name` in the config file to `value`, or removes it if `value` is
+/// `None`.
+///
+/// The rest of the file, including comments, is left as it is.
+pub(crate) async fn set_default(name: &str, value: Option<&str>) -> Result<(), Error> {
+    let path = local::config_file(CONFIG_FILE)
+        .ok_or_else(|| Error::ConfigSaveFailed(std::io::ErrorKind::NotFound.into()))?;
+    let data = match fs::read_to_string(&path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::ConfigReadFailed(e)),
+    };
+
+    let data = update_default(&data, name, value)?;
+    local::write_atomic(&path, data)
+        .await
+        .map_err(Error::ConfigSaveFailed)
+}
+
+/// Edits the default `name` in `data`.
+///
+/// Only the `[defaults]` table is checked, so that a mistake elsewhere in the file can
+/// still be worked around with `plc config`.
+fn update_default(data: &str, name: &str, value: Option<&str>) -> Result<String, Error> {
+    toml::from_str::<toml::Table>(data).map_err(Error::ConfigInvalid)?;
+    let mut doc = data
+        .parse::<DocumentMut>()
+        .expect("checked that it is valid TOML");
+
+    match value {
+        Some(value) => {
+            let defaults = doc
+                .entry("defaults")
+                .or_insert_with(toml_edit::table)
+                .as_table_like_mut()
+                .ok_or_else(|| Error::ConfigInvalidDefault(name.into()))?;
+            defaults.insert(name, toml_edit::value(value));
+        }
+        None => {
+            if let Some(defaults) = doc
+                .get_mut("defaults")
+                .and_then(|defaults| defaults.as_table_like_mut())
+            {
+                defaults.remove(name);
+            }
+        }
+    }
+
+    // Reject values that the config wouldn't load with.
+    let mut defaults = DocumentMut::new();
+    if let Some(item) = doc.get("defaults") {
+        defaults.insert("defaults", item.clone());
+    }
+    Config::parse(&defaults.to_string()).map_err(|_| Error::ConfigInvalidDefault(name.into()))?;
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_default, Config};
+    use crate::{
+        cli::TimeFormat,
+        policy::{OperationKind, Requirement},
+    };
+
+    #[test]
+    fn parse() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(
+            config.policy.requirement(&[OperationKind::Tombstone]),
+            Requirement::Allow,
+        );
+
+        let config = Config::parse(
+            r#"
+            [policy]
+            default = "phrase"
+            tombstone = "disable"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.policy.requirement(&[OperationKind::Update]),
+            Requirement::Phrase,
+        );
+        assert_eq!(
+            config.policy.requirement(&[OperationKind::Tombstone]),
+            Requirement::Disable,
+        );
+
+        assert!(Config::parse("[policy]\ntombstone = \"maybe\"").is_err());
+        assert!(Config::parse("[polcy]").is_err());
+    }
+
+    #[test]
+    fn defaults() {
+        let data = "# Organization policy\n[policy]\ntombstone = \"disable\"\n";
+        let data = update_default(data, "plc-directory", Some("https://plc.example.com")).unwrap();
+        let data = update_default(&data, "time", Some("relative")).unwrap();
+        assert!(data.starts_with("# Organization policy\n[policy]\n"));
+
+        let config = Config::parse(&data).unwrap();
+        assert_eq!(
+            config.defaults.plc_directory.as_deref(),
+            Some("https://plc.example.com"),
+        );
+        assert!(matches!(config.defaults.time, Some(TimeFormat::Relative)));
+        assert_eq!(
+            config.policy.requirement(&[OperationKind::Tombstone]),
+            Requirement::Disable,
+        );
+
+        let data = update_default(&data, "time", None).unwrap();
+        assert!(Config::parse(&data).unwrap().defaults.time.is_none());
+
+        assert!(update_default(&data, "time", Some("sometimes")).is_err());
+        assert!(update_default(&data, "colour", Some("always")).is_err());
+        // Mistakes outside the defaults don't stop them being edited.
+        let data = update_default("[polcy]\n", "time", Some("utc")).unwrap();
+        assert!(data.starts_with("[polcy]\n"));
+        assert!(update_default("[defaults", "time", Some("utc")).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cli::Options,
+    config::Config,
+    error::Error,
+    remote::{
+        handle,
+        plc::{Directory, PlcDirectoryClient},
+    },
+    warning::{Warning, Warnings},
+};
+
+/// Shared state for commands, constructed once from the global options and the
+/// config file.
+pub(crate) struct AppContext {
+    pub(crate) resolver: handle::Resolver,
+    pub(crate) directory: Arc<dyn Directory>,
+    /// The base URL of the configured directory.
+    pub(crate) directory_url: String,
+    /// The loaded config file.
+    pub(crate) config: Config,
+    /// Whether we must never prompt for input.
+    pub(crate) non_interactive: bool,
+    /// Whether any warning is fatal.
+    deny_warnings: bool,
+    warnings: Mutex<Warnings>,
+    cancel: CancellationToken,
+    ctrl_c: Once,
+}
+
+impl AppContext {
+    pub(crate) fn new(opts: &Options, config: Config) -> Result<Self, Error> {
+        let directory_url = opts
+            .plc_directory
+            .clone()
+            .or_else(|| config.defaults.plc_directory.clone())
+            .unwrap_or_else(|| crate::remote::plc::DEFAULT_DIRECTORY.into());
+
+        Ok(Self {
+            resolver: handle::Resolver::new(
+                &opts.dns.servers,
+                opts.dns.system,
+                opts.dns.timeout.map(Duration::from_secs),
+            )?,
+            directory: Arc::new(PlcDirectoryClient::new(&directory_url)),
+            directory_url,
+            config,
+            non_interactive: opts.non_interactive,
+            deny_warnings: opts.deny_warnings,
+            warnings: Mutex::default(),
+            cancel: CancellationToken::new(),
+            ctrl_c: Once::new(),
+        })
+    }
+
+    /// Constructs a context for tests, backed by the given directory.
+    #[cfg(test)]
+    pub(crate) fn for_tests(directory: impl Directory + 'static) -> Self {
+        Self {
+            resolver: handle::Resolver::new(&[], false, None).unwrap(),
+            directory: Arc::new(directory),
+            directory_url: crate::remote::plc::DEFAULT_DIRECTORY.into(),
+            config: Config::default(),
+            non_interactive: true,
+            deny_warnings: false,
+            warnings: Mutex::default(),
+            cancel: CancellationToken::new(),
+            ctrl_c: Once::new(),
+        }
+    }
+
+    /// Raises a warning, to be reported once before the command finishes.
+    pub(crate) fn warn(&self, warning: Warning) {
+        self.warnings.lock().expect("not poisoned").push(warning);
+    }
+
+    /// Reports any warnings raised since the last report, failing if warnings are
+    /// denied and any have been raised.
+    ///
+    /// Commands should call this before asking the user to confirm anything or
+    /// submitting an operation, so that the user sees the warnings first and a denied
+    /// warning stops the command before it has any effect.
+    pub(crate) fn report_warnings(&self) -> Result<(), Error> {
+        let mut warnings = self.warnings.lock().expect("not poisoned");
+        warnings.report(self.non_interactive);
+        match warnings.count() {
+            n if self.deny_warnings && n > 0 => Err(Error::WarningsDenied(n)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Cancels any in-progress work, as if the user pressed Ctrl-C.
+    #[cfg(test)]
+    pub(crate) fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Returns a token that is cancelled when the user presses Ctrl-C.
+    ///
+    /// Long-running commands should call this and abort cleanly when the token is
+    /// cancelled. Until it is called Ctrl-C keeps its default behaviour of terminating
+    /// the process, so commands that block on synchronous input (such as password
+    /// prompts) remain interruptible.
+    pub(crate) fn cancel_on_ctrl_c(&self) -> CancellationToken {
+        self.ctrl_c.call_once(|| {
+            let cancel = self.cancel.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    cancel.cancel();
+                }
+            });
+        });
+        self.cancel.clone()
+    }
+}
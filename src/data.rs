@@ -1,17 +1,18 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use atrium_api::types::string::Did;
 use atrium_crypto::Algorithm;
+use chrono::Utc;
 use diff::Diff;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    error::Error,
-    remote::{handle, plc},
+    context::AppContext, delegations, error::Error, output::format_time, pins, remote::pds,
+    warning::Warning,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct State {
     did: Did,
@@ -19,7 +20,7 @@ pub(crate) struct State {
     plc: PlcData,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Diff)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Diff)]
 #[diff(attr(
     #[derive(Debug)]
 ))]
@@ -41,17 +42,62 @@ pub(crate) struct Service {
     pub(crate) endpoint: String,
 }
 
+impl PlcData {
+    /// Returns the primary handle in this state.
+    pub(crate) fn handle(&self) -> Option<&str> {
+        self.also_known_as.iter().find_map(|uri| {
+            uri.strip_prefix("at://")
+                .map(|s| s.split_once('/').map(|(handle, _)| handle).unwrap_or(s))
+        })
+    }
+
+    /// Returns the PDS endpoint in this state.
+    pub(crate) fn endpoint(&self) -> Option<&str> {
+        self.services
+            .get("atproto_pds")
+            .and_then(|v| (v.r#type == "AtprotoPersonalDataServer").then_some(v.endpoint.as_str()))
+    }
+
+    /// Sets the primary handle, keeping any other `also_known_as` entries.
+    pub(crate) fn set_handle(&mut self, handle: &str) {
+        let uri = format!("at://{handle}");
+        match self
+            .also_known_as
+            .iter()
+            .position(|aka| aka.starts_with("at://"))
+        {
+            Some(i) => self.also_known_as[i] = uri,
+            None => self.also_known_as.insert(0, uri),
+        }
+    }
+
+    /// Sets the endpoint of the ATProto PDS.
+    pub(crate) fn set_endpoint(&mut self, endpoint: String) {
+        self.services.insert(
+            "atproto_pds".into(),
+            Service {
+                r#type: "AtprotoPersonalDataServer".into(),
+                endpoint,
+            },
+        );
+    }
+}
+
 impl State {
-    pub(crate) async fn resolve(user: &str, client: &Client) -> Result<Self, Error> {
+    pub(crate) fn new(did: Did, plc: PlcData) -> Self {
+        Self { did, plc }
+    }
+
+    pub(crate) async fn resolve(user: &str, ctx: &AppContext) -> Result<Self, Error> {
         // Parse `user` as a DID, or look it up as a handle.
         let did = match Did::new(user.into()) {
             Ok(did) => did,
-            Err(_) => handle::resolve(user, client).await?,
+            Err(_) => ctx.resolver.resolve(user).await?,
         };
 
         // Fetch the current DID state.
         let state = match did.method() {
-            "did:plc" => plc::get_state(&did, client).await,
+            "did:plc" => ctx.directory.resolve(&did).await,
             method => Err(Error::UnsupportedDidMethod(method.into())),
         }?;
 
@@ -60,6 +106,23 @@ impl State {
             return Err(Error::HandleInvalid);
         }
 
+        // Warn if the rotation keys have changed since they were pinned.
+        if let Some(change) = pins::check(&state).await? {
+            ctx.warn(Warning::PinnedKeysChanged(did.as_str().into(), change));
+        }
+
+        // Nag about temporary rotation keys that have outstayed their welcome.
+        let now = Utc::now().fixed_offset();
+        for (key, delegation) in delegations::for_did(&did, state.inner_data()).await? {
+            if delegation.is_expired(&now) {
+                ctx.warn(Warning::TemporaryKeyExpired {
+                    did: did.as_str().into(),
+                    key,
+                    expired: format_time(&delegation.expires),
+                });
+            }
+        }
+
         Ok(state)
     }
 
@@ -73,10 +136,7 @@ impl State {
 
     /// Returns the current primary handle for this DID.
     pub(crate) fn handle(&self) -> Option<&str> {
-        self.plc.also_known_as.iter().find_map(|uri| {
-            uri.strip_prefix("at://")
-                .map(|s| s.split_once('/').map(|(handle, _)| handle).unwrap_or(s))
-        })
+        self.plc.handle()
     }
 
     pub(crate) fn signing_key(&self) -> Option<atrium_crypto::Result<Key>> {
@@ -90,10 +150,45 @@ impl State {
 
     /// Returns the endpoint for the user's currently-configured PDS.
     pub(crate) fn endpoint(&self) -> Option<&str> {
-        self.plc
-            .services
-            .get("atproto_pds")
-            .and_then(|v| (v.r#type == "AtprotoPersonalDataServer").then_some(v.endpoint.as_str()))
+        self.plc.endpoint()
+    }
+
+    /// Asks the user's PDS for the status of their account, or returns `None` if the
+    /// DID has no PDS.
+    pub(crate) async fn account_status(&self) -> Result<Option<AccountStatus>, Error> {
+        match self.endpoint() {
+            Some(pds) => pds::Agent::new(pds.into())
+                .get_repo_status(&self.did)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The status of an account, as reported by the PDS hosting it.
+///
+/// This is independent of the DID: a PDS can deactivate an account (at the user's
+/// request, or before migrating it elsewhere) while its DID remains active in the PLC
+/// directory. Tombstoning the DID instead removes it from the directory permanently.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum AccountStatus {
+    Active,
+    /// The account is not active, with the reason the PDS gave (such as `deactivated`,
+    /// `suspended`, or `takendown`).
+    Inactive(Option<String>),
+    /// The PDS doesn't host an account for this DID.
+    NotHosted,
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountStatus::Active => write!(f, "Active"),
+            AccountStatus::Inactive(Some(status)) => write!(f, "Inactive ({status})"),
+            AccountStatus::Inactive(None) => write!(f, "Inactive"),
+            AccountStatus::NotHosted => write!(f, "Not hosted by the PDS"),
+        }
     }
 }
 
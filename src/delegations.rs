@@ -0,0 +1,129 @@
+//! Rotation keys that were added temporarily, such as for a support engineer during a
+//! migration.
+//!
+//! PLC has no notion of a key expiring, so we track the intended expiry locally and
+//! nag until the key is removed.
+
+use std::collections::BTreeMap;
+
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{data::PlcData, error::Error, local};
+
+pub(crate) const DELEGATIONS_FILE: &str = "delegations.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Delegation {
+    /// When the key should be removed.
+    pub(crate) expires: Datetime,
+}
+
+impl Delegation {
+    pub(crate) fn is_expired(&self, now: &DateTime<FixedOffset>) -> bool {
+        self.expires.as_ref() <= now
+    }
+}
+
+/// A mapping from DIDs to their temporary rotation keys.
+type Delegations = BTreeMap<String, BTreeMap<String, Delegation>>;
+
+/// Records that `key` was added to `did` temporarily, until `expires`.
+pub(crate) async fn add(did: &Did, key: String, expires: Datetime) -> Result<(), Error> {
+    let mut delegations = load().await?;
+    delegations
+        .entry(did.as_str().into())
+        .or_default()
+        .insert(key, Delegation { expires });
+    save(&delegations).await
+}
+
+/// Returns the temporary rotation keys for `did` that are still present in `data`.
+pub(crate) async fn for_did(
+    did: &Did,
+    data: &PlcData,
+) -> Result<BTreeMap<String, Delegation>, Error> {
+    Ok(load()
+        .await?
+        .remove(did.as_str())
+        .map(|keys| current(keys, data))
+        .unwrap_or_default())
+}
+
+/// Stops tracking `key` for `did`, if it was temporary.
+pub(crate) async fn remove(did: &Did, key: &str) -> Result<(), Error> {
+    let mut delegations = load().await?;
+    let Some(keys) = delegations.get_mut(did.as_str()) else {
+        return Ok(());
+    };
+    if keys.remove(key).is_some() {
+        if keys.is_empty() {
+            delegations.remove(did.as_str());
+        }
+        save(&delegations).await?;
+    }
+    Ok(())
+}
+
+/// Filters out delegations for keys that have since been removed from the DID.
+fn current(keys: BTreeMap<String, Delegation>, data: &PlcData) -> BTreeMap<String, Delegation> {
+    keys.into_iter()
+        .filter(|(key, _)| data.rotation_keys.contains(key))
+        .collect()
+}
+
+async fn load() -> Result<Delegations, Error> {
+    match local::config_file(DELEGATIONS_FILE) {
+        Some(path) => match fs::read_to_string(&path).await {
+            Ok(data) => serde_json::from_str(&data).map_err(Error::DelegationsFileInvalid),
+            // The user hasn't added any temporary keys.
+            Err(_) => Ok(Delegations::new()),
+        },
+        None => Ok(Delegations::new()),
+    }
+}
+
+async fn save(delegations: &Delegations) -> Result<(), Error> {
+    let path = local::config_file(DELEGATIONS_FILE).ok_or(Error::DelegationsSaveFailed)?;
+    let data =
+        serde_json::to_string_pretty(delegations).map_err(|_| Error::DelegationsSaveFailed)?;
+    local::write_atomic(&path, data)
+        .await
+        .map_err(|_| Error::DelegationsSaveFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use atrium_api::types::string::Datetime;
+
+    use super::{current, Delegation};
+    use crate::data::PlcData;
+
+    #[test]
+    fn removed_keys_are_ignored() {
+        let delegation = |expires: &str| Delegation {
+            expires: expires.parse::<Datetime>().unwrap(),
+        };
+        let keys = BTreeMap::from([
+            ("a".to_string(), delegation("2024-01-01T00:00:00Z")),
+            ("b".to_string(), delegation("2024-06-01T00:00:00Z")),
+        ]);
+        let data = PlcData {
+            rotation_keys: vec!["b".into(), "c".into()],
+            verification_methods: HashMap::new(),
+            also_known_as: vec![],
+            services: HashMap::new(),
+        };
+
+        let current = current(keys, &data);
+        assert_eq!(current.keys().collect::<Vec<_>>(), vec!["b"]);
+
+        let now = "2024-03-01T00:00:00Z".parse::<Datetime>().unwrap();
+        assert!(!current["b"].is_expired(now.as_ref()));
+        assert!(delegation("2024-01-01T00:00:00Z").is_expired(now.as_ref()));
+    }
+}
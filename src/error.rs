@@ -2,28 +2,313 @@ use std::fmt;
 
 use atrium_api::types::string::Handle;
 
+use crate::{
+    policy::OperationKind,
+    remote::{
+        handle::{DnsTxtError, WellKnownError},
+        plc::SpecProfile,
+    },
+};
+
+/// The exit status when validation fails, as opposed to the command failing to run.
+const VALIDATION_FAILED_EXIT_CODE: i32 = 3;
+
 pub(crate) enum Error {
+    AppPasswordReadFailed(std::io::Error),
+    AppPasswordRequired,
+    AuditLogInvalid,
+    Cancelled,
+    ConfigInvalid(toml::de::Error),
+    ConfigInvalidDefault(String),
+    ConfigReadFailed(std::io::Error),
+    ConfigSaveFailed(std::io::Error),
+    DelegationsFileInvalid(serde_json::Error),
+    DelegationsSaveFailed,
     DidDocumentHasNoPds,
+    DidHasNoHandle,
+    DidListInvalid(String),
+    DidTombstoned(String),
+    DidVerificationFailed,
+    DidVerificationIncomplete,
+    DirectoryAuditFailed,
+    DirectoryHistoryChanged,
+    DirectoryProbeFailed,
+    DnsConfigInvalid(String),
+    EmailTokenRequired,
+    EntryVerificationFailed,
+    EvidenceSaveFailed(std::io::Error),
+    FleetCheckFailed,
+    FleetConfigInvalid(toml::de::Error),
+    FleetConfigInvalidKey(String, atrium_crypto::Error),
+    FleetConfigReadFailed(std::io::Error),
+    FleetEnforceNeedsForce,
+    GrindAttemptsExhausted(u64),
+    GrindPrefixInvalid(String),
+    HandleAlreadySet(String),
     HandleInvalid,
-    HandleResolutionFailed,
+    HandleProofTimedOut,
+    HandleResolutionFailed {
+        dns: DnsTxtError,
+        https: WellKnownError,
+    },
+    HookFailed(String, std::io::Error),
+    HookVetoed(String, std::process::ExitStatus),
+    HttpClientInitFailed(reqwest::Error),
+    IncidentFileInvalid(serde_json::Error),
+    IncidentNeedsForce,
+    IncidentNoLocalKey,
+    IncidentSaveFailed,
+    InputReadFailed(std::io::Error),
+    InvalidLogEntry(serde_json::Error),
+    InvalidOperation(serde_json::Error),
+    InvalidOperationCbor(String),
+    JournalEntryNotFound(usize),
+    JournalInvalid(usize, serde_json::Error),
+    JournalReadFailed(std::io::Error),
+    JournalWriteFailed(std::io::Error),
+    KeyAlgorithmRequired,
+    KeystoreDecryptionFailed(age::DecryptError),
+    KeystoreInvalid(serde_json::Error),
+    KeystoreKeyNotFound(String),
+    KeystoreReadFailed(std::io::Error),
+    KeystoreWriteFailed(std::io::Error),
+    KnownKeysFileInvalid(serde_json::Error),
+    KnownKeysSaveFailed,
+    LogAuditFailed,
     LoggedIntoDifferentAccount(Handle),
     NeedToLogIn,
     NeedToLogInAgain,
+    NonCanonicalOperation,
+    NoRecoverableOperations,
+    OperationInvalid,
+    OperationMakesNoChanges,
+    OperationNotRecoverable(String),
+    OutputWriteFailed(std::io::Error),
+    PassphraseEmpty,
+    PassphraseMismatch,
+    PassphraseReadFailed(std::io::Error),
+    PassphraseRequired(&'static str),
+    PdsAlreadySet(String),
     PdsAuthFailed(atrium_xrpc::Error<atrium_api::com::atproto::server::create_session::Error>),
     PdsAuthRefreshFailed(
         atrium_xrpc::Error<atrium_api::com::atproto::server::refresh_session::Error>,
     ),
+    PdsEndpointInvalid(String),
+    PdsPlcSignatureRequestFailed(
+        atrium_xrpc::Error<
+            atrium_api::com::atproto::identity::request_plc_operation_signature::Error,
+        >,
+    ),
+    PdsPlcSigningFailed(
+        atrium_xrpc::Error<atrium_api::com::atproto::identity::sign_plc_operation::Error>,
+    ),
+    PdsPlcSubmissionFailed(
+        atrium_xrpc::Error<atrium_api::com::atproto::identity::submit_plc_operation::Error>,
+    ),
+    PdsRepoStatusFailed(atrium_xrpc::Error<atrium_api::com::atproto::sync::get_repo_status::Error>),
+    PdsReturnedInvalidOperation,
     PdsServerKeyLookupFailed(
         atrium_xrpc::Error<
             atrium_api::com::atproto::identity::get_recommended_did_credentials::Error,
         >,
     ),
+    PdsSignedUnexpectedOperation,
+    PinsFileInvalid(serde_json::Error),
+    PinsSaveFailed,
+    PlcDirectoryRejectedOperation(String),
     PlcDirectoryRequestFailed(reqwest::Error),
     PlcDirectoryReturnedInvalidAuditLog,
     PlcDirectoryReturnedInvalidDidDocument,
+    PlcDirectoryReturnedInvalidExport,
     PlcDirectoryReturnedInvalidOperationLog,
+    PolicyCidMismatch(String),
+    PolicyConfirmationFailed,
+    PolicyConfirmationRequired,
+    PolicyForbidsOperation(OperationKind),
+    PrivateKeyInvalid(String),
+    ProfileContainsUnknownFile(String),
+    ProfileDecryptionFailed(age::DecryptError),
+    ProfileHooksNeedAllowHooks,
+    ProfileInvalid(serde_json::Error),
+    ProfileReadFailed(std::io::Error),
+    ProfileUnsupportedVersion(u32),
+    ProfileWouldOverwrite(String),
+    ProfileWriteFailed(std::io::Error),
+    RecoveryCancelled,
+    RecoveryNeedsForce,
+    RotationKeyAlreadyPresent(String),
+    RotationKeyIsLast,
+    RotationKeyNotFound(String),
+    RotationKeyRemovalCancelled,
+    RotationKeyRemovalNeedsForce,
+    SecretOnCommandLine,
+    SelfTestFailed,
+    #[cfg(feature = "server")]
+    ServerBindFailed(std::io::Error),
+    #[cfg(feature = "server")]
+    ServerFailed(std::io::Error),
     SessionSaveFailed,
+    SigningKeyNotAuthorized(String),
+    SubsetLogInvalid(String),
+    TombstoneCancelled,
+    TombstoneConfirmationMismatch,
+    TombstoneNeedsConfirmation,
     UnsupportedDidMethod(String),
+    UnsupportedOperationTemplate,
+    WarningsDenied(usize),
+}
+
+impl Error {
+    /// The exit status for a process that fails with this error.
+    ///
+    /// Validation failures get a status of their own, so that scripts can tell them
+    /// apart from a command failing to run.
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Error::DidVerificationFailed
+            | Error::DirectoryAuditFailed
+            | Error::EntryVerificationFailed
+            | Error::LogAuditFailed => VALIDATION_FAILED_EXIT_CODE,
+            _ => 1,
+        }
+    }
+
+    /// A stable identifier for this kind of error, for machine-readable diagnostics.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Error::AppPasswordReadFailed(..) => "app-password-read-failed",
+            Error::AppPasswordRequired => "app-password-required",
+            Error::AuditLogInvalid => "audit-log-invalid",
+            Error::Cancelled => "cancelled",
+            Error::ConfigInvalid(..) => "config-invalid",
+            Error::ConfigInvalidDefault(..) => "config-invalid-default",
+            Error::ConfigReadFailed(..) => "config-read-failed",
+            Error::ConfigSaveFailed(..) => "config-save-failed",
+            Error::DelegationsFileInvalid(..) => "delegations-file-invalid",
+            Error::DelegationsSaveFailed => "delegations-save-failed",
+            Error::DidDocumentHasNoPds => "did-document-has-no-pds",
+            Error::DidHasNoHandle => "did-has-no-handle",
+            Error::DidListInvalid(..) => "did-list-invalid",
+            Error::DidTombstoned(..) => "did-tombstoned",
+            Error::DidVerificationFailed => "did-verification-failed",
+            Error::DidVerificationIncomplete => "did-verification-incomplete",
+            Error::DirectoryAuditFailed => "directory-audit-failed",
+            Error::DirectoryHistoryChanged => "directory-history-changed",
+            Error::DirectoryProbeFailed => "directory-probe-failed",
+            Error::DnsConfigInvalid(..) => "dns-config-invalid",
+            Error::EmailTokenRequired => "email-token-required",
+            Error::EntryVerificationFailed => "entry-verification-failed",
+            Error::EvidenceSaveFailed(..) => "evidence-save-failed",
+            Error::FleetCheckFailed => "fleet-check-failed",
+            Error::FleetConfigInvalid(..) => "fleet-config-invalid",
+            Error::FleetConfigInvalidKey(..) => "fleet-config-invalid-key",
+            Error::FleetConfigReadFailed(..) => "fleet-config-read-failed",
+            Error::FleetEnforceNeedsForce => "fleet-enforce-needs-force",
+            Error::GrindAttemptsExhausted(..) => "grind-attempts-exhausted",
+            Error::GrindPrefixInvalid(..) => "grind-prefix-invalid",
+            Error::HandleAlreadySet(..) => "handle-already-set",
+            Error::HandleInvalid => "handle-invalid",
+            Error::HandleProofTimedOut => "handle-proof-timed-out",
+            Error::HandleResolutionFailed { .. } => "handle-resolution-failed",
+            Error::HookFailed(..) => "hook-failed",
+            Error::HookVetoed(..) => "hook-vetoed",
+            Error::HttpClientInitFailed(..) => "http-client-init-failed",
+            Error::IncidentFileInvalid(..) => "incident-file-invalid",
+            Error::IncidentNeedsForce => "incident-needs-force",
+            Error::IncidentNoLocalKey => "incident-no-local-key",
+            Error::IncidentSaveFailed => "incident-save-failed",
+            Error::InputReadFailed(..) => "input-read-failed",
+            Error::InvalidLogEntry(..) => "invalid-log-entry",
+            Error::InvalidOperation(..) => "invalid-operation",
+            Error::InvalidOperationCbor(..) => "invalid-operation-cbor",
+            Error::JournalEntryNotFound(..) => "journal-entry-not-found",
+            Error::JournalInvalid(..) => "journal-invalid",
+            Error::JournalReadFailed(..) => "journal-read-failed",
+            Error::JournalWriteFailed(..) => "journal-write-failed",
+            Error::KeyAlgorithmRequired => "key-algorithm-required",
+            Error::KeystoreDecryptionFailed(..) => "keystore-decryption-failed",
+            Error::KeystoreInvalid(..) => "keystore-invalid",
+            Error::KeystoreKeyNotFound(..) => "keystore-key-not-found",
+            Error::KeystoreReadFailed(..) => "keystore-read-failed",
+            Error::KeystoreWriteFailed(..) => "keystore-write-failed",
+            Error::KnownKeysFileInvalid(..) => "known-keys-file-invalid",
+            Error::KnownKeysSaveFailed => "known-keys-save-failed",
+            Error::LogAuditFailed => "log-audit-failed",
+            Error::LoggedIntoDifferentAccount(..) => "logged-into-different-account",
+            Error::NeedToLogIn => "need-to-log-in",
+            Error::NeedToLogInAgain => "need-to-log-in-again",
+            Error::NonCanonicalOperation => "non-canonical-operation",
+            Error::NoRecoverableOperations => "no-recoverable-operations",
+            Error::OperationInvalid => "operation-invalid",
+            Error::OperationMakesNoChanges => "operation-makes-no-changes",
+            Error::OperationNotRecoverable(..) => "operation-not-recoverable",
+            Error::OutputWriteFailed(..) => "output-write-failed",
+            Error::PassphraseEmpty => "passphrase-empty",
+            Error::PassphraseMismatch => "passphrase-mismatch",
+            Error::PassphraseReadFailed(..) => "passphrase-read-failed",
+            Error::PassphraseRequired(..) => "passphrase-required",
+            Error::PdsAlreadySet(..) => "pds-already-set",
+            Error::PdsAuthFailed(..) => "pds-auth-failed",
+            Error::PdsAuthRefreshFailed(..) => "pds-auth-refresh-failed",
+            Error::PdsEndpointInvalid(..) => "pds-endpoint-invalid",
+            Error::PdsPlcSignatureRequestFailed(..) => "pds-plc-signature-request-failed",
+            Error::PdsPlcSigningFailed(..) => "pds-plc-signing-failed",
+            Error::PdsPlcSubmissionFailed(..) => "pds-plc-submission-failed",
+            Error::PdsRepoStatusFailed(..) => "pds-repo-status-failed",
+            Error::PdsReturnedInvalidOperation => "pds-returned-invalid-operation",
+            Error::PdsServerKeyLookupFailed(..) => "pds-server-key-lookup-failed",
+            Error::PdsSignedUnexpectedOperation => "pds-signed-unexpected-operation",
+            Error::PinsFileInvalid(..) => "pins-file-invalid",
+            Error::PinsSaveFailed => "pins-save-failed",
+            Error::PlcDirectoryRejectedOperation(..) => "plc-directory-rejected-operation",
+            Error::PlcDirectoryRequestFailed(..) => "plc-directory-request-failed",
+            Error::PlcDirectoryReturnedInvalidAuditLog => {
+                "plc-directory-returned-invalid-audit-log"
+            }
+            Error::PlcDirectoryReturnedInvalidDidDocument => {
+                "plc-directory-returned-invalid-did-document"
+            }
+            Error::PlcDirectoryReturnedInvalidExport => "plc-directory-returned-invalid-export",
+            Error::PlcDirectoryReturnedInvalidOperationLog => {
+                "plc-directory-returned-invalid-operation-log"
+            }
+            Error::PolicyCidMismatch(..) => "policy-cid-mismatch",
+            Error::PolicyConfirmationFailed => "policy-confirmation-failed",
+            Error::PolicyConfirmationRequired => "policy-confirmation-required",
+            Error::PolicyForbidsOperation(..) => "policy-forbids-operation",
+            Error::PrivateKeyInvalid(..) => "private-key-invalid",
+            Error::ProfileContainsUnknownFile(..) => "profile-contains-unknown-file",
+            Error::ProfileDecryptionFailed(..) => "profile-decryption-failed",
+            Error::ProfileHooksNeedAllowHooks => "profile-hooks-need-allow-hooks",
+            Error::ProfileInvalid(..) => "profile-invalid",
+            Error::ProfileReadFailed(..) => "profile-read-failed",
+            Error::ProfileUnsupportedVersion(..) => "profile-unsupported-version",
+            Error::ProfileWouldOverwrite(..) => "profile-would-overwrite",
+            Error::ProfileWriteFailed(..) => "profile-write-failed",
+            Error::RecoveryCancelled => "recovery-cancelled",
+            Error::RecoveryNeedsForce => "recovery-needs-force",
+            Error::RotationKeyAlreadyPresent(..) => "rotation-key-already-present",
+            Error::RotationKeyIsLast => "rotation-key-is-last",
+            Error::RotationKeyNotFound(..) => "rotation-key-not-found",
+            Error::RotationKeyRemovalCancelled => "rotation-key-removal-cancelled",
+            Error::RotationKeyRemovalNeedsForce => "rotation-key-removal-needs-force",
+            Error::SecretOnCommandLine => "secret-on-command-line",
+            Error::SelfTestFailed => "self-test-failed",
+            #[cfg(feature = "server")]
+            Error::ServerBindFailed(..) => "server-bind-failed",
+            #[cfg(feature = "server")]
+            Error::ServerFailed(..) => "server-failed",
+            Error::SessionSaveFailed => "session-save-failed",
+            Error::SigningKeyNotAuthorized(..) => "signing-key-not-authorized",
+            Error::SubsetLogInvalid(..) => "subset-log-invalid",
+            Error::TombstoneCancelled => "tombstone-cancelled",
+            Error::TombstoneConfirmationMismatch => "tombstone-confirmation-mismatch",
+            Error::TombstoneNeedsConfirmation => "tombstone-needs-confirmation",
+            Error::UnsupportedDidMethod(..) => "unsupported-did-method",
+            Error::UnsupportedOperationTemplate => "unsupported-operation-template",
+            Error::WarningsDenied(..) => "warnings-denied",
+        }
+    }
 }
 
 // Rust only supports `fn main() -> Result<(), E: Debug>`, so we implement `Debug`
@@ -31,15 +316,96 @@ pub(crate) enum Error {
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Error::AppPasswordReadFailed(e) => write!(f, "Failed to read app password: {e}"),
+            Error::AppPasswordRequired => write!(f, "An app password is required; set PLC_APP_PASSWORD or pass --app-password-file"),
+            Error::AuditLogInvalid => write!(f, "The audit log is invalid, so it is not clear which operations can be nullified; run `plc ops audit` for details"),
+            Error::Cancelled => write!(f, "Cancelled"),
+            Error::ConfigInvalid(e) => write!(f, "Invalid config file: {e}"),
+            Error::ConfigInvalidDefault(name) => write!(f, "Invalid value for default {name}"),
+            Error::ConfigReadFailed(e) => write!(f, "Failed to read config file: {e}"),
+            Error::ConfigSaveFailed(e) => write!(f, "Failed to save config file: {e}"),
+            Error::DelegationsFileInvalid(e) => write!(f, "Failed to parse temporary keys file: {e}"),
+            Error::DelegationsSaveFailed => write!(f, "Failed to save temporary keys"),
             Error::DidDocumentHasNoPds => write!(f, "The user's DID document doesn't contain a services entry for a PDS"),
+            Error::DidHasNoHandle => write!(f, "The DID document doesn't contain a handle; pass one with --handle"),
+            Error::DidListInvalid(line) => write!(f, "Invalid DID in the list: {line}"),
+            Error::DidTombstoned(did) => write!(f, "{did} has been tombstoned, permanently deactivating it in the PLC directory"),
+            Error::DidVerificationFailed => write!(f, "One or more DIDs don't derive from their genesis operations"),
+            Error::DidVerificationIncomplete => write!(f, "One or more DIDs could not be verified"),
+            Error::DirectoryAuditFailed => write!(f, "One or more DIDs in the directory have invalid audit logs"),
+            Error::DirectoryHistoryChanged => write!(f, "The directory has rewritten the history of one or more DIDs"),
+            Error::DirectoryProbeFailed => write!(f, "One or more directory health checks failed"),
+            Error::DnsConfigInvalid(e) => write!(f, "Failed to load DNS resolver configuration: {e}"),
+            Error::EmailTokenRequired => write!(f, "An email token from the PDS is required; set PLC_EMAIL_TOKEN or pass --token"),
+            Error::EntryVerificationFailed => write!(f, "The audit log entry is invalid"),
+            Error::EvidenceSaveFailed(e) => write!(f, "Failed to save evidence: {e}"),
+            Error::FleetCheckFailed => write!(f, "One or more accounts violate the fleet policy"),
+            Error::FleetConfigInvalid(e) => write!(f, "Invalid fleet configuration: {e}"),
+            Error::FleetConfigInvalidKey(key, e) => write!(f, "Invalid key {key} in fleet configuration: {e}"),
+            Error::FleetConfigReadFailed(e) => write!(f, "Failed to read fleet configuration: {e}"),
+            Error::FleetEnforceNeedsForce => write!(f, "Enforcing a fleet policy non-interactively requires --force"),
+            Error::GrindAttemptsExhausted(attempts) => write!(f, "No DID with the prefix was found in {attempts} attempts"),
+            Error::GrindPrefixInvalid(e) => write!(f, "Invalid DID prefix: {e}"),
+            Error::HandleAlreadySet(handle) => write!(f, "@{handle} is already the primary handle"),
             Error::HandleInvalid => write!(f, "The provided handle is invalid (it does not appear in the DID document it points to)"),
-            Error::HandleResolutionFailed => write!(f, "Handle resolution failed"),
+            Error::HandleProofTimedOut => write!(f, "The handle could not be verified before the timeout"),
+            Error::HandleResolutionFailed { dns, https } => write!(f, "Handle resolution failed\n- DNS TXT method: {dns}\n- HTTPS well-known method: {https}"),
+            Error::HookFailed(command, e) => write!(f, "Failed to run hook {command}: {e}"),
+            Error::HookVetoed(command, status) => write!(f, "Hook {command} rejected the operation ({status})"),
+            Error::HttpClientInitFailed(e) => write!(f, "Failed to initialize HTTP client: {e}"),
+            Error::IncidentFileInvalid(e) => write!(f, "Incident progress file is invalid: {e}"),
+            Error::IncidentNeedsForce => write!(f, "Responding to an incident non-interactively requires --force"),
+            Error::IncidentNoLocalKey => write!(f, "None of the DID's rotation keys are in the keystore; import one with `plc keys import`"),
+            Error::IncidentSaveFailed => write!(f, "Failed to save incident progress"),
+            Error::InputReadFailed(e) => write!(f, "Failed to read input: {e}"),
+            Error::InvalidLogEntry(e) => write!(f, "Invalid audit log entry: {e}"),
+            Error::InvalidOperation(e) => write!(f, "Invalid PLC operation: {e}"),
+            Error::InvalidOperationCbor(e) => write!(f, "Invalid DAG-CBOR PLC operation: {e}"),
+            Error::JournalEntryNotFound(index) => write!(f, "There is no journal entry {index}"),
+            Error::JournalInvalid(line, e) => write!(f, "Journal entry on line {line} is invalid: {e}"),
+            Error::JournalReadFailed(e) => write!(f, "Failed to read the journal: {e}"),
+            Error::JournalWriteFailed(e) => write!(f, "Failed to write to the journal: {e}"),
+            Error::KeyAlgorithmRequired => write!(f, "Hex-encoded keys don't say which curve they are for; pass --algorithm"),
+            Error::KeystoreDecryptionFailed(e) => write!(f, "Failed to decrypt key: {e}"),
+            Error::KeystoreInvalid(e) => write!(f, "Failed to parse stored key: {e}"),
+            Error::KeystoreKeyNotFound(key) => write!(f, "{key} is not in the keystore"),
+            Error::KeystoreReadFailed(e) => write!(f, "Failed to read key from the keystore: {e}"),
+            Error::KeystoreWriteFailed(e) => write!(f, "Failed to save key to the keystore: {e}"),
+            Error::KnownKeysFileInvalid(e) => write!(f, "Failed to parse known keys file: {e}"),
+            Error::KnownKeysSaveFailed => write!(f, "Failed to save known keys"),
+            Error::LogAuditFailed => write!(f, "The audit log failed validation"),
             Error::LoggedIntoDifferentAccount(handle) => write!(f, "Currently logged into {}", handle.as_str()),
             Error::NeedToLogIn => write!(f, "This operation requires authentication, please log in"),
             Error::NeedToLogInAgain => write!(f, "Session has expired, please log in again"),
+            Error::NonCanonicalOperation => write!(f, "PLC operation is not canonically encoded as DAG-CBOR, so its CID would not match"),
+            Error::NoRecoverableOperations => write!(f, "The key can't nullify any operations: none were signed by a lower-priority rotation key within the last {} hours", SpecProfile::default().recovery_window.num_hours()),
+            Error::OperationInvalid => write!(
+                f,
+                "The directory would reject the operation as invalid for this DID's audit log"
+            ),
+            Error::OperationMakesNoChanges => write!(f, "The operation would not change anything; pass --handle, --pds, --signing-key, or --template"),
+            Error::OperationNotRecoverable(cid) => write!(f, "The key can't nullify operation {cid}"),
+            Error::OutputWriteFailed(e) => write!(f, "Failed to write output: {e}"),
+            Error::PassphraseEmpty => write!(f, "The passphrase can't be empty"),
+            Error::PassphraseMismatch => write!(f, "Passphrases didn't match"),
+            Error::PassphraseReadFailed(e) => write!(f, "Failed to read passphrase: {e}"),
+            Error::PassphraseRequired(var) => write!(f, "A passphrase is required; set {var}"),
+            Error::PdsAlreadySet(endpoint) => write!(f, "The DID already points to the PDS at {endpoint}"),
             Error::PdsAuthFailed(e) => write!(f, "Failed to authenticate to PDS: {}", e),
             Error::PdsAuthRefreshFailed(e) => write!(f, "Failed to refresh PDS session: {}", e),
+            Error::PdsEndpointInvalid(e) => write!(f, "Invalid PDS endpoint: {e}"),
+            Error::PdsPlcSignatureRequestFailed(e) => write!(f, "Failed to request a PLC operation signature from the PDS: {e}"),
+            Error::PdsPlcSigningFailed(e) => write!(f, "The PDS failed to sign the PLC operation: {e}"),
+            Error::PdsPlcSubmissionFailed(e) => write!(f, "The PDS failed to submit the PLC operation: {e}"),
+            Error::PdsRepoStatusFailed(e) => write!(f, "Failed to fetch the account status from the PDS: {e}"),
+            Error::PdsReturnedInvalidOperation => write!(f, "The PDS returned an invalid PLC operation"),
             Error::PdsServerKeyLookupFailed(e) => write!(f, "Lookup of PDS server keys failed: {}", e),
+            Error::PdsSignedUnexpectedOperation => write!(f, "The PDS signed an operation that differs from the requested change; it was not submitted"),
+            Error::PinsFileInvalid(e) => write!(f, "Failed to parse pinned keys file: {e}"),
+            Error::PinsSaveFailed => write!(f, "Failed to save pinned keys"),
+            Error::PlcDirectoryRejectedOperation(reason) => {
+                write!(f, "plc.directory rejected the operation: {reason}")
+            }
             Error::PlcDirectoryRequestFailed(e) => {
                 write!(f, "An error occurred while talking to plc.directory: {e}")
             }
@@ -49,11 +415,47 @@ impl fmt::Debug for Error {
             Error::PlcDirectoryReturnedInvalidDidDocument => {
                 write!(f, "plc.directory returned an invalid DID document")
             }
+            Error::PlcDirectoryReturnedInvalidExport => {
+                write!(f, "plc.directory returned an invalid export page")
+            }
             Error::PlcDirectoryReturnedInvalidOperationLog => {
                 write!(f, "plc.directory returned an invalid operation log")
             }
+            Error::PolicyCidMismatch(cid) => write!(f, "Policy requires confirming this operation with --confirm-cid {cid}"),
+            Error::PolicyConfirmationFailed => write!(f, "Confirmation did not match; the operation was not submitted"),
+            Error::PolicyConfirmationRequired => write!(f, "Policy requires interactive confirmation of this operation"),
+            Error::PolicyForbidsOperation(kind) => write!(f, "Policy forbids submitting a {kind} operation"),
+            Error::PrivateKeyInvalid(e) => write!(f, "Invalid private key: {e}"),
+            Error::ProfileContainsUnknownFile(name) => write!(f, "Profile contains an unexpected file {name}"),
+            Error::ProfileDecryptionFailed(e) => write!(f, "Failed to decrypt profile: {e}"),
+            Error::ProfileHooksNeedAllowHooks => write!(f, "Importing a profile with hooks non-interactively requires --allow-hooks"),
+            Error::ProfileInvalid(e) => write!(f, "Failed to parse profile: {e}"),
+            Error::ProfileReadFailed(e) => write!(f, "Failed to read profile: {e}"),
+            Error::ProfileUnsupportedVersion(v) => write!(f, "Profile version {v} is not supported by this version of plc"),
+            Error::ProfileWouldOverwrite(path) => write!(f, "{path} already exists; pass --force to replace it"),
+            Error::ProfileWriteFailed(e) => write!(f, "Failed to write profile: {e}"),
+            Error::RecoveryCancelled => write!(f, "Cancelled; no operations were nullified"),
+            Error::RecoveryNeedsForce => write!(f, "Nullifying operations non-interactively requires --force"),
+            Error::RotationKeyAlreadyPresent(key) => write!(f, "{key} is already a rotation key"),
+            Error::RotationKeyIsLast => write!(f, "Can't remove the DID's only rotation key"),
+            Error::RotationKeyNotFound(key) => write!(f, "{key} is not a rotation key for this DID"),
+            Error::RotationKeyRemovalCancelled => write!(f, "Cancelled; the rotation key was not removed"),
+            Error::RotationKeyRemovalNeedsForce => write!(f, "Removing a rotation key without confirmation requires --force"),
+            Error::SecretOnCommandLine => write!(f, "In non-interactive mode, secrets must be provided via the environment or a file, not as arguments"),
+            Error::SelfTestFailed => write!(f, "One or more self-test checks failed"),
+            #[cfg(feature = "server")]
+            Error::ServerBindFailed(e) => write!(f, "Failed to bind server: {e}"),
+            #[cfg(feature = "server")]
+            Error::ServerFailed(e) => write!(f, "Server failed: {e}"),
             Error::SessionSaveFailed => write!(f, "Failed to save PDS session data"),
+            Error::SigningKeyNotAuthorized(key) => write!(f, "{key} is not a rotation key for this DID, so it can't sign operations for it"),
+            Error::SubsetLogInvalid(did) => write!(f, "The audit log for {did} is missing or invalid, so the subset would not be audit-valid"),
+            Error::TombstoneCancelled => write!(f, "Cancelled; the DID was not deactivated"),
+            Error::TombstoneConfirmationMismatch => write!(f, "The confirmation did not match the DID; it was not deactivated"),
+            Error::TombstoneNeedsConfirmation => write!(f, "Deactivating a DID non-interactively requires --confirm-did"),
             Error::UnsupportedDidMethod(method) => write!(f, "Unsupported DID method {}; this tool only works with did:plc identities", method),
+            Error::UnsupportedOperationTemplate => write!(f, "Only plc_operation and plc_tombstone operations can be signed"),
+            Error::WarningsDenied(n) => write!(f, "{n} warning(s) raised with --deny-warnings"),
         }
     }
 }
@@ -0,0 +1,349 @@
+//! Configuration invariants for a fleet of DIDs.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::{
+    data::{Key, PlcData, State},
+    error::Error,
+};
+
+/// The contents of a `fleet.toml` file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Fleet {
+    /// The policy that applies to every account, unless overridden.
+    #[serde(default)]
+    policy: Policy,
+    #[serde(default, rename = "account")]
+    pub(crate) accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Account {
+    /// A handle or DID.
+    pub(crate) user: String,
+    /// Overrides for the fleet-wide policy.
+    #[serde(default)]
+    policy: Policy,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Policy {
+    /// The PDS endpoint the account must be hosted on.
+    pds: Option<String>,
+    /// Rotation keys the account must contain.
+    rotation_keys: Option<Vec<RequiredKey>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RequiredKey {
+    /// The key, as a `did:key`.
+    key: String,
+    /// The position the key must have, if any.
+    index: Option<usize>,
+}
+
+/// A way in which an account does not satisfy its policy.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Violation {
+    WrongPds {
+        expected: String,
+        actual: Option<String>,
+    },
+    RotationKeyMissing {
+        key: String,
+    },
+    RotationKeyMisplaced {
+        key: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::WrongPds {
+                expected,
+                actual: Some(actual),
+            } => write!(f, "PDS is {actual}, expected {expected}"),
+            Violation::WrongPds {
+                expected,
+                actual: None,
+            } => write!(f, "No PDS configured, expected {expected}"),
+            Violation::RotationKeyMissing { key } => write!(f, "Missing rotation key {key}"),
+            Violation::RotationKeyMisplaced {
+                key,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Rotation key {key} is at [{actual}], expected [{expected}]"
+            ),
+        }
+    }
+}
+
+impl Fleet {
+    /// Loads a fleet configuration from the given path.
+    pub(crate) async fn load(path: &Path) -> Result<Self, Error> {
+        let data = fs::read_to_string(path)
+            .await
+            .map_err(Error::FleetConfigReadFailed)?;
+        Self::parse(&data)
+    }
+
+    fn parse(data: &str) -> Result<Self, Error> {
+        let fleet: Self = toml::from_str(data).map_err(Error::FleetConfigInvalid)?;
+
+        // Catch typos in keys before we contact the network.
+        for key in fleet
+            .accounts
+            .iter()
+            .flat_map(|account| fleet.policy_for(account).rotation_keys)
+            .flatten()
+        {
+            Key::did(&key.key).map_err(|e| Error::FleetConfigInvalidKey(key.key.clone(), e))?;
+        }
+
+        Ok(fleet)
+    }
+
+    /// Returns the effective policy for the given account.
+    fn policy_for(&self, account: &Account) -> Policy {
+        Policy {
+            pds: account.policy.pds.clone().or(self.policy.pds.clone()),
+            rotation_keys: account
+                .policy
+                .rotation_keys
+                .clone()
+                .or(self.policy.rotation_keys.clone()),
+        }
+    }
+
+    /// Checks the given account's current state against its policy.
+    pub(crate) fn check(&self, account: &Account, state: &State) -> Vec<Violation> {
+        let policy = self.policy_for(account);
+        let mut violations = vec![];
+
+        if let Some(expected) = policy.pds {
+            let actual = state.endpoint();
+            if actual.map(|s| s.trim_end_matches('/')) != Some(expected.trim_end_matches('/')) {
+                violations.push(Violation::WrongPds {
+                    expected,
+                    actual: actual.map(String::from),
+                });
+            }
+        }
+
+        let rotation_keys = state.rotation_keys();
+        for required in policy.rotation_keys.into_iter().flatten() {
+            let key = Key::did(&required.key).expect("checked in Fleet::parse");
+            match (
+                rotation_keys
+                    .iter()
+                    .position(|k| k.as_ref().is_ok_and(|k| k == &key)),
+                required.index,
+            ) {
+                (None, _) => violations.push(Violation::RotationKeyMissing { key: required.key }),
+                (Some(actual), Some(expected)) if actual != expected => {
+                    violations.push(Violation::RotationKeyMisplaced {
+                        key: required.key,
+                        expected,
+                        actual,
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        violations
+    }
+
+    /// Returns the given account's state, changed as little as possible to satisfy its
+    /// policy.
+    ///
+    /// Required rotation keys are moved to (or inserted at) their positions, and
+    /// required keys without a position are appended if they are missing. Other keys
+    /// keep their relative order.
+    pub(crate) fn corrected(&self, account: &Account, state: &State) -> PlcData {
+        let policy = self.policy_for(account);
+        let mut data = state.inner_data().clone();
+
+        if let Some(expected) = policy.pds {
+            if state.endpoint().map(|s| s.trim_end_matches('/'))
+                != Some(expected.trim_end_matches('/'))
+            {
+                data.set_endpoint(expected);
+            }
+        }
+
+        let required = policy.rotation_keys.unwrap_or_default();
+        let mut placed = required
+            .iter()
+            .filter_map(|r| r.index.map(|index| (index, r.key.clone())))
+            .collect::<BTreeMap<_, _>>();
+        let mut others = data
+            .rotation_keys
+            .iter()
+            .filter(|key| !placed.values().any(|k| k == *key))
+            .cloned()
+            .collect::<Vec<_>>();
+        others.extend(
+            required
+                .iter()
+                .filter(|r| r.index.is_none() && !data.rotation_keys.contains(&r.key))
+                .map(|r| r.key.clone()),
+        );
+
+        let mut others = others.into_iter();
+        let mut rotation_keys = vec![];
+        loop {
+            if let Some(key) = placed.remove(&rotation_keys.len()) {
+                rotation_keys.push(key);
+            } else if let Some(key) = others.next() {
+                rotation_keys.push(key);
+            } else {
+                // Positions past the end of the list can't be met exactly.
+                rotation_keys.extend(std::mem::take(&mut placed).into_values());
+                break;
+            }
+        }
+        data.rotation_keys = rotation_keys;
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fleet, Violation};
+    use crate::data::State;
+
+    const ORG_KEY: &str = "did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg";
+    const OTHER_KEY: &str = "did:key:zQ3shpKnbdPx3g3CmPf5cRVTPe1HtSwVn5ish3wSnDPQCbLJK";
+
+    fn state(rotation_keys: &[&str], pds: &str) -> State {
+        serde_json::from_value(serde_json::json!({
+            "did": "did:plc:z72i7hdynmk6r22z27h6tvur",
+            "rotationKeys": rotation_keys,
+            "verificationMethods": {},
+            "alsoKnownAs": ["at://alice.example.com"],
+            "services": {
+                "atproto_pds": {
+                    "type": "AtprotoPersonalDataServer",
+                    "endpoint": pds,
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn check() {
+        let fleet = Fleet::parse(&format!(
+            r#"
+            [policy]
+            pds = "https://pds.example.com"
+            rotation_keys = [{{ key = "{ORG_KEY}", index = 0 }}]
+
+            [[account]]
+            user = "alice.example.com"
+
+            [[account]]
+            user = "bot.example.com"
+            policy = {{ pds = "https://bots.example.com/" }}
+            "#
+        ))
+        .unwrap();
+        let (alice, bot) = (&fleet.accounts[0], &fleet.accounts[1]);
+
+        let good = state(&[ORG_KEY, OTHER_KEY], "https://pds.example.com");
+        assert_eq!(fleet.check(alice, &good), vec![]);
+        assert_eq!(
+            fleet.check(bot, &good),
+            vec![Violation::WrongPds {
+                expected: "https://bots.example.com/".into(),
+                actual: Some("https://pds.example.com".into()),
+            }],
+        );
+
+        let misplaced = state(&[OTHER_KEY, ORG_KEY], "https://pds.example.com/");
+        assert_eq!(
+            fleet.check(alice, &misplaced),
+            vec![Violation::RotationKeyMisplaced {
+                key: ORG_KEY.into(),
+                expected: 0,
+                actual: 1,
+            }],
+        );
+
+        let missing = state(&[OTHER_KEY], "https://pds.example.com");
+        assert_eq!(
+            fleet.check(alice, &missing),
+            vec![Violation::RotationKeyMissing {
+                key: ORG_KEY.into()
+            }],
+        );
+    }
+
+    #[test]
+    fn corrected() {
+        let fleet = Fleet::parse(&format!(
+            r#"
+            [policy]
+            pds = "https://pds.example.com"
+            rotation_keys = [{{ key = "{ORG_KEY}", index = 0 }}]
+
+            [[account]]
+            user = "alice.example.com"
+
+            [[account]]
+            user = "bot.example.com"
+            policy = {{ rotation_keys = [{{ key = "{ORG_KEY}" }}] }}
+            "#
+        ))
+        .unwrap();
+        let (alice, bot) = (&fleet.accounts[0], &fleet.accounts[1]);
+
+        let good = state(&[ORG_KEY, OTHER_KEY], "https://pds.example.com");
+        assert_eq!(&fleet.corrected(alice, &good), good.inner_data());
+
+        let bad = state(&[OTHER_KEY, ORG_KEY], "https://old.example.com");
+        let fixed = fleet.corrected(alice, &bad);
+        assert_eq!(fixed.rotation_keys, vec![ORG_KEY, OTHER_KEY]);
+        assert_eq!(fixed.endpoint(), Some("https://pds.example.com"));
+
+        let missing = state(&[OTHER_KEY], "https://pds.example.com");
+        assert_eq!(
+            fleet.corrected(alice, &missing).rotation_keys,
+            vec![ORG_KEY, OTHER_KEY],
+        );
+        assert_eq!(
+            fleet.corrected(bot, &missing).rotation_keys,
+            vec![OTHER_KEY, ORG_KEY],
+        );
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(Fleet::parse(
+            r#"
+            [policy]
+            rotation_keys = [{ key = "did:key:invalid" }]
+
+            [[account]]
+            user = "alice.example.com"
+            "#
+        )
+        .is_err());
+    }
+}
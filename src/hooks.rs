@@ -0,0 +1,144 @@
+//! External commands run before signing and after submitting operations.
+//!
+//! Each hook receives the operation as JSON on stdin. A pre-sign hook can veto the
+//! operation by exiting with a non-zero status; post-submit hooks run after the fact,
+//! so their failures are only reported.
+
+use std::process::Stdio;
+
+use atrium_api::types::string::Did;
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{error::Error, output::violation};
+
+/// The `[hooks]` table of the config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Hooks {
+    /// Run before an operation is signed, e.g. to check it against a change ticket.
+    #[serde(default)]
+    pub(crate) pre_sign: Vec<Hook>,
+    /// Run after an operation is submitted, e.g. to notify a team or update a CMDB.
+    #[serde(default)]
+    pub(crate) post_submit: Vec<Hook>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Hook {
+    pub(crate) command: String,
+    #[serde(default)]
+    pub(crate) args: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Stage {
+    PreSign,
+    PostSubmit,
+}
+
+impl Stage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Stage::PreSign => "pre-sign",
+            Stage::PostSubmit => "post-submit",
+        }
+    }
+}
+
+impl Hook {
+    /// Runs the hook, passing `operation` on stdin.
+    ///
+    /// The hook's output is passed through to the user. It can also read the stage and
+    /// DID from the `PLC_HOOK` and `PLC_DID` environment variables.
+    async fn run(
+        &self,
+        stage: Stage,
+        did: Option<&Did>,
+        operation: &serde_json::Value,
+    ) -> Result<(), Error> {
+        let failed = |e| Error::HookFailed(self.command.clone(), e);
+
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .env("PLC_HOOK", stage.as_str())
+            .stdin(Stdio::piped());
+        if let Some(did) = did {
+            command.env("PLC_DID", did.as_str());
+        }
+        let mut child = command.spawn().map_err(failed)?;
+
+        let input = serde_json::to_vec(operation).expect("can serialize");
+        let mut stdin = child.stdin.take().expect("piped");
+        // The hook may exit without reading its input; that's its prerogative.
+        let _ = stdin.write_all(&input).await;
+        drop(stdin);
+
+        let status = child.wait().await.map_err(failed)?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::HookVetoed(self.command.clone(), status))
+        }
+    }
+}
+
+impl Hooks {
+    /// Runs the pre-sign hooks in order, stopping at the first that vetoes `operation`.
+    pub(crate) async fn pre_sign(
+        &self,
+        did: Option<&Did>,
+        operation: &serde_json::Value,
+    ) -> Result<(), Error> {
+        for hook in &self.pre_sign {
+            hook.run(Stage::PreSign, did, operation).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs every post-submit hook, reporting (but otherwise ignoring) failures.
+    pub(crate) async fn post_submit(&self, did: &Did, operation: &serde_json::Value) {
+        for hook in &self.post_submit {
+            if let Err(e) = hook.run(Stage::PostSubmit, Some(did), operation).await {
+                eprintln!("{} {e:?}", violation("Warning:"));
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{Hook, Hooks};
+    use crate::error::Error;
+
+    fn hook(script: &str) -> Hook {
+        Hook {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+        }
+    }
+
+    #[tokio::test]
+    async fn pre_sign_veto() {
+        let operation = serde_json::json!({ "type": "plc_tombstone" });
+
+        let hooks = Hooks {
+            pre_sign: vec![hook(r#"grep -q plc_operation"#)],
+            post_submit: vec![],
+        };
+        assert!(matches!(
+            hooks.pre_sign(None, &operation).await,
+            Err(Error::HookVetoed(..)),
+        ));
+
+        let hooks = Hooks {
+            pre_sign: vec![hook(
+                r#"grep -q plc_tombstone && [ "$PLC_HOOK" = pre-sign ]"#,
+            )],
+            post_submit: vec![],
+        };
+        assert!(hooks.pre_sign(None, &operation).await.is_ok());
+    }
+}
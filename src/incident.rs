@@ -0,0 +1,170 @@
+//! The progress of responding to a compromised identity, so the response can be
+//! resumed if it is interrupted.
+//!
+//! Each DID has at most one open incident, stored in the `incidents` directory under
+//! the config directory until the response is complete.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use atrium_api::types::string::{Datetime, Did};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{error::Error, local};
+
+const INCIDENTS_DIR: &str = "incidents";
+
+/// The steps of an incident response, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Step {
+    /// Audit the log, and find the operations that can still be nullified.
+    Audit,
+    /// Nullify those operations.
+    Recover,
+    /// Remove the rotation keys that signed them.
+    RotateKeys,
+    /// Produce a timeline of the incident.
+    Report,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Audit => write!(f, "Audit"),
+            Step::Recover => write!(f, "Recovery"),
+            Step::RotateKeys => write!(f, "Key rotation"),
+            Step::Report => write!(f, "Report"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Incident {
+    pub(crate) started: Datetime,
+    /// The rotation key from the keystore that the response is signed with.
+    pub(crate) key: String,
+    /// The earliest operation that the audit found to be malicious, if any.
+    #[serde(default)]
+    pub(crate) nullify_from: Option<String>,
+    /// The untrusted rotation keys that signed the operations being nullified.
+    #[serde(default)]
+    pub(crate) compromised_keys: Vec<String>,
+    #[serde(default)]
+    pub(crate) steps: Vec<CompletedStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CompletedStep {
+    pub(crate) step: Step,
+    pub(crate) completed: Datetime,
+    /// What the step did, for the timeline.
+    pub(crate) outcome: String,
+}
+
+impl Incident {
+    pub(crate) fn new(key: String) -> Self {
+        Self {
+            started: Datetime::new(Utc::now().fixed_offset()),
+            key,
+            nullify_from: None,
+            compromised_keys: vec![],
+            steps: vec![],
+        }
+    }
+
+    /// Loads the open incident for `did`, if any.
+    pub(crate) async fn load(did: &Did) -> Result<Option<Self>, Error> {
+        let Some(path) = incident_file(did) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(&path).await {
+            Ok(data) => serde_json::from_str(&data)
+                .map(Some)
+                .map_err(Error::IncidentFileInvalid),
+            // There is no open incident.
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub(crate) fn is_done(&self, step: Step) -> bool {
+        self.steps.iter().any(|s| s.step == step)
+    }
+
+    /// Records that `step` is complete, saving progress so far.
+    pub(crate) async fn complete(
+        &mut self,
+        did: &Did,
+        step: Step,
+        outcome: String,
+    ) -> Result<(), Error> {
+        self.steps.push(CompletedStep {
+            step,
+            completed: Datetime::new(Utc::now().fixed_offset()),
+            outcome,
+        });
+        self.save(did).await
+    }
+
+    /// Saves the incident as the open incident for `did`.
+    pub(crate) async fn save(&self, did: &Did) -> Result<(), Error> {
+        let path = incident_file(did).ok_or(Error::IncidentSaveFailed)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|_| Error::IncidentSaveFailed)?;
+        }
+        let data = serde_json::to_string_pretty(self).map_err(|_| Error::IncidentSaveFailed)?;
+        local::write_atomic(&path, data)
+            .await
+            .map_err(|_| Error::IncidentSaveFailed)
+    }
+
+    /// Closes the open incident for `did`.
+    pub(crate) async fn close(did: &Did) -> Result<(), Error> {
+        match incident_file(did) {
+            Some(path) => match fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(Error::IncidentSaveFailed)
+                }
+                _ => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+fn incident_file(did: &Did) -> Option<PathBuf> {
+    // `:` is not valid in Windows filenames.
+    local::config_file(format!(
+        "{INCIDENTS_DIR}/{}.json",
+        did.as_str().replace(':', "_")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Incident, Step};
+
+    #[test]
+    fn progress_round_trips() {
+        let data = r#"{
+            "started": "2024-06-01T00:00:00Z",
+            "key": "did:key:zDnae",
+            "steps": [
+                {"step": "audit", "completed": "2024-06-01T00:01:00Z", "outcome": "Found 1"},
+                {"step": "rotate-keys", "completed": "2024-06-01T00:02:00Z", "outcome": "Done"}
+            ]
+        }"#;
+        let incident: Incident = serde_json::from_str(data).unwrap();
+        assert!(incident.compromised_keys.is_empty());
+        assert!(incident.is_done(Step::Audit));
+        assert!(!incident.is_done(Step::Recover));
+        assert!(incident.is_done(Step::RotateKeys));
+
+        let reencoded = serde_json::to_string(&incident).unwrap();
+        assert!(reencoded.contains(r#""step":"rotate-keys""#));
+    }
+}
@@ -0,0 +1,122 @@
+//! An append-only local journal of the operations `plc` signs or submits.
+//!
+//! When something goes wrong long after the fact, the journal lets users reconstruct
+//! exactly what this tool did to their identity.
+
+use atrium_api::types::string::Datetime;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{error::Error, local};
+
+pub(crate) const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// What the tool did with an operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Signed,
+    Submitted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "result", content = "error")]
+pub(crate) enum Outcome {
+    Succeeded,
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct JournalEntry {
+    pub(crate) time: Datetime,
+    pub(crate) action: Action,
+    pub(crate) did: String,
+    pub(crate) cid: String,
+    /// The key (or service) used to sign the operation.
+    pub(crate) signer: String,
+    pub(crate) outcome: Outcome,
+    /// The signed operation itself.
+    pub(crate) operation: serde_json::Value,
+}
+
+/// Appends an entry to the journal.
+pub(crate) async fn record(entry: &JournalEntry) -> Result<(), Error> {
+    let path = local::config_file(JOURNAL_FILE)
+        .ok_or_else(|| Error::JournalWriteFailed(std::io::ErrorKind::NotFound.into()))?;
+
+    let mut line = serde_json::to_vec(entry).expect("can serialize");
+    line.push(b'\n');
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(Error::JournalWriteFailed)?;
+    file.write_all(&line)
+        .await
+        .map_err(Error::JournalWriteFailed)?;
+    file.sync_data().await.map_err(Error::JournalWriteFailed)
+}
+
+/// Loads every entry in the journal, oldest first.
+pub(crate) async fn load() -> Result<Vec<JournalEntry>, Error> {
+    let Some(path) = local::config_file(JOURNAL_FILE) else {
+        return Ok(vec![]);
+    };
+    let data = match fs::read_to_string(path).await {
+        Ok(data) => data,
+        // Nothing has been journaled yet.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(Error::JournalReadFailed(e)),
+    };
+
+    parse(&data)
+}
+
+fn parse(data: &str) -> Result<Vec<JournalEntry>, Error> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| Error::JournalInvalid(i + 1, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use atrium_api::types::string::Datetime;
+    use chrono::Utc;
+
+    use super::{parse, Action, JournalEntry, Outcome};
+
+    fn entry(action: Action, outcome: Outcome) -> JournalEntry {
+        JournalEntry {
+            time: Datetime::new(Utc::now().fixed_offset()),
+            action,
+            did: "did:plc:z72i7hdynmk6r22z27h6tvur".into(),
+            cid: "bafyreiaaaa".into(),
+            signer: "did:key:zQ3shhCGUqDKjStzuDxPkTxN6ujddP4RkEKJJouJGRRkaLGbg".into(),
+            outcome,
+            operation: serde_json::json!({"type": "plc_operation"}),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries = [
+            entry(Action::Signed, Outcome::Succeeded),
+            entry(Action::Submitted, Outcome::Failed("rejected".into())),
+        ];
+
+        let data = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect::<String>();
+        let parsed = parse(&data).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].action, Action::Signed);
+        assert_eq!(parsed[1].outcome, Outcome::Failed("rejected".into()));
+        assert!(parse("{not json}\n").is_err());
+    }
+}
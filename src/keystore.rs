@@ -0,0 +1,304 @@
+//! Rotation keys generated or imported into `plc`, encrypted at rest.
+//!
+//! Each key is stored in its own file in the `keys` directory under the config
+//! directory. The public parts are stored in the clear so keys can be listed without
+//! a passphrase; the private key is encrypted with age.
+
+use std::path::PathBuf;
+
+use age::secrecy::SecretString;
+use atrium_api::types::string::Datetime;
+use atrium_crypto::keypair::{Did as _, Export, P256Keypair, Secp256k1Keypair};
+use base64ct::{Base64, Encoding};
+use chrono::Utc;
+use clap::ValueEnum;
+use p256::pkcs8::{DecodePrivateKey, LineEnding};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use zeroize::Zeroizing;
+
+use crate::{error::Error, local};
+
+pub(crate) const KEYS_DIR: &str = "keys";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum KeyAlgorithm {
+    /// NIST P-256, which hardware keys commonly support.
+    #[default]
+    P256,
+    /// secp256k1, which most PDS implementations use.
+    K256,
+}
+
+/// The encodings a private key can be imported from or exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum KeyFormat {
+    /// The raw scalar, hex-encoded. Importing this requires `--algorithm`.
+    Hex,
+    /// A multicodec-prefixed, base58btc multibase string, as used by `goat`.
+    #[default]
+    Multibase,
+    /// A SEC1 PEM file (PKCS #8 PEM files can also be imported).
+    Pem,
+}
+
+/// The multicodec prefixes (as varints) for private keys.
+const P256_PRIV_PREFIX: [u8; 2] = [0x86, 0x26];
+const K256_PRIV_PREFIX: [u8; 2] = [0x81, 0x26];
+
+/// An unencrypted private key.
+pub(crate) struct SecretKey {
+    algorithm: KeyAlgorithm,
+    /// The raw scalar, which is always valid for `algorithm`.
+    bytes: Zeroizing<Vec<u8>>,
+}
+
+impl SecretKey {
+    pub(crate) fn generate(algorithm: KeyAlgorithm) -> Self {
+        let bytes = match algorithm {
+            KeyAlgorithm::P256 => P256Keypair::create(&mut OsRng).export(),
+            KeyAlgorithm::K256 => Secp256k1Keypair::create(&mut OsRng).export(),
+        };
+        Self {
+            algorithm,
+            bytes: Zeroizing::new(bytes),
+        }
+    }
+
+    /// Parses a private key in any of the supported formats.
+    ///
+    /// Hex-encoded keys don't say which curve they are for, so `algorithm` must be
+    /// given for them.
+    pub(crate) fn parse(input: &str, algorithm: Option<KeyAlgorithm>) -> Result<Self, Error> {
+        let input = input.trim();
+        let invalid = |e: &dyn std::fmt::Display| Error::PrivateKeyInvalid(e.to_string());
+
+        let (algorithm, bytes) = if input.starts_with("-----BEGIN") {
+            if let Ok(key) = p256::SecretKey::from_sec1_pem(input)
+                .or_else(|_| p256::SecretKey::from_pkcs8_pem(input))
+            {
+                (KeyAlgorithm::P256, key.to_bytes().to_vec())
+            } else {
+                let key = k256::SecretKey::from_sec1_pem(input)
+                    .or_else(|_| k256::SecretKey::from_pkcs8_pem(input))
+                    .map_err(|e| invalid(&e))?;
+                (KeyAlgorithm::K256, key.to_bytes().to_vec())
+            }
+        } else if let Ok(bytes) = hex::decode(input) {
+            (algorithm.ok_or(Error::KeyAlgorithmRequired)?, bytes)
+        } else {
+            let (_, decoded) = multibase::decode(input).map_err(|e| invalid(&e))?;
+            let decoded = Zeroizing::new(decoded);
+            match decoded.split_at(2.min(decoded.len())) {
+                (prefix, bytes) if prefix == P256_PRIV_PREFIX => {
+                    (KeyAlgorithm::P256, bytes.to_vec())
+                }
+                (prefix, bytes) if prefix == K256_PRIV_PREFIX => {
+                    (KeyAlgorithm::K256, bytes.to_vec())
+                }
+                _ => {
+                    return Err(Error::PrivateKeyInvalid(
+                        "not a P-256 or secp256k1 private key".into(),
+                    ))
+                }
+            }
+        };
+        let bytes = Zeroizing::new(bytes);
+
+        // Check that the scalar is valid for the curve.
+        match algorithm {
+            KeyAlgorithm::P256 => P256Keypair::import(&bytes).map(|_| ()),
+            KeyAlgorithm::K256 => Secp256k1Keypair::import(&bytes).map(|_| ()),
+        }
+        .map_err(|e| invalid(&e))?;
+
+        Ok(Self { algorithm, bytes })
+    }
+
+    /// Returns the public key, as a `did:key`.
+    pub(crate) fn did(&self) -> String {
+        match self.algorithm {
+            KeyAlgorithm::P256 => P256Keypair::import(&self.bytes).expect("valid").did(),
+            KeyAlgorithm::K256 => Secp256k1Keypair::import(&self.bytes).expect("valid").did(),
+        }
+    }
+
+    /// Signs `msg`, returning the signature in its "low-S" form.
+    pub(crate) fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        match self.algorithm {
+            KeyAlgorithm::P256 => P256Keypair::import(&self.bytes).expect("valid").sign(msg),
+            KeyAlgorithm::K256 => Secp256k1Keypair::import(&self.bytes)
+                .expect("valid")
+                .sign(msg),
+        }
+        .expect("valid keys can sign")
+    }
+
+    pub(crate) fn encode(&self, format: KeyFormat) -> Zeroizing<String> {
+        match format {
+            KeyFormat::Hex => Zeroizing::new(hex::encode(&self.bytes)),
+            KeyFormat::Multibase => {
+                let prefix = match self.algorithm {
+                    KeyAlgorithm::P256 => P256_PRIV_PREFIX,
+                    KeyAlgorithm::K256 => K256_PRIV_PREFIX,
+                };
+                let data = Zeroizing::new([&prefix[..], &self.bytes].concat());
+                Zeroizing::new(multibase::encode(multibase::Base::Base58Btc, &data))
+            }
+            KeyFormat::Pem => match self.algorithm {
+                KeyAlgorithm::P256 => p256::SecretKey::from_slice(&self.bytes)
+                    .expect("valid")
+                    .to_sec1_pem(LineEnding::LF),
+                KeyAlgorithm::K256 => k256::SecretKey::from_slice(&self.bytes)
+                    .expect("valid")
+                    .to_sec1_pem(LineEnding::LF),
+            }
+            .expect("can encode"),
+        }
+    }
+}
+
+/// A key held in the keystore.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StoredKey {
+    /// The public key, as a `did:key`.
+    pub(crate) did: String,
+    pub(crate) algorithm: KeyAlgorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) label: Option<String>,
+    pub(crate) created: Datetime,
+    /// The private key, encrypted with age and Base64-encoded.
+    secret: String,
+}
+
+impl StoredKey {
+    /// Wraps a private key for storage, encrypting it to `passphrase`.
+    pub(crate) fn new(key: &SecretKey, label: Option<String>, passphrase: SecretString) -> Self {
+        let encrypted = age::encrypt(&age::scrypt::Recipient::new(passphrase), &key.bytes)
+            .expect("in-memory encryption can't fail");
+
+        Self {
+            did: key.did(),
+            algorithm: key.algorithm,
+            label,
+            created: Datetime::new(Utc::now().fixed_offset()),
+            secret: Base64::encode_string(&encrypted),
+        }
+    }
+
+    /// Loads the key with the given `did:key` from the keystore.
+    pub(crate) async fn load(did: &str) -> Result<Self, Error> {
+        let path = key_file(did).ok_or_else(|| Error::KeystoreKeyNotFound(did.into()))?;
+        let data = match fs::read_to_string(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::KeystoreKeyNotFound(did.into()))
+            }
+            Err(e) => return Err(Error::KeystoreReadFailed(e)),
+        };
+        serde_json::from_str(&data).map_err(Error::KeystoreInvalid)
+    }
+
+    /// Decrypts the private key.
+    pub(crate) fn decrypt(&self, passphrase: SecretString) -> Result<SecretKey, Error> {
+        let encrypted = Base64::decode_vec(&self.secret)
+            .map_err(|_| Error::PrivateKeyInvalid("stored key is not valid Base64".into()))?;
+        let bytes = age::decrypt(&age::scrypt::Identity::new(passphrase), &encrypted)
+            .map(Zeroizing::new)
+            .map_err(Error::KeystoreDecryptionFailed)?;
+
+        let key = SecretKey {
+            algorithm: self.algorithm,
+            bytes,
+        };
+        // Don't trust the stored public parts.
+        match self.algorithm {
+            KeyAlgorithm::P256 => P256Keypair::import(&key.bytes).map(|_| ()),
+            KeyAlgorithm::K256 => Secp256k1Keypair::import(&key.bytes).map(|_| ()),
+        }
+        .map_err(|e| Error::PrivateKeyInvalid(e.to_string()))?;
+        if key.did() != self.did {
+            return Err(Error::PrivateKeyInvalid(
+                "stored key does not match its did:key".into(),
+            ));
+        }
+        Ok(key)
+    }
+
+    /// Saves the key to the keystore, refusing to replace an existing file.
+    pub(crate) async fn save(&self) -> Result<(), Error> {
+        let path = key_file(&self.did)
+            .ok_or_else(|| Error::KeystoreWriteFailed(std::io::ErrorKind::NotFound.into()))?;
+        if path.exists() {
+            return Err(Error::KeystoreWriteFailed(
+                std::io::ErrorKind::AlreadyExists.into(),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(Error::KeystoreWriteFailed)?;
+            local::restrict_dir(parent)
+                .await
+                .map_err(Error::KeystoreWriteFailed)?;
+        }
+
+        local::write_atomic_private(
+            &path,
+            serde_json::to_vec_pretty(self).expect("can serialize"),
+        )
+        .await
+        .map_err(Error::KeystoreWriteFailed)
+    }
+}
+
+pub(crate) fn key_file(did: &str) -> Option<PathBuf> {
+    // `did:key`s are multibase, so the suffix is safe to use as a filename.
+    let suffix = did.strip_prefix("did:key:")?;
+    if !suffix.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    local::config_file(format!("{KEYS_DIR}/{suffix}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyAlgorithm, KeyFormat, SecretKey, StoredKey};
+    use crate::error::Error;
+
+    #[test]
+    fn stored_keys_decrypt() {
+        let key = SecretKey::generate(KeyAlgorithm::K256);
+        let stored = StoredKey::new(&key, None, "hunter2".to_string().into());
+        assert_eq!(stored.did, key.did());
+
+        assert!(matches!(
+            stored.decrypt("wrong".to_string().into()),
+            Err(Error::KeystoreDecryptionFailed(_)),
+        ));
+        let decrypted = stored.decrypt("hunter2".to_string().into()).unwrap();
+        assert_eq!(decrypted.did(), key.did());
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        for algorithm in [KeyAlgorithm::P256, KeyAlgorithm::K256] {
+            let key = SecretKey::generate(algorithm);
+            for format in [KeyFormat::Hex, KeyFormat::Multibase, KeyFormat::Pem] {
+                let encoded = key.encode(format);
+                let hint = (format == KeyFormat::Hex).then_some(algorithm);
+                let parsed = SecretKey::parse(&encoded, hint).unwrap();
+                assert_eq!(parsed.algorithm, algorithm);
+                assert_eq!(parsed.did(), key.did());
+            }
+        }
+
+        let hex = SecretKey::generate(KeyAlgorithm::P256).encode(KeyFormat::Hex);
+        assert!(matches!(
+            SecretKey::parse(&hex, None),
+            Err(Error::KeyAlgorithmRequired),
+        ));
+    }
+}
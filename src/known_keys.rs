@@ -0,0 +1,107 @@
+//! Labels for well-known keys, such as the rotation keys held by large PDS providers.
+
+use std::collections::BTreeMap;
+
+use tokio::fs;
+
+use crate::{data::Key, error::Error, local};
+
+/// The dataset of known keys that ships with `plc`.
+const BUILTIN: &str = include_str!("known_keys.json");
+
+/// The user's own known keys, which extend (and override) the built-in dataset.
+pub(crate) const USER_FILE: &str = "known-keys.json";
+
+/// A mapping from `did:key` strings to labels.
+type Dataset = BTreeMap<String, String>;
+
+pub(crate) struct KnownKeys {
+    keys: Vec<(Key, String)>,
+}
+
+impl KnownKeys {
+    /// Loads the built-in dataset, extended with the user's own known keys.
+    pub(crate) async fn load() -> Result<Self, Error> {
+        let mut dataset: Dataset = serde_json::from_str(BUILTIN).expect("valid");
+        dataset.extend(load_user().await?);
+
+        Ok(Self {
+            keys: dataset
+                .into_iter()
+                .filter_map(|(key, label)| Key::did(key).ok().map(|key| (key, label)))
+                .collect(),
+        })
+    }
+
+    /// Returns the label for the given key, if it is known.
+    pub(crate) fn label(&self, key: &Key) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// Returns the label for the given `did:key`, if it is valid and known.
+    pub(crate) fn label_for_did(&self, did_key: &str) -> Option<&str> {
+        Key::did(did_key).ok().and_then(|key| self.label(&key))
+    }
+}
+
+/// Returns the built-in and user datasets, for display.
+pub(crate) async fn datasets() -> Result<(Dataset, Dataset), Error> {
+    Ok((
+        serde_json::from_str(BUILTIN).expect("valid"),
+        load_user().await?,
+    ))
+}
+
+/// Adds or replaces a label in the user's known keys.
+pub(crate) async fn add(did_key: String, label: String) -> Result<(), Error> {
+    let mut dataset = load_user().await?;
+    dataset.insert(did_key, label);
+    save_user(&dataset).await
+}
+
+/// Removes a key from the user's known keys, returning `false` if it wasn't present.
+pub(crate) async fn remove(did_key: &str) -> Result<bool, Error> {
+    let mut dataset = load_user().await?;
+    let removed = dataset.remove(did_key).is_some();
+    if removed {
+        save_user(&dataset).await?;
+    }
+    Ok(removed)
+}
+
+async fn load_user() -> Result<Dataset, Error> {
+    match local::config_file(USER_FILE) {
+        Some(path) => match fs::read_to_string(&path).await {
+            Ok(data) => serde_json::from_str(&data).map_err(Error::KnownKeysFileInvalid),
+            // The user hasn't added any known keys.
+            Err(_) => Ok(Dataset::new()),
+        },
+        None => Ok(Dataset::new()),
+    }
+}
+
+async fn save_user(dataset: &Dataset) -> Result<(), Error> {
+    let path = local::config_file(USER_FILE).ok_or(Error::KnownKeysSaveFailed)?;
+    let data = serde_json::to_string_pretty(dataset).map_err(|_| Error::KnownKeysSaveFailed)?;
+    local::write_atomic(&path, data)
+        .await
+        .map_err(|_| Error::KnownKeysSaveFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dataset, BUILTIN};
+    use crate::data::Key;
+
+    #[test]
+    fn builtin_dataset_is_valid() {
+        let dataset: Dataset = serde_json::from_str(BUILTIN).unwrap();
+        assert!(!dataset.is_empty());
+        for key in dataset.keys() {
+            assert!(Key::did(key).is_ok(), "{key}");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use atrium_api::{
@@ -6,12 +7,13 @@ use atrium_api::{
 };
 use atrium_xrpc_client::reqwest::ReqwestClient;
 use serde::{Deserialize, Serialize};
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt};
 
-use crate::error::Error;
+use crate::{error::Error, remote::plc::AuditLog};
 
 const APP_DIR: &str = "plc";
-const SESSION_FILE: &str = "session.json";
+pub(crate) const SESSION_FILE: &str = "session.json";
+const AUDIT_CACHE_FILE: &str = "audit-cache.json";
 
 pub(crate) fn config_file<P: AsRef<Path>>(filename: P) -> Option<PathBuf> {
     #[cfg(windows)]
@@ -31,6 +33,140 @@ pub(crate) fn config_file<P: AsRef<Path>>(filename: P) -> Option<PathBuf> {
     }
 }
 
+pub(crate) fn cache_file<P: AsRef<Path>>(filename: P) -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        use known_folders::{get_known_folder_path, KnownFolder};
+        let base = get_known_folder_path(KnownFolder::LocalAppData)?
+            .join(APP_DIR)
+            .join("cache");
+        std::fs::create_dir_all(&base).ok()?;
+        Some(base.join(filename))
+    }
+
+    #[cfg(any(unix, target_os = "redox"))]
+    {
+        xdg::BaseDirectories::with_prefix(APP_DIR)
+            .ok()?
+            .place_cache_file(filename)
+            .ok()
+    }
+}
+
+pub(crate) fn data_file<P: AsRef<Path>>(filename: P) -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        use known_folders::{get_known_folder_path, KnownFolder};
+        let base = get_known_folder_path(KnownFolder::LocalAppData)?
+            .join(APP_DIR)
+            .join("data");
+        std::fs::create_dir_all(&base).ok()?;
+        Some(base.join(filename))
+    }
+
+    #[cfg(any(unix, target_os = "redox"))]
+    {
+        xdg::BaseDirectories::with_prefix(APP_DIR)
+            .ok()?
+            .place_data_file(filename)
+            .ok()
+    }
+}
+
+/// Writes `data` to `path`, replacing any existing file.
+///
+/// The data is written to a temporary file that is then renamed over `path`, so an
+/// interrupted write never leaves a truncated file behind.
+pub(crate) async fn write_atomic(path: &Path, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, data).await?;
+    fs::rename(&tmp, path).await
+}
+
+/// Like [`write_atomic`], but the file can only be read by the user.
+pub(crate) async fn write_atomic_private(
+    path: &Path,
+    data: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    // A leftover temporary file would keep its permissions.
+    let _ = fs::remove_file(&tmp).await;
+    let mut file = create_private(&tmp).await?;
+    file.write_all(data.as_ref()).await?;
+    file.sync_all().await?;
+    fs::rename(&tmp, path).await
+}
+
+/// Creates a new file that can only be read by the user, failing if `path` exists.
+pub(crate) async fn create_private(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path).await
+}
+
+/// Makes the directory at `path` accessible only to the user.
+pub(crate) async fn restrict_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o700)).await?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// The audit logs that were found to be valid, by a hash of their contents.
+///
+/// A log that has changed in any way (including an operation being nullified, or the
+/// directory rewriting its history) has a different hash, and is validated again.
+/// Only valid results are cached, so that invalid logs are always revalidated with
+/// full error details.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct AuditCache {
+    valid: HashMap<String, String>,
+}
+
+impl AuditCache {
+    /// Loads the audit cache from disk.
+    ///
+    /// Returns an empty cache if there is no valid cache stored on disk.
+    pub(crate) async fn load() -> Self {
+        let Some(cache_file) = cache_file(AUDIT_CACHE_FILE) else {
+            return Self::default();
+        };
+        match fs::read_to_string(cache_file).await {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Returns `true` if `log` is known to be valid.
+    pub(crate) fn is_valid(&self, log: &AuditLog) -> bool {
+        self.valid
+            .get(log.did().as_str())
+            .is_some_and(|cached| *cached == log.digest())
+    }
+
+    /// Records that `log` is valid, and saves the cache to disk.
+    ///
+    /// The cache is an optimisation, so failures to save it are ignored.
+    pub(crate) async fn record_valid(&mut self, log: &AuditLog) {
+        self.valid.insert(log.did().as_str().into(), log.digest());
+
+        if let (Some(cache_file), Ok(data)) =
+            (cache_file(AUDIT_CACHE_FILE), serde_json::to_string(self))
+        {
+            let _ = write_atomic(&cache_file, data).await;
+        }
+    }
+}
+
 /// A session with a PDS.
 #[derive(Serialize, Deserialize)]
 pub(crate) struct Session {
@@ -68,7 +204,7 @@ impl Session {
         let session_file = config_file(SESSION_FILE).ok_or(Error::SessionSaveFailed)?;
         let session_data =
             serde_json::to_string_pretty(self).map_err(|_| Error::SessionSaveFailed)?;
-        fs::write(session_file, session_data)
+        write_atomic(&session_file, session_data)
             .await
             .map_err(|_| Error::SessionSaveFailed)
     }
@@ -2,20 +2,130 @@ use clap::Parser;
 
 mod cli;
 mod commands;
+mod config;
+mod context;
 mod data;
+mod delegations;
 mod error;
+mod fleet;
+mod hooks;
+mod incident;
+mod journal;
+mod keystore;
+mod known_keys;
 mod local;
+mod output;
+mod pins;
+mod policy;
+mod profile;
 mod remote;
 mod util;
+mod warning;
 
 #[tokio::main]
 async fn main() -> Result<(), error::Error> {
     let opts = cli::Options::parse();
 
-    match opts.command {
-        cli::Command::Auth(cli::Auth::Login(command)) => command.run().await,
-        cli::Command::Keys(cli::Keys::List(command)) => command.run().await,
-        cli::Command::Ops(cli::Ops::List(command)) => command.run().await,
-        cli::Command::Ops(cli::Ops::Audit(command)) => command.run().await,
+    let config = match config::Config::load().await {
+        Ok(config) => config,
+        // Let `plc config` fix a config file that doesn't load.
+        Err(error::Error::ConfigInvalid(_))
+            if matches!(
+                opts.command,
+                cli::Command::Config(cli::ConfigCmd::Set(_) | cli::ConfigCmd::Unset(_)),
+            ) =>
+        {
+            config::Config::default()
+        }
+        Err(e) => return Err(e),
+    };
+
+    output::init(
+        opts.no_color,
+        opts.time.or(config.defaults.time).unwrap_or_default(),
+    );
+
+    let ctx = context::AppContext::new(&opts, config)?;
+
+    let res = match opts.command {
+        cli::Command::Auth(cli::Auth::Login(command)) => command.run(&ctx).await,
+        cli::Command::Compare(command) => command.run(&ctx).await,
+        cli::Command::Config(cli::ConfigCmd::Show(command)) => command.run(&ctx).await,
+        cli::Command::Config(cli::ConfigCmd::Set(command)) => command.run(&ctx).await,
+        cli::Command::Config(cli::ConfigCmd::Unset(command)) => command.run(&ctx).await,
+        cli::Command::Did(cli::DidCmd::Preview(command)) => command.run(&ctx).await,
+        cli::Command::Did(cli::DidCmd::Grind(command)) => command.run(&ctx).await,
+        cli::Command::Directory(cli::Directory::Export(command)) => command.run(&ctx).await,
+        cli::Command::Directory(cli::Directory::ExportSubset(command)) => command.run(&ctx).await,
+        cli::Command::Directory(cli::Directory::Audit(command)) => command.run(&ctx).await,
+        cli::Command::Directory(cli::Directory::CheckHistory(command)) => command.run(&ctx).await,
+        cli::Command::Directory(cli::Directory::Status(command)) => command.run(&ctx).await,
+        cli::Command::Explain(command) => command.run(&ctx).await,
+        cli::Command::Fleet(cli::Fleet::Check(command)) => command.run(&ctx).await,
+        cli::Command::Fleet(cli::Fleet::Enforce(command)) => command.run(&ctx).await,
+        #[cfg(feature = "server")]
+        cli::Command::Handle(cli::Handle::ServeWellKnown(command)) => command.run(&ctx).await,
+        cli::Command::Handle(cli::Handle::Prove(command)) => command.run(&ctx).await,
+        cli::Command::Handle(cli::Handle::Set(command)) => command.run(&ctx).await,
+        cli::Command::Hooks(cli::Hooks::Test(command)) => command.run(&ctx).await,
+        cli::Command::Incident(cli::Incident::Start(command)) => command.run(&ctx).await,
+        cli::Command::Journal(cli::Journal::List(command)) => command.run(&ctx).await,
+        cli::Command::Journal(cli::Journal::Show(command)) => command.run(&ctx).await,
+        cli::Command::Journal(cli::Journal::Export(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::List(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::AddRotation(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::Export(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::Generate(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::Import(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::RemoveRotation(command)) => command.run(&ctx).await,
+        cli::Command::Keys(cli::Keys::Known(cli::KnownKeys::List(command))) => {
+            command.run(&ctx).await
+        }
+        cli::Command::Keys(cli::Keys::Known(cli::KnownKeys::Add(command))) => {
+            command.run(&ctx).await
+        }
+        cli::Command::Keys(cli::Keys::Known(cli::KnownKeys::Remove(command))) => {
+            command.run(&ctx).await
+        }
+        cli::Command::Ops(cli::Ops::List(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Audit(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Report(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Graph(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Format(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::CompareSources(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Submit(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Recover(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::Tombstone(command)) => command.run(&ctx).await,
+        cli::Command::Ops(cli::Ops::VerifyEntry(command)) => command.run(&ctx).await,
+        cli::Command::Pds(cli::Pds::Migrate(command)) => command.run(&ctx).await,
+        cli::Command::Pin(command) => command.run(&ctx).await,
+        cli::Command::Policy(cli::Policy::Check(command)) => command.run(&ctx).await,
+        cli::Command::Profile(cli::Profile::Export(command)) => command.run(&ctx).await,
+        cli::Command::Profile(cli::Profile::Import(command)) => command.run(&ctx).await,
+        cli::Command::SelfTest(command) => command.run(&ctx).await,
+        #[cfg(feature = "server")]
+        cli::Command::ServeResolver(command) => command.run(&ctx).await,
+        cli::Command::VerifyDid(command) => command.run(&ctx).await,
+    };
+
+    let res = res.and(ctx.report_warnings());
+
+    // Automation needs to be able to distinguish failures without parsing prose.
+    match res {
+        Err(e) if opts.non_interactive => {
+            eprintln!(
+                "{}",
+                serde_json::json!({
+                    "error": e.code(),
+                    "message": format!("{e:?}"),
+                })
+            );
+            std::process::exit(e.exit_code())
+        }
+        Err(e) if e.exit_code() != 1 => {
+            eprintln!("Error: {e:?}");
+            std::process::exit(e.exit_code())
+        }
+        res => res,
     }
 }
@@ -0,0 +1,135 @@
+//! Terminal output styling.
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+use anstyle::{AnsiColor, Style};
+use atrium_api::types::string::Datetime;
+use chrono::{Local, SecondsFormat, Utc};
+
+use crate::cli::TimeFormat;
+
+pub(crate) mod html;
+pub(crate) mod svg;
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+static TIME_FORMAT: OnceLock<TimeFormat> = OnceLock::new();
+
+/// Configures terminal output.
+///
+/// Styled output is enabled if stdout is a terminal, unless `no_color` is set or the
+/// `NO_COLOR` environment variable is set.
+pub(crate) fn init(no_color: bool, time: TimeFormat) {
+    let enabled = !no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+    let _ = TIME_FORMAT.set(time);
+}
+
+/// A value that is rendered with a style when styled output is enabled.
+pub(crate) struct Styled<D>(Style, D);
+
+impl<D: fmt::Display> fmt::Display for Styled<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if COLOR_ENABLED.load(Ordering::Relaxed) {
+            write!(f, "{}{}{:#}", self.0, self.1, self.0)
+        } else {
+            self.1.fmt(f)
+        }
+    }
+}
+
+/// Styles a section heading.
+pub(crate) fn heading<D: fmt::Display>(value: D) -> Styled<D> {
+    Styled(Style::new().bold(), value)
+}
+
+/// Styles something that was checked and is valid.
+pub(crate) fn valid<D: fmt::Display>(value: D) -> Styled<D> {
+    Styled(AnsiColor::Green.on_default(), value)
+}
+
+/// Styles something that violates the specification or is otherwise broken.
+pub(crate) fn violation<D: fmt::Display>(value: D) -> Styled<D> {
+    Styled(AnsiColor::Red.on_default().bold(), value)
+}
+
+/// Styles something that is valid but deserves the user's attention.
+pub(crate) fn advisory<D: fmt::Display>(value: D) -> Styled<D> {
+    Styled(AnsiColor::Yellow.on_default(), value)
+}
+
+/// An item in a bulleted list, with optional nested items.
+pub(crate) struct Item {
+    pub(crate) text: String,
+    pub(crate) children: Vec<String>,
+}
+
+impl Item {
+    pub(crate) fn new(text: impl Into<String>, children: Vec<String>) -> Self {
+        Self {
+            text: text.into(),
+            children,
+        }
+    }
+
+    pub(crate) fn leaf(text: impl Into<String>) -> Self {
+        Self::new(text, vec![])
+    }
+}
+
+/// Prints the given items as a bulleted list.
+pub(crate) fn print_items(items: &[Item]) {
+    for item in items {
+        println!("- {}", item.text);
+        for child in &item.children {
+            println!("  - {child}");
+        }
+    }
+}
+
+/// Returns the width of the widest of the given labels, for aligning columns.
+pub(crate) fn label_width<'a>(labels: impl Iterator<Item = &'a str>) -> usize {
+    labels.map(|s| s.chars().count()).max().unwrap_or(0)
+}
+
+/// Returns the width of the index labels (`[0]`, `[1]`, ...) for a list of `len` items.
+pub(crate) fn index_width(len: usize) -> usize {
+    len.saturating_sub(1).to_string().len() + 2
+}
+
+/// Formats a timestamp in the configured presentation.
+pub(crate) fn format_time(time: &Datetime) -> String {
+    match TIME_FORMAT.get().copied().unwrap_or_default() {
+        TimeFormat::Utc => time
+            .as_ref()
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Millis, true),
+        TimeFormat::Local => time
+            .as_ref()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S %:z")
+            .to_string(),
+        TimeFormat::Relative => {
+            let delta = Utc::now().signed_duration_since(time.as_ref());
+            let (amount, unit) = match delta.abs() {
+                d if d.num_minutes() < 1 => (d.num_seconds(), "second"),
+                d if d.num_hours() < 1 => (d.num_minutes(), "minute"),
+                d if d.num_days() < 1 => (d.num_hours(), "hour"),
+                d if d.num_days() < 60 => (d.num_days(), "day"),
+                d if d.num_days() < 730 => (d.num_days() / 30, "month"),
+                d => (d.num_days() / 365, "year"),
+            };
+            let plural = if amount == 1 { "" } else { "s" };
+            if delta < chrono::TimeDelta::zero() {
+                format!("in {amount} {unit}{plural}")
+            } else {
+                format!("{amount} {unit}{plural} ago")
+            }
+        }
+    }
+}
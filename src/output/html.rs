@@ -0,0 +1,130 @@
+//! Self-contained HTML rendering of structured output.
+
+use std::fmt::{self, Write};
+
+use super::Item;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 1.5em; word-break: break-all; }
+h2 { border-bottom: 1px solid #ccc; padding-bottom: 0.2em; }
+code, td { font-family: ui-monospace, monospace; font-size: 0.9em; word-break: break-all; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #eee; vertical-align: top; }
+.time { color: #666; font-weight: normal; font-size: 0.9em; }
+.valid { color: #1a7f37; }
+.violation { color: #cf222e; font-weight: bold; }
+.advisory { color: #9a6700; }
+";
+
+/// Escapes text for inclusion in HTML element content or attribute values.
+pub(crate) struct Escaped<'a>(pub(crate) &'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A self-contained HTML page, built up section by section.
+pub(crate) struct Page {
+    title: String,
+    body: String,
+}
+
+impl Page {
+    pub(crate) fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            body: String::new(),
+        }
+    }
+
+    /// Adds a section heading, with an optional timestamp.
+    pub(crate) fn heading(&mut self, text: &str, time: Option<&str>) {
+        let _ = write!(self.body, "<h2>{}", Escaped(text));
+        if let Some(time) = time {
+            let _ = write!(self.body, " <span class=\"time\">{}</span>", Escaped(time));
+        }
+        self.body.push_str("</h2>\n");
+    }
+
+    /// Adds a paragraph with the given class (`valid`, `violation`, or `advisory`), if
+    /// any.
+    pub(crate) fn paragraph(&mut self, text: &str, class: Option<&str>) {
+        match class {
+            Some(class) => {
+                let _ = writeln!(self.body, "<p class=\"{class}\">{}</p>", Escaped(text));
+            }
+            None => {
+                let _ = writeln!(self.body, "<p>{}</p>", Escaped(text));
+            }
+        }
+    }
+
+    /// Adds a bulleted list of items.
+    pub(crate) fn items(&mut self, items: &[Item]) {
+        self.body.push_str("<ul>\n");
+        for item in items {
+            let _ = write!(self.body, "<li>{}", Escaped(&item.text));
+            if !item.children.is_empty() {
+                self.body.push_str("<ul>");
+                for child in &item.children {
+                    let _ = write!(self.body, "<li><code>{}</code></li>", Escaped(child));
+                }
+                self.body.push_str("</ul>");
+            }
+            self.body.push_str("</li>\n");
+        }
+        self.body.push_str("</ul>\n");
+    }
+
+    /// Adds a table with the given column headers.
+    pub(crate) fn table<R: AsRef<[String]>>(&mut self, headers: &[&str], rows: &[R]) {
+        self.body.push_str("<table>\n<tr>");
+        for header in headers {
+            let _ = write!(self.body, "<th>{}</th>", Escaped(header));
+        }
+        self.body.push_str("</tr>\n");
+        for row in rows {
+            self.body.push_str("<tr>");
+            for cell in row.as_ref() {
+                let _ = write!(self.body, "<td>{}</td>", Escaped(cell));
+            }
+            self.body.push_str("</tr>\n");
+        }
+        self.body.push_str("</table>\n");
+    }
+
+    /// Renders the complete page.
+    pub(crate) fn render(&self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{}</body>\n</html>\n",
+            self.body,
+            title = Escaped(&self.title),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Escaped;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            Escaped("<a href=\"x\">Tom & Jerry's</a>").to_string(),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;",
+        );
+    }
+}
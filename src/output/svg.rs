@@ -0,0 +1,157 @@
+//! Self-contained SVG rendering of operation graphs.
+
+use std::fmt::Write;
+
+use super::html::Escaped;
+
+const NODE_WIDTH: usize = 340;
+const NODE_HEIGHT: usize = 64;
+const GAP_X: usize = 40;
+const GAP_Y: usize = 24;
+const MARGIN: usize = 20;
+const TITLE_HEIGHT: usize = 40;
+const LINE_HEIGHT: usize = 16;
+
+const STYLE: &str = "
+text { font-family: ui-monospace, monospace; font-size: 12px; fill: #222; }
+.title { font-family: system-ui, sans-serif; font-size: 16px; font-weight: bold; }
+rect { fill: #fff; stroke-width: 2; }
+path { fill: none; stroke: #888; stroke-width: 1.5; }
+.active rect { stroke: #1a7f37; }
+.nullified rect, path.nullified { stroke: #cf222e; stroke-dasharray: 6 4; }
+.nullified text { fill: #666; }
+.tombstone rect { stroke: #222; fill: #eee; }
+";
+
+/// How a node in the graph is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NodeStyle {
+    Active,
+    Nullified,
+    Tombstone,
+}
+
+impl NodeStyle {
+    fn class(self) -> &'static str {
+        match self {
+            NodeStyle::Active => "active",
+            NodeStyle::Nullified => "nullified",
+            NodeStyle::Tombstone => "tombstone",
+        }
+    }
+}
+
+/// A box in the graph, with an edge from its parent.
+pub(crate) struct Node {
+    pub(crate) lines: Vec<String>,
+    /// The index of the node this one follows, which must come before it.
+    pub(crate) parent: Option<usize>,
+    pub(crate) column: usize,
+    pub(crate) style: NodeStyle,
+}
+
+/// A graph drawn top to bottom, with one node per row in the order they were added.
+pub(crate) struct Graph {
+    title: String,
+    nodes: Vec<Node>,
+}
+
+impl Graph {
+    pub(crate) fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            nodes: vec![],
+        }
+    }
+
+    pub(crate) fn add(&mut self, node: Node) {
+        self.nodes.push(node);
+    }
+
+    fn position(&self, row: usize) -> (usize, usize) {
+        let x = MARGIN + self.nodes[row].column * (NODE_WIDTH + GAP_X);
+        let y = MARGIN + TITLE_HEIGHT + row * (NODE_HEIGHT + GAP_Y);
+        (x, y)
+    }
+
+    /// Renders the complete image.
+    pub(crate) fn render(&self) -> String {
+        let columns = self.nodes.iter().map(|n| n.column + 1).max().unwrap_or(1);
+        let width = 2 * MARGIN + columns * NODE_WIDTH + (columns - 1) * GAP_X;
+        let height = 2 * MARGIN + TITLE_HEIGHT + self.nodes.len() * (NODE_HEIGHT + GAP_Y);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n<style>{STYLE}</style>\n<rect x=\"0\" y=\"0\" width=\"{width}\" height=\"{height}\" style=\"stroke: none\"/>\n<text class=\"title\" x=\"{MARGIN}\" y=\"{}\">{}</text>\n",
+            MARGIN + LINE_HEIGHT,
+            Escaped(&self.title),
+        );
+
+        // Draw edges first, so that nodes are drawn over them.
+        for (row, node) in self.nodes.iter().enumerate() {
+            let Some(parent) = node.parent else { continue };
+            let (px, py) = self.position(parent);
+            let (x, y) = self.position(row);
+            let cx = x + NODE_WIDTH / 2;
+            let d = if self.nodes[parent].column == node.column {
+                format!("M {cx} {} V {y}", py + NODE_HEIGHT)
+            } else {
+                // Leave the parent sideways, so the edge doesn't cross the nodes below it.
+                format!(
+                    "M {} {} H {cx} V {y}",
+                    px + NODE_WIDTH,
+                    py + NODE_HEIGHT / 2
+                )
+            };
+            let _ = writeln!(svg, "<path class=\"{}\" d=\"{d}\"/>", node.style.class());
+        }
+
+        for (row, node) in self.nodes.iter().enumerate() {
+            let (x, y) = self.position(row);
+            let _ = writeln!(
+                svg,
+                "<g class=\"{}\">\n<rect x=\"{x}\" y=\"{y}\" width=\"{NODE_WIDTH}\" height=\"{NODE_HEIGHT}\" rx=\"6\"/>",
+                node.style.class(),
+            );
+            for (i, line) in node.lines.iter().enumerate() {
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\">{}</text>",
+                    x + 10,
+                    y + (i + 1) * LINE_HEIGHT + 4,
+                    Escaped(line),
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, Node, NodeStyle};
+
+    #[test]
+    fn rendering() {
+        let mut graph = Graph::new("Operations for <did>");
+        let node = |parent, column, style| Node {
+            lines: vec!["op".into()],
+            parent,
+            column,
+            style,
+        };
+        graph.add(node(None, 0, NodeStyle::Active));
+        graph.add(node(Some(0), 1, NodeStyle::Nullified));
+        graph.add(node(Some(0), 0, NodeStyle::Tombstone));
+        let svg = graph.render();
+
+        assert!(svg.contains("Operations for &lt;did&gt;"));
+        assert!(svg.contains(r#"width="760""#));
+        // The fork leaves its parent sideways; the active chain continues straight down.
+        assert!(svg.contains(r#"<path class="nullified" d="M 360 92 H 570 V 148"/>"#));
+        assert!(svg.contains(r#"<path class="tombstone" d="M 190 124 V 236"/>"#));
+        assert_eq!(svg.matches("<rect").count(), 4);
+    }
+}
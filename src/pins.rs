@@ -0,0 +1,149 @@
+//! Trust-on-first-use pins of DIDs' rotation keys.
+//!
+//! The rotation keys control a DID, so an unexpected change to them is the clearest
+//! sign that an identity has been taken over.
+
+use std::collections::BTreeMap;
+
+use atrium_api::types::string::Did;
+use tokio::fs;
+
+use crate::{data::State, error::Error, local};
+
+pub(crate) const PINS_FILE: &str = "pins.json";
+
+/// A mapping from DIDs to their pinned rotation keys, in priority order.
+type Pins = BTreeMap<String, Vec<String>>;
+
+/// How a DID's rotation keys differ from their pinned set.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PinChange {
+    pub(crate) added: Vec<String>,
+    pub(crate) removed: Vec<String>,
+    /// Whether the keys present in both sets are in a different priority order.
+    pub(crate) reordered: bool,
+}
+
+impl PinChange {
+    /// Compares the current rotation keys against the pinned set, returning `None` if
+    /// they are identical.
+    fn between(pinned: &[String], current: &[String]) -> Option<Self> {
+        if pinned == current {
+            return None;
+        }
+
+        let kept = |a: &[String], b: &[String]| {
+            a.iter()
+                .filter(|key| b.contains(key))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        Some(Self {
+            added: current
+                .iter()
+                .filter(|key| !pinned.contains(key))
+                .cloned()
+                .collect(),
+            removed: pinned
+                .iter()
+                .filter(|key| !current.contains(key))
+                .cloned()
+                .collect(),
+            reordered: kept(pinned, current) != kept(current, pinned),
+        })
+    }
+}
+
+/// Checks the rotation keys of `state` against its pin, if it has one.
+pub(crate) async fn check(state: &State) -> Result<Option<PinChange>, Error> {
+    Ok(load()
+        .await?
+        .get(state.did().as_str())
+        .and_then(|pinned| PinChange::between(pinned, &state.inner_data().rotation_keys)))
+}
+
+/// Pins the current rotation keys of `state`, replacing any existing pin.
+pub(crate) async fn pin(state: &State) -> Result<(), Error> {
+    let mut pins = load().await?;
+    pins.insert(
+        state.did().as_str().into(),
+        state.inner_data().rotation_keys.clone(),
+    );
+    save(&pins).await
+}
+
+/// Replaces the pin for `did` with `rotation_keys`, if it is pinned.
+///
+/// We call this after submitting an operation, so that our own changes don't look
+/// like a takeover the next time the DID is checked.
+pub(crate) async fn update_if_pinned(did: &Did, rotation_keys: &[String]) -> Result<(), Error> {
+    let mut pins = load().await?;
+    match pins.get_mut(did.as_str()) {
+        Some(pinned) => {
+            *pinned = rotation_keys.to_vec();
+            save(&pins).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Removes the pin for `state`, returning `false` if it wasn't pinned.
+pub(crate) async fn unpin(state: &State) -> Result<bool, Error> {
+    let mut pins = load().await?;
+    let removed = pins.remove(state.did().as_str()).is_some();
+    if removed {
+        save(&pins).await?;
+    }
+    Ok(removed)
+}
+
+async fn load() -> Result<Pins, Error> {
+    match local::config_file(PINS_FILE) {
+        Some(path) => match fs::read_to_string(&path).await {
+            Ok(data) => serde_json::from_str(&data).map_err(Error::PinsFileInvalid),
+            // The user hasn't pinned any DIDs.
+            Err(_) => Ok(Pins::new()),
+        },
+        None => Ok(Pins::new()),
+    }
+}
+
+async fn save(pins: &Pins) -> Result<(), Error> {
+    let path = local::config_file(PINS_FILE).ok_or(Error::PinsSaveFailed)?;
+    let data = serde_json::to_string_pretty(pins).map_err(|_| Error::PinsSaveFailed)?;
+    local::write_atomic(&path, data)
+        .await
+        .map_err(|_| Error::PinsSaveFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PinChange;
+
+    #[test]
+    fn pin_change() {
+        let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            PinChange::between(&keys(&["a", "b"]), &keys(&["a", "b"])),
+            None
+        );
+        assert_eq!(
+            PinChange::between(&keys(&["a", "b"]), &keys(&["c", "a"])),
+            Some(PinChange {
+                added: keys(&["c"]),
+                removed: keys(&["b"]),
+                reordered: false,
+            }),
+        );
+        assert_eq!(
+            PinChange::between(&keys(&["a", "b"]), &keys(&["b", "a"])),
+            Some(PinChange {
+                added: vec![],
+                removed: vec![],
+                reordered: true,
+            }),
+        );
+    }
+}
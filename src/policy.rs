@@ -0,0 +1,168 @@
+//! Guardrails that must be satisfied before an operation is submitted.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use atrium_api::types::string::Did;
+use serde::Deserialize;
+
+use crate::{error::Error, remote::plc::SignedOperation};
+
+/// The kinds of operation that a policy can govern.
+///
+/// An operation can be of several kinds; e.g. an update that changes rotation keys is
+/// both [`OperationKind::Update`] and [`OperationKind::RotationKeys`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OperationKind {
+    Genesis,
+    Update,
+    RotationKeys,
+    Tombstone,
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationKind::Genesis => write!(f, "genesis"),
+            OperationKind::Update => write!(f, "update"),
+            OperationKind::RotationKeys => write!(f, "rotation key change"),
+            OperationKind::Tombstone => write!(f, "tombstone"),
+        }
+    }
+}
+
+/// What must happen before an operation may be submitted, from least to most strict.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Requirement {
+    /// No confirmation is needed.
+    #[default]
+    Allow,
+    /// The user must type the DID being changed.
+    Phrase,
+    /// The user must pass `--confirm-cid` with the CID of the operation.
+    ConfirmCid,
+    /// The operation may not be submitted at all.
+    Disable,
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Requirement::Allow => write!(f, "allowed"),
+            Requirement::Phrase => write!(f, "requires typing the DID to confirm"),
+            Requirement::ConfirmCid => write!(f, "requires --confirm-cid with the operation's CID"),
+            Requirement::Disable => write!(f, "disabled"),
+        }
+    }
+}
+
+/// The `[policy]` table of the config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Policy {
+    /// Applies to every operation not covered by a more specific rule.
+    #[serde(default)]
+    default: Requirement,
+    genesis: Option<Requirement>,
+    rotation_keys: Option<Requirement>,
+    tombstone: Option<Requirement>,
+}
+
+impl Policy {
+    fn requirement_for(&self, kind: OperationKind) -> Requirement {
+        match kind {
+            OperationKind::Genesis => self.genesis,
+            OperationKind::Update => None,
+            OperationKind::RotationKeys => self.rotation_keys,
+            OperationKind::Tombstone => self.tombstone,
+        }
+        .unwrap_or(self.default)
+    }
+
+    /// Returns the strictest requirement that applies to an operation of the given
+    /// kinds.
+    pub(crate) fn requirement(&self, kinds: &[OperationKind]) -> Requirement {
+        kinds
+            .iter()
+            .map(|kind| self.requirement_for(*kind))
+            .max()
+            .unwrap_or(self.default)
+    }
+
+    /// Checks that `op` may be submitted for `did`, prompting the user to confirm it if
+    /// the policy requires.
+    pub(crate) fn enforce(
+        &self,
+        did: &Did,
+        op: &SignedOperation,
+        kinds: &[OperationKind],
+        confirm_cid: Option<&str>,
+        non_interactive: bool,
+    ) -> Result<(), Error> {
+        match self.requirement(kinds) {
+            Requirement::Allow => Ok(()),
+            Requirement::Phrase if non_interactive => Err(Error::PolicyConfirmationRequired),
+            Requirement::Phrase => {
+                print!("Type {} to confirm: ", did.as_str());
+                io::stdout().flush().map_err(Error::OutputWriteFailed)?;
+                let mut line = String::new();
+                io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .map_err(Error::InputReadFailed)?;
+                if line.trim() == did.as_str() {
+                    Ok(())
+                } else {
+                    Err(Error::PolicyConfirmationFailed)
+                }
+            }
+            Requirement::ConfirmCid => {
+                let cid = op.cid().as_ref().to_string();
+                if confirm_cid == Some(cid.as_str()) {
+                    Ok(())
+                } else {
+                    Err(Error::PolicyCidMismatch(cid))
+                }
+            }
+            Requirement::Disable => Err(Error::PolicyForbidsOperation(
+                *kinds
+                    .iter()
+                    .find(|kind| self.requirement_for(**kind) == Requirement::Disable)
+                    .expect("some kind is disabled"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OperationKind, Policy, Requirement};
+
+    #[test]
+    fn strictest_requirement_wins() {
+        let policy = Policy {
+            default: Requirement::Phrase,
+            genesis: Some(Requirement::Allow),
+            rotation_keys: Some(Requirement::ConfirmCid),
+            tombstone: None,
+        };
+
+        assert_eq!(
+            policy.requirement(&[OperationKind::Genesis]),
+            Requirement::Allow
+        );
+        assert_eq!(
+            policy.requirement(&[OperationKind::Update]),
+            Requirement::Phrase
+        );
+        assert_eq!(
+            policy.requirement(&[OperationKind::Update, OperationKind::RotationKeys]),
+            Requirement::ConfirmCid,
+        );
+        assert_eq!(
+            policy.requirement(&[OperationKind::Tombstone]),
+            Requirement::Phrase
+        );
+    }
+}
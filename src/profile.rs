@@ -0,0 +1,274 @@
+//! Portable bundles of the local state `plc` keeps, for moving to another machine or
+//! sharing a baseline with a team.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use age::secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{
+    config, delegations, error::Error, journal, keystore::KEYS_DIR, known_keys, local, pins,
+};
+
+const VERSION: u32 = 1;
+
+/// The files in the config directory that a profile carries.
+const FILES: &[&str] = &[
+    config::CONFIG_FILE,
+    pins::PINS_FILE,
+    known_keys::USER_FILE,
+    delegations::DELEGATIONS_FILE,
+    journal::JOURNAL_FILE,
+];
+
+/// Files that grant access to an account, and are only exported on request (along
+/// with the keystore).
+const SECRET_FILES: &[&str] = &[local::SESSION_FILE];
+
+/// Every age file starts with this.
+const AGE_MAGIC: &[u8] = b"age-encryption.org/";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Profile {
+    version: u32,
+    /// The contents of files in the config directory, by filename.
+    pub(crate) files: BTreeMap<String, String>,
+    /// The fleet definition, which lives outside the config directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) fleet: Option<String>,
+}
+
+impl Profile {
+    /// Collects the local state into a profile, including secrets if requested.
+    pub(crate) async fn collect(fleet: &Path, include_secrets: bool) -> Result<Self, Error> {
+        let secrets = if include_secrets { SECRET_FILES } else { &[] };
+
+        let mut files = BTreeMap::new();
+        for name in FILES.iter().chain(secrets) {
+            if let Some(data) = read_optional(local::config_file(name).as_deref()).await? {
+                files.insert(name.to_string(), data);
+            }
+        }
+
+        if include_secrets {
+            if let Some(dir) = local::config_file(KEYS_DIR) {
+                match fs::read_dir(&dir).await {
+                    Ok(mut entries) => {
+                        while let Some(entry) = entries
+                            .next_entry()
+                            .await
+                            .map_err(Error::ProfileReadFailed)?
+                        {
+                            let name =
+                                format!("{KEYS_DIR}/{}", entry.file_name().to_string_lossy());
+                            if is_key_file(&name) {
+                                let data = fs::read_to_string(entry.path())
+                                    .await
+                                    .map_err(Error::ProfileReadFailed)?;
+                                files.insert(name, data);
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                    Err(e) => return Err(Error::ProfileReadFailed(e)),
+                }
+            }
+        }
+
+        Ok(Self {
+            version: VERSION,
+            files,
+            fleet: read_optional(Some(fleet)).await?,
+        })
+    }
+
+    /// Returns `true` if the profile contains secrets.
+    pub(crate) fn has_secrets(&self) -> bool {
+        self.files
+            .keys()
+            .any(|name| SECRET_FILES.contains(&name.as_str()) || is_key_file(name))
+    }
+
+    /// Returns the `[hooks]` table of the profile's config file, if it has any hooks.
+    ///
+    /// Hooks run arbitrary commands, so they must be reviewed before they are imported.
+    pub(crate) fn hooks(&self) -> Option<String> {
+        let config = self.files.get(config::CONFIG_FILE)?;
+        let config = toml::from_str::<toml::Table>(config).ok()?;
+        let hooks = config.get("hooks")?.as_table()?;
+        (!hooks.is_empty()).then(|| {
+            toml::to_string(&toml::Table::from_iter([(
+                "hooks".to_string(),
+                toml::Value::Table(hooks.clone()),
+            )]))
+            .expect("can serialize")
+        })
+    }
+
+    /// Encodes the profile, encrypting it to `passphrase` if given.
+    pub(crate) fn to_bytes(&self, passphrase: Option<SecretString>) -> Vec<u8> {
+        let data = serde_json::to_vec_pretty(self).expect("can serialize");
+        match passphrase {
+            Some(passphrase) => age::encrypt(&age::scrypt::Recipient::new(passphrase), &data)
+                .expect("in-memory encryption can't fail"),
+            None => data,
+        }
+    }
+
+    /// Returns `true` if `data` is an encrypted profile.
+    pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(AGE_MAGIC)
+    }
+
+    /// Decodes a profile, decrypting it with `passphrase` if it is encrypted.
+    pub(crate) fn from_bytes(data: &[u8], passphrase: Option<SecretString>) -> Result<Self, Error> {
+        let decrypted;
+        let data = match passphrase {
+            Some(passphrase) if Self::is_encrypted(data) => {
+                decrypted = age::decrypt(&age::scrypt::Identity::new(passphrase), data)
+                    .map_err(Error::ProfileDecryptionFailed)?;
+                &decrypted
+            }
+            _ => data,
+        };
+
+        let profile: Self = serde_json::from_slice(data).map_err(Error::ProfileInvalid)?;
+        if profile.version != VERSION {
+            return Err(Error::ProfileUnsupportedVersion(profile.version));
+        }
+        // Only restore the files we know about, so a profile can't write anywhere else.
+        if let Some(name) = profile.files.keys().find(|name| {
+            !FILES.contains(&name.as_str())
+                && !SECRET_FILES.contains(&name.as_str())
+                && !is_key_file(name)
+        }) {
+            return Err(Error::ProfileContainsUnknownFile(name.clone()));
+        }
+        Ok(profile)
+    }
+
+    /// Writes the profile's files into place, refusing to replace existing files
+    /// unless `overwrite` is set.
+    pub(crate) async fn apply(&self, fleet: &Path, overwrite: bool) -> Result<(), Error> {
+        let mut targets = vec![];
+        for (name, data) in &self.files {
+            let path = local::config_file(name)
+                .ok_or_else(|| Error::ProfileWriteFailed(std::io::ErrorKind::NotFound.into()))?;
+            let private = SECRET_FILES.contains(&name.as_str()) || is_key_file(name);
+            targets.push((path, data, private));
+        }
+        if let Some(data) = &self.fleet {
+            targets.push((fleet.to_path_buf(), data, false));
+        }
+
+        if !overwrite {
+            if let Some((path, ..)) = targets.iter().find(|(path, ..)| path.exists()) {
+                return Err(Error::ProfileWouldOverwrite(path.display().to_string()));
+            }
+        }
+
+        for (path, data, private) in targets {
+            if private {
+                if let Some(parent) = path.parent() {
+                    local::restrict_dir(parent)
+                        .await
+                        .map_err(Error::ProfileWriteFailed)?;
+                }
+                local::write_atomic_private(&path, data).await
+            } else {
+                local::write_atomic(&path, data).await
+            }
+            .map_err(Error::ProfileWriteFailed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if `name` is a key file in the keystore.
+fn is_key_file(name: &str) -> bool {
+    name.strip_prefix(KEYS_DIR)
+        .and_then(|name| name.strip_prefix('/'))
+        .and_then(|name| name.strip_suffix(".json"))
+        .is_some_and(|suffix| {
+            !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Reads a file if it exists.
+async fn read_optional(path: Option<&Path>) -> Result<Option<String>, Error> {
+    let Some(path) = path else { return Ok(None) };
+    match fs::read_to_string(path).await {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::ProfileReadFailed(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Profile;
+    use crate::error::Error;
+
+    #[test]
+    fn round_trip() {
+        let profile = Profile {
+            version: 1,
+            files: BTreeMap::from([("pins.json".into(), "{}".into())]),
+            fleet: Some("[[account]]\n".into()),
+        };
+
+        let data = profile.to_bytes(None);
+        assert!(!Profile::is_encrypted(&data));
+        assert_eq!(Profile::from_bytes(&data, None).unwrap(), profile);
+
+        let data = profile.to_bytes(Some("correct horse".to_string().into()));
+        assert!(Profile::is_encrypted(&data));
+        assert!(Profile::from_bytes(&data, None).is_err());
+        assert!(matches!(
+            Profile::from_bytes(&data, Some("wrong".to_string().into())),
+            Err(Error::ProfileDecryptionFailed(_)),
+        ));
+        assert_eq!(
+            Profile::from_bytes(&data, Some("correct horse".to_string().into())).unwrap(),
+            profile,
+        );
+    }
+
+    #[test]
+    fn unknown_files_are_rejected() {
+        let data = br#"{"version":1,"files":{"../../.bashrc":"oops"}}"#;
+        assert!(matches!(
+            Profile::from_bytes(data, None),
+            Err(Error::ProfileContainsUnknownFile(_)),
+        ));
+
+        let data = br#"{"version":1,"files":{"keys/zQ3shabc.json":"{}"}}"#;
+        assert!(Profile::from_bytes(data, None).unwrap().has_secrets());
+        let data = br#"{"version":1,"files":{"keys/../config.toml":"oops"}}"#;
+        assert!(matches!(
+            Profile::from_bytes(data, None),
+            Err(Error::ProfileContainsUnknownFile(_)),
+        ));
+    }
+
+    #[test]
+    fn hooks() {
+        let profile = |config: &str| Profile {
+            version: 1,
+            files: BTreeMap::from([("config.toml".into(), config.into())]),
+            fleet: None,
+        };
+
+        assert_eq!(profile("[defaults]\ntime = \"utc\"\n").hooks(), None);
+        assert_eq!(profile("[hooks]\n").hooks(), None);
+        let hooks = profile("[defaults]\ntime = \"utc\"\n\n[hooks]\npre-sign = \"curl evil\"\n")
+            .hooks()
+            .unwrap();
+        assert!(hooks.contains("curl evil"));
+        assert!(!hooks.contains("utc"));
+    }
+}
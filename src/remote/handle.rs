@@ -1,67 +1,283 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
 use atrium_api::types::string::Did;
-use hickory_resolver::TokioAsyncResolver;
-use reqwest::{header::CONTENT_TYPE, Client};
+use hickory_resolver::{
+    config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts},
+    error::ResolveError,
+    TokioAsyncResolver,
+};
+use reqwest::{
+    header::{CONTENT_TYPE, LOCATION},
+    redirect, Client, StatusCode, Url,
+};
 
 use crate::error::Error;
 
-/// Resolves the DID for the given handle, if any.
-pub(crate) async fn resolve(handle: &str, client: &Client) -> Result<Did, Error> {
-    if let Some(did) = resolve_dns_txt(handle).await {
-        Ok(did)
-    } else if let Some(did) = resolve_https_well_known(handle, client).await {
-        Ok(did)
-    } else {
+/// The maximum number of redirects we follow for the HTTPS well-known method.
+const MAX_WELL_KNOWN_REDIRECTS: usize = 3;
+
+/// The maximum size of a well-known response. DIDs are at most 2 KiB.
+const MAX_WELL_KNOWN_LEN: usize = 2048;
+
+/// A handle resolver.
+pub(crate) struct Resolver {
+    dns: TokioAsyncResolver,
+    client: Client,
+}
+
+impl Resolver {
+    /// Constructs a handle resolver.
+    ///
+    /// - If `servers` is non-empty, DNS queries are sent to those servers.
+    /// - Otherwise, if `system` is `true`, the system's resolver configuration is used.
+    /// - Otherwise, Google Public DNS is used.
+    ///
+    /// `timeout`, if set, overrides the per-query timeout.
+    pub(crate) fn new(
+        servers: &[SocketAddr],
+        system: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error> {
+        let (config, mut opts) = if !servers.is_empty() {
+            let mut config = ResolverConfig::new();
+            for addr in servers {
+                // Register each server over both UDP and TCP, so that truncated TXT
+                // responses received over UDP are retried over TCP.
+                config.add_name_server(NameServerConfig::new(*addr, Protocol::Udp));
+                config.add_name_server(NameServerConfig::new(*addr, Protocol::Tcp));
+            }
+            (config, ResolverOpts::default())
+        } else if system {
+            hickory_resolver::system_conf::read_system_conf()
+                .map_err(|e| Error::DnsConfigInvalid(e.to_string()))?
+        } else {
+            (ResolverConfig::default(), ResolverOpts::default())
+        };
+
+        if let Some(timeout) = timeout {
+            opts.timeout = timeout;
+        }
+
+        // We follow redirects manually, to restrict where they can go.
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(Error::HttpClientInitFailed)?;
+
+        Ok(Self {
+            dns: TokioAsyncResolver::tokio(config, opts),
+            client,
+        })
+    }
+
+    /// Resolves the DID for the given handle, if any.
+    pub(crate) async fn resolve(&self, handle: &str) -> Result<Did, Error> {
+        let dns = match self.resolve_dns_txt(handle).await {
+            Ok(did) => return Ok(did),
+            Err(e) => e,
+        };
+
+        let https = match self.resolve_https_well_known(handle).await {
+            Ok(did) => return Ok(did),
+            Err(e) => e,
+        };
+
         // Neither resolution method worked.
-        Err(Error::HandleResolutionFailed)
+        Err(Error::HandleResolutionFailed { dns, https })
+    }
+
+    /// DNS TXT resolution method.
+    ///
+    /// https://atproto.com/specs/handle#dns-txt-method
+    pub(crate) async fn resolve_dns_txt(&self, handle: &str) -> Result<Did, DnsTxtError> {
+        let resp = self
+            .dns
+            .txt_lookup(format!("_atproto.{}.", handle))
+            .await
+            .map_err(DnsTxtError::Lookup)?;
+
+        let mut records = resp
+            .into_iter()
+            .map(|r| r.to_string())
+            // Any TXT records with values not starting with `did=` should be ignored.
+            .filter_map(|r| {
+                r.strip_prefix("did=")
+                    .and_then(|did| did.parse::<Did>().ok())
+            });
+
+        // Only a single valid record should exist at any point in time. If multiple valid
+        // records with different DIDs are present, resolution should fail.
+        match (records.next(), records.next()) {
+            (Some(did), None) => Ok(did),
+            (None, _) => Err(DnsTxtError::NoRecords),
+            (Some(_), Some(_)) => Err(DnsTxtError::MultipleRecords),
+        }
+    }
+
+    /// HTTPS well-known resolution method.
+    ///
+    /// https://atproto.com/specs/handle#https-well-known-method
+    pub(crate) async fn resolve_https_well_known(
+        &self,
+        handle: &str,
+    ) -> Result<Did, WellKnownError> {
+        let mut url = Url::parse(&format!("https://{}/.well-known/atproto-did", handle))
+            .map_err(|_| WellKnownError::InvalidUrl)?;
+        let domain = registrable_domain(&url)
+            .ok_or(WellKnownError::InvalidUrl)?
+            .to_string();
+
+        let mut redirects = 0;
+        let mut resp = loop {
+            let resp = self
+                .client
+                .get(url.clone())
+                .send()
+                .await
+                .map_err(WellKnownError::RequestFailed)?;
+
+            if !resp.status().is_redirection() {
+                break resp;
+            }
+
+            // Only follow a bounded number of redirects, and only within the handle's
+            // registrable domain.
+            if redirects == MAX_WELL_KNOWN_REDIRECTS {
+                return Err(WellKnownError::TooManyRedirects);
+            }
+            redirects += 1;
+
+            let location = resp
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| url.join(v).ok())
+                .ok_or(WellKnownError::InvalidRedirect)?;
+            if location.scheme() != "https"
+                || registrable_domain(&location) != Some(domain.as_str())
+            {
+                return Err(WellKnownError::RedirectOutsideDomain(location));
+            }
+            url = location;
+        };
+
+        if !resp.status().is_success() {
+            return Err(WellKnownError::UnexpectedStatus(resp.status()));
+        }
+
+        match resp.headers().get(CONTENT_TYPE) {
+            Some(v) if v.as_bytes().starts_with(b"text/plain") => (),
+            v => {
+                return Err(WellKnownError::UnexpectedContentType(
+                    v.map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned()),
+                ))
+            }
+        }
+
+        if resp
+            .content_length()
+            .is_some_and(|len| len > MAX_WELL_KNOWN_LEN as u64)
+        {
+            return Err(WellKnownError::ResponseTooLarge);
+        }
+
+        // Don't trust the `Content-Length` header; enforce the limit as we read.
+        let mut body = vec![];
+        while let Some(chunk) = resp.chunk().await.map_err(WellKnownError::RequestFailed)? {
+            if body.len() + chunk.len() > MAX_WELL_KNOWN_LEN {
+                return Err(WellKnownError::ResponseTooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let body = String::from_utf8(body).map_err(|_| WellKnownError::InvalidDid)?;
+        body.parse().map_err(|_| WellKnownError::InvalidDid)
     }
 }
 
-/// DNS TXT resolution method.
-///
-/// https://atproto.com/specs/handle#dns-txt-method
-async fn resolve_dns_txt(handle: &str) -> Option<Did> {
-    let resolver = TokioAsyncResolver::tokio(Default::default(), Default::default());
-    let resp = resolver
-        .txt_lookup(format!("_atproto.{}.", handle))
-        .await
-        .ok()?;
-
-    let mut records = resp
-        .into_iter()
-        .map(|r| r.to_string())
-        // Any TXT records with values not starting with `did=` should be ignored.
-        .filter_map(|r| {
-            r.strip_prefix("did=")
-                .and_then(|did| did.parse::<Did>().ok())
-        });
-
-    // Only a single valid record should exist at any point in time. If multiple valid
-    // records with different DIDs are present, resolution should fail.
-    match (records.next(), records.next()) {
-        (Some(did), None) => Some(did),
-        _ => None,
+/// Returns the registrable domain (the public suffix plus one label) of the given URL.
+fn registrable_domain(url: &Url) -> Option<&str> {
+    url.host_str().and_then(psl::domain_str)
+}
+
+/// Reasons why the DNS TXT resolution method failed.
+pub(crate) enum DnsTxtError {
+    Lookup(ResolveError),
+    NoRecords,
+    MultipleRecords,
+}
+
+impl fmt::Display for DnsTxtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsTxtError::Lookup(e) => write!(f, "lookup failed: {e}"),
+            DnsTxtError::NoRecords => write!(f, "no valid `did=` TXT records"),
+            DnsTxtError::MultipleRecords => write!(f, "multiple valid `did=` TXT records"),
+        }
     }
 }
 
-/// HTTPS well-known resolution method.
-///
-/// https://atproto.com/specs/handle#https-well-known-method
-async fn resolve_https_well_known(handle: &str, client: &Client) -> Option<Did> {
-    match client
-        .get(format!("https://{}/.well-known/atproto-did", handle))
-        .send()
-        .await
-    {
-        Ok(resp)
-            if resp.status().is_success()
-                && resp
-                    .headers()
-                    .get(CONTENT_TYPE)
-                    .map(|v| v.as_bytes().starts_with(b"text/plain"))
-                    .unwrap_or(false) =>
-        {
-            resp.text().await.ok().and_then(|s| s.parse().ok())
+/// Reasons why the HTTPS well-known resolution method failed.
+pub(crate) enum WellKnownError {
+    InvalidUrl,
+    RequestFailed(reqwest::Error),
+    TooManyRedirects,
+    InvalidRedirect,
+    RedirectOutsideDomain(Url),
+    UnexpectedStatus(StatusCode),
+    UnexpectedContentType(Option<String>),
+    ResponseTooLarge,
+    InvalidDid,
+}
+
+impl fmt::Display for WellKnownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WellKnownError::InvalidUrl => write!(f, "handle is not a valid domain"),
+            WellKnownError::RequestFailed(e) => write!(f, "request failed: {e}"),
+            WellKnownError::TooManyRedirects => {
+                write!(f, "more than {MAX_WELL_KNOWN_REDIRECTS} redirects")
+            }
+            WellKnownError::InvalidRedirect => write!(f, "redirect has an invalid location"),
+            WellKnownError::RedirectOutsideDomain(url) => {
+                write!(f, "redirect to {url} leaves the handle's domain")
+            }
+            WellKnownError::UnexpectedStatus(status) => write!(f, "server returned {status}"),
+            WellKnownError::UnexpectedContentType(Some(ty)) => {
+                write!(f, "expected content type text/plain, got {ty}")
+            }
+            WellKnownError::UnexpectedContentType(None) => {
+                write!(f, "expected content type text/plain, got none")
+            }
+            WellKnownError::ResponseTooLarge => {
+                write!(f, "response is larger than {MAX_WELL_KNOWN_LEN} bytes")
+            }
+            WellKnownError::InvalidDid => write!(f, "response is not a valid DID"),
         }
-        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::registrable_domain;
+
+    #[test]
+    fn registrable_domains() {
+        let domain = |url| registrable_domain(&Url::parse(url).unwrap()).map(String::from);
+
+        assert_eq!(domain("https://example.com/"), Some("example.com".into()));
+        assert_eq!(
+            domain("https://alice.example.com/"),
+            Some("example.com".into())
+        );
+        assert_eq!(
+            domain("https://alice.example.co.uk/"),
+            Some("example.co.uk".into())
+        );
+        assert_eq!(domain("https://co.uk/"), None);
     }
 }
@@ -4,11 +4,40 @@ use std::sync::Arc;
 
 use atrium_api::{
     agent::{store::MemorySessionStore, AtpAgent},
-    types::{string::Did, TryFromUnknown},
+    com::atproto::{
+        identity::{sign_plc_operation, submit_plc_operation},
+        sync::get_repo_status,
+    },
+    types::{string::Did, TryFromUnknown, TryIntoUnknown, Unknown},
 };
+use atrium_xrpc::error::{XrpcError, XrpcErrorKind};
 use atrium_xrpc_client::reqwest::ReqwestClient;
 
-use crate::{data::Key, error::Error, local};
+use crate::{
+    data::{AccountStatus, Key, PlcData, State},
+    error::Error,
+    local,
+    remote::plc::SignedOperation,
+};
+
+/// Fetches the server keys recommended by the user's PDS, if we are logged in to it.
+///
+/// `get_recommended_server_keys` requires authentication, so this returns `None`
+/// instead of failing if we don't have a session for the user.
+pub(crate) async fn get_server_keys_if_logged_in(
+    state: &State,
+) -> Result<Option<ServerKeys>, Error> {
+    let Some(pds) = state.endpoint() else {
+        return Ok(None);
+    };
+
+    let agent = Agent::new(pds.into());
+    if agent.resume_session(state.did()).await.is_ok() {
+        agent.get_recommended_server_keys().await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
 
 pub(crate) struct Agent {
     inner: Arc<AtpAgent<MemorySessionStore, ReqwestClient>>,
@@ -72,6 +101,99 @@ impl Agent {
 
         Ok(ServerKeys { signing, rotation })
     }
+
+    /// Fetches the status of the account that the PDS hosts for `did`.
+    ///
+    /// This doesn't require authentication.
+    pub(crate) async fn get_repo_status(&self, did: &Did) -> Result<AccountStatus, Error> {
+        match self
+            .inner
+            .api
+            .com
+            .atproto
+            .sync
+            .get_repo_status(get_repo_status::ParametersData { did: did.clone() }.into())
+            .await
+        {
+            Ok(res) if res.data.active => Ok(AccountStatus::Active),
+            Ok(res) => Ok(AccountStatus::Inactive(res.data.status)),
+            Err(atrium_xrpc::Error::XrpcResponse(XrpcError {
+                error: Some(XrpcErrorKind::Custom(get_repo_status::Error::RepoNotFound(_))),
+                ..
+            })) => Ok(AccountStatus::NotHosted),
+            Err(e) => Err(Error::PdsRepoStatusFailed(e)),
+        }
+    }
+
+    /// Asks the PDS to email the user a token authorizing it to sign a PLC operation.
+    pub(crate) async fn request_plc_operation_signature(&self) -> Result<(), Error> {
+        self.inner
+            .api
+            .com
+            .atproto
+            .identity
+            .request_plc_operation_signature()
+            .await
+            .map_err(Error::PdsPlcSignatureRequestFailed)
+    }
+
+    /// Asks the PDS to sign an operation that updates the user's DID to `data`.
+    ///
+    /// The PDS chooses the operation's `prev`, and signs it with one of its rotation
+    /// keys.
+    pub(crate) async fn sign_plc_operation(
+        &self,
+        token: String,
+        data: &PlcData,
+    ) -> Result<SignedOperation, Error> {
+        let res = self
+            .inner
+            .api
+            .com
+            .atproto
+            .identity
+            .sign_plc_operation(
+                sign_plc_operation::InputData {
+                    also_known_as: Some(data.also_known_as.clone()),
+                    rotation_keys: Some(data.rotation_keys.clone()),
+                    services: Some(to_unknown(&data.services)),
+                    token: Some(token),
+                    verification_methods: Some(to_unknown(&data.verification_methods)),
+                }
+                .into(),
+            )
+            .await
+            .map_err(Error::PdsPlcSigningFailed)?;
+
+        serde_json::to_value(&res.data.operation)
+            .and_then(serde_json::from_value)
+            .map_err(|_| Error::PdsReturnedInvalidOperation)
+    }
+
+    /// Asks the PDS to submit a signed operation for the user's DID to the PLC
+    /// directory, and to announce the resulting identity change to the network.
+    pub(crate) async fn submit_plc_operation(&self, op: &SignedOperation) -> Result<(), Error> {
+        self.inner
+            .api
+            .com
+            .atproto
+            .identity
+            .submit_plc_operation(
+                submit_plc_operation::InputData {
+                    operation: to_unknown(op),
+                }
+                .into(),
+            )
+            .await
+            .map_err(Error::PdsPlcSubmissionFailed)
+    }
+}
+
+fn to_unknown<T: serde::Serialize>(value: &T) -> Unknown {
+    serde_json::to_value(value)
+        .expect("can serialize")
+        .try_into_unknown()
+        .expect("JSON values are valid")
 }
 
 pub(crate) struct ServerKeys {
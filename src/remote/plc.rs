@@ -1,123 +1,550 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
 use atrium_api::types::string::{Cid, Datetime, Did};
+use base64ct::Encoding;
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeDelta};
 use cid::multihash::Multihash;
 use diff::Diff;
-use reqwest::Client;
+use futures_util::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use reqwest::{header::DATE, Client, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 use crate::{
     data::{PlcData, PlcDataDiff, Service, State},
     error::Error,
+    keystore::SecretKey,
+    local,
+    policy::OperationKind,
+    util::derive_did,
 };
 
 mod audit;
-pub(crate) use audit::AuditLog;
+pub(crate) use audit::{
+    AuditLog, Authorship, ExportGroups, HistoryChange, Nullification, ValidatedEntry, ValidatedLog,
+};
+
+pub(crate) mod probe;
 
+mod spec;
+pub(crate) use spec::{SpecProfile, V0_1_RECOVERY_WINDOW_HOURS};
+
+#[cfg(test)]
+pub(crate) mod mock;
+#[cfg(test)]
+pub(crate) mod testing;
 #[cfg(test)]
-mod testing;
+mod tests;
 
-pub(crate) async fn get_state(did: &Did, client: &Client) -> Result<State, Error> {
-    let resp = client
-        .get(format!("https://plc.directory/{}/data", did.as_str()))
-        .send()
-        .await
-        .and_then(|r| r.error_for_status())
-        .map_err(Error::PlcDirectoryRequestFailed)?;
+/// The maximum number of entries plc.directory returns per `/export` page.
+const EXPORT_PAGE_SIZE: usize = 1000;
 
-    resp.json::<State>()
-        .await
-        .map_err(|_| Error::PlcDirectoryReturnedInvalidDidDocument)
+/// The public PLC directory.
+pub(crate) const DEFAULT_DIRECTORY: &str = "https://plc.directory";
+
+/// How to retry requests that fail for transient reasons (connection errors, rate
+/// limiting, and server errors).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryPolicy {
+    /// The maximum number of times to retry a request after it first fails.
+    pub(crate) max_retries: u32,
+    /// How long to wait before the first retry. Each subsequent retry waits twice as
+    /// long as the previous one.
+    pub(crate) initial_backoff: Duration,
 }
 
-pub(crate) async fn get_ops_log(did: &Did, client: &Client) -> Result<OperationsLog, Error> {
-    let resp = client
-        .get(format!("https://plc.directory/{}/log", did.as_str()))
-        .send()
-        .await
-        .and_then(|r| r.error_for_status())
-        .map_err(Error::PlcDirectoryRequestFailed)?;
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
 
-    let ops = resp
-        .json()
-        .await
-        .map_err(|_| Error::PlcDirectoryReturnedInvalidOperationLog)?;
+impl RetryPolicy {
+    /// Returns how long to wait before the given retry (starting from 0).
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+    }
+}
 
-    OperationsLog::new(ops)
+/// A client for plc.directory, or a mirror that implements the same API.
+#[derive(Clone, Debug)]
+pub(crate) struct PlcDirectoryClient {
+    client: Client,
+    base_url: String,
+    retry: RetryPolicy,
 }
 
-pub(crate) async fn get_audit_log(did: &Did, client: &Client) -> Result<AuditLog, Error> {
-    let resp = client
-        .get(format!("https://plc.directory/{}/log/audit", did.as_str()))
-        .send()
-        .await
-        .and_then(|r| r.error_for_status())
-        .map_err(Error::PlcDirectoryRequestFailed)?;
+impl Default for PlcDirectoryClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIRECTORY)
+    }
+}
+
+impl PlcDirectoryClient {
+    /// Creates a client for the directory at `base_url`.
+    pub(crate) fn new(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').into(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Sends a GET request for `path`, retrying transient failures.
+    async fn get(&self, path: &str) -> Result<Response, Error> {
+        let url = format!("{}/{path}", self.base_url);
+        let mut retry = 0;
+        loop {
+            let res = self.client.get(&url).send().await;
+
+            let transient = match &res {
+                Ok(resp) => {
+                    resp.status() == StatusCode::TOO_MANY_REQUESTS
+                        || resp.status().is_server_error()
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+
+            if transient && retry < self.retry.max_retries {
+                tokio::time::sleep(self.retry.backoff(retry)).await;
+                retry += 1;
+            } else {
+                return res
+                    .and_then(|r| r.error_for_status())
+                    .map_err(Error::PlcDirectoryRequestFailed);
+            }
+        }
+    }
+
+    /// Fetches the most recent active operation for a DID.
+    async fn last_op(&self, did: &Did) -> Result<SignedOperation, Error> {
+        self.get(&format!("{}/log/last", did.as_str()))
+            .await?
+            .json()
+            .await
+            .map_err(|_| Error::PlcDirectoryReturnedInvalidOperationLog)
+    }
+
+    async fn export_page(&self, after: Option<&Datetime>) -> Result<Vec<LogEntry>, Error> {
+        let mut path = format!("export?count={EXPORT_PAGE_SIZE}");
+        if let Some(after) = after {
+            // Normalise to UTC so the timestamp needs no escaping in the query string.
+            path.push_str("&after=");
+            path.push_str(
+                &after
+                    .as_ref()
+                    .to_utc()
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            );
+        }
+
+        // The export is newline-delimited JSON.
+        let body = self
+            .get(&path)
+            .await?
+            .text()
+            .await
+            .map_err(Error::PlcDirectoryRequestFailed)?;
+
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|_| Error::PlcDirectoryReturnedInvalidExport)
+            })
+            .collect()
+    }
+}
+
+/// The operations that commands need from a PLC directory.
+///
+/// This is implemented by [`PlcDirectoryClient`] for real directories, and by an
+/// in-memory mock in tests so that command logic can be tested without the network.
+#[async_trait]
+pub(crate) trait Directory: Send + Sync {
+    /// Checks that the directory is healthy, returning the directory's current time
+    /// if it reported one.
+    async fn health(&self) -> Result<Option<DateTime<FixedOffset>>, Error>;
+
+    /// Resolves the current state of a DID.
+    async fn resolve(&self, did: &Did) -> Result<State, Error>;
+
+    /// Fetches the DID document for a DID, exactly as the directory serves it.
+    #[cfg(feature = "server")]
+    async fn did_document(&self, did: &Did) -> Result<serde_json::Value, Error>;
+
+    /// Fetches the genesis operation for a DID.
+    async fn genesis_op(&self, did: &Did) -> Result<SignedOperation, Error>;
+
+    /// Fetches the audit log for a DID, possibly from a local cache.
+    async fn audit_log(&self, did: &Did) -> Result<AuditLog, Error>;
+
+    /// Fetches the complete audit log for a DID, bypassing any local cache, and
+    /// records it locally.
+    async fn fetch_audit_log(&self, did: &Did) -> Result<AuditLog, Error>;
+
+    /// Fetches the complete audit log for a DID, without touching any local state.
+    async fn download_audit_log(&self, did: &Did) -> Result<AuditLog, Error>;
+
+    /// Streams every operation in the directory created after `after` (or from the
+    /// beginning if `None`), in creation order.
+    fn export_stream(&self, after: Option<Datetime>) -> BoxStream<'_, Result<LogEntry, Error>>;
+
+    /// Submits a signed operation for a DID.
+    async fn submit(&self, did: &Did, op: &SignedOperation) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl Directory for PlcDirectoryClient {
+    /// Checks that the directory is healthy, returning the directory's current time
+    /// if it reported one.
+    async fn health(&self) -> Result<Option<DateTime<FixedOffset>>, Error> {
+        let resp = self.get("_health").await?;
+
+        Ok(resp
+            .headers()
+            .get(DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok()))
+    }
+
+    /// Resolves the current state of a DID.
+    async fn resolve(&self, did: &Did) -> Result<State, Error> {
+        self.get(&format!("{}/data", did.as_str()))
+            .await
+            .map_err(|e| match e {
+                // The directory returns 410 Gone for tombstoned DIDs.
+                Error::PlcDirectoryRequestFailed(e) if e.status() == Some(StatusCode::GONE) => {
+                    Error::DidTombstoned(did.as_str().into())
+                }
+                e => e,
+            })?
+            .json::<State>()
+            .await
+            .map_err(|_| Error::PlcDirectoryReturnedInvalidDidDocument)
+    }
+
+    #[cfg(feature = "server")]
+    async fn did_document(&self, did: &Did) -> Result<serde_json::Value, Error> {
+        self.get(did.as_str())
+            .await?
+            .json()
+            .await
+            .map_err(|_| Error::PlcDirectoryReturnedInvalidDidDocument)
+    }
+
+    async fn genesis_op(&self, did: &Did) -> Result<SignedOperation, Error> {
+        let ops: Vec<SignedOperation> = self
+            .get(&format!("{}/log", did.as_str()))
+            .await?
+            .json()
+            .await
+            .map_err(|_| Error::PlcDirectoryReturnedInvalidOperationLog)?;
+
+        // Genesis operations can never be nullified, so the first operation in the log
+        // is always the genesis operation.
+        ops.into_iter()
+            .next()
+            .ok_or(Error::PlcDirectoryReturnedInvalidOperationLog)
+    }
+
+    /// Fetches the audit log for a DID, reusing the locally cached copy if it is
+    /// current.
+    ///
+    /// The directory has no way to fetch only the entries after a given point, but
+    /// every new operation (including those that nullify earlier operations) becomes
+    /// the most recent active operation. So if `/log/last` matches the cached log, the
+    /// cached log is still complete and the (potentially very large) audit log isn't
+    /// downloaded.
+    async fn audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        if let Some(entries) = load_cached_audit_log(did).await {
+            let last_active = entries.iter().rev().find(|entry| !entry.nullified);
+            if let (Some(cached), Ok(last)) = (last_active, self.last_op(did).await) {
+                if cached.cid == last.cid() {
+                    return Ok(AuditLog::new(did.clone(), entries));
+                }
+            }
+        }
+
+        self.fetch_audit_log(did).await
+    }
+
+    /// Fetches the complete audit log for a DID, and updates the local cache and the
+    /// seen history with it.
+    async fn fetch_audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        let log = self.download_audit_log(did).await?;
+        save_cached_audit_log(did, log.entries()).await;
+        record_seen_history(did, log.entries()).await;
+        Ok(log)
+    }
+
+    async fn download_audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        let entries = self
+            .get(&format!("{}/log/audit", did.as_str()))
+            .await?
+            .json()
+            .await
+            .map_err(|_| Error::PlcDirectoryReturnedInvalidAuditLog)?;
+
+        Ok(AuditLog::new(did.clone(), entries))
+    }
+
+    /// Streams every operation in the directory created after `after` (or from the
+    /// beginning if `None`), in creation order.
+    ///
+    /// Pages through `/export` using the creation time of the last entry received as
+    /// the cursor, until a page brings nothing new. Several operations can share a
+    /// creation time, and a page may end partway through them, so each page after the
+    /// first is requested from just before the cursor, and the entries around it that
+    /// were already received are skipped. Failed page requests are retried like any
+    /// other request.
+    fn export_stream(&self, after: Option<Datetime>) -> BoxStream<'_, Result<LogEntry, Error>> {
+        let boundary = HashMap::<Cid, DateTime<FixedOffset>>::new();
+        stream::try_unfold(Some((after, boundary)), move |cursor| async move {
+            let Some((after, mut boundary)) = cursor else {
+                return Ok(None);
+            };
+
+            let from = match after {
+                Some(after) if !boundary.is_empty() => {
+                    Some(Datetime::new(*after.as_ref() - TimeDelta::milliseconds(1)))
+                }
+                after => after,
+            };
+            let page = self
+                .export_page(from.as_ref())
+                .await?
+                .into_iter()
+                .filter(|entry| !boundary.contains_key(&entry.cid))
+                .collect::<Vec<_>>();
 
-    let entries = resp
-        .json()
+            let next = match page.last() {
+                // A page with nothing new means we have caught up with the directory.
+                None => None,
+                Some(last) => {
+                    let cursor = last.created_at.clone();
+                    let since = *cursor.as_ref() - TimeDelta::milliseconds(1);
+                    boundary.extend(
+                        page.iter()
+                            .map(|entry| (entry.cid.clone(), *entry.created_at.as_ref())),
+                    );
+                    boundary.retain(|_, created_at| *created_at >= since);
+                    Some((Some(cursor), boundary))
+                }
+            };
+
+            Ok(Some((stream::iter(page.into_iter().map(Ok)), next)))
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    /// Submits a signed operation for a DID.
+    ///
+    /// This is not retried: if the directory accepted the operation before a failure,
+    /// resubmitting it would be rejected anyway.
+    async fn submit(&self, did: &Did, op: &SignedOperation) -> Result<(), Error> {
+        let resp = self
+            .client
+            .post(format!("{}/{}", self.base_url, did.as_str()))
+            .json(op)
+            .send()
+            .await
+            .map_err(Error::PlcDirectoryRequestFailed)?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            let status = resp.status();
+            // The directory explains why it rejected the operation in the body.
+            let reason = resp.text().await.unwrap_or_default();
+            Err(Error::PlcDirectoryRejectedOperation(format!(
+                "{status}: {reason}"
+            )))
+        }
+    }
+}
+
+fn audit_log_cache_file(did: &Did) -> Option<std::path::PathBuf> {
+    // `:` is not valid in Windows filenames.
+    local::cache_file(format!("logs/{}.json", did.as_str().replace(':', "_")))
+}
+
+/// Loads the cached audit log for a DID, if any.
+///
+/// The cached log is not trusted: it is validated like any other audit log.
+async fn load_cached_audit_log(did: &Did) -> Option<Vec<LogEntry>> {
+    let data = tokio::fs::read(audit_log_cache_file(did)?).await.ok()?;
+    let entries: Vec<LogEntry> = serde_json::from_slice(&data).ok()?;
+    entries
+        .iter()
+        .all(|entry| &entry.did == did)
+        .then_some(entries)
+}
+
+fn seen_history_file(did: &Did) -> Option<std::path::PathBuf> {
+    local::data_file(format!("history/{}.json", did.as_str().replace(':', "_")))
+}
+
+/// Returns every audit log entry we have seen for a DID, if any.
+///
+/// This is the baseline that `directory check-history` compares the directory
+/// against. Unlike the cache, it is append-only: entries are never removed or
+/// replaced, and only ever become nullified, so a directory can't change the baseline
+/// by serving a rewritten log to other commands.
+pub(crate) async fn seen_history(did: &Did) -> Option<AuditLog> {
+    let data = tokio::fs::read(seen_history_file(did)?).await.ok()?;
+    let entries: Vec<LogEntry> = serde_json::from_slice(&data).ok()?;
+    entries
+        .iter()
+        .all(|entry| &entry.did == did)
+        .then(|| AuditLog::new(did.clone(), entries))
+}
+
+/// Adds the entries of an audit log downloaded from the directory to the seen history
+/// for a DID.
+///
+/// Like the cache, this is best-effort, so failures to save it are ignored.
+pub(crate) async fn record_seen_history(did: &Did, entries: &[LogEntry]) {
+    let mut seen = seen_history(did)
         .await
-        .map_err(|_| Error::PlcDirectoryReturnedInvalidAuditLog)?;
+        .map(|log| log.entries().to_vec())
+        .unwrap_or_default();
+    if !audit::merge_seen(&mut seen, entries) {
+        return;
+    }
+
+    if let (Some(path), Ok(data)) = (seen_history_file(did), serde_json::to_vec(&seen)) {
+        if let Some(parent) = path.parent() {
+            let _ = tokio::fs::create_dir_all(parent).await;
+        }
+        let _ = local::write_atomic(&path, data).await;
+    }
+}
+
+/// Returns the DIDs we have seen audit logs for.
+pub(crate) async fn seen_dids() -> Vec<Did> {
+    let Some(path) = local::data_file("history") else {
+        return vec![];
+    };
+    let Ok(mut dir) = tokio::fs::read_dir(path).await else {
+        return vec![];
+    };
 
-    Ok(AuditLog::new(did.clone(), entries))
+    let mut dids = vec![];
+    while let Ok(Some(entry)) = dir.next_entry().await {
+        if let Some(did) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|name| Did::new(name.replace('_', ":")).ok())
+        {
+            dids.push(did);
+        }
+    }
+    dids
+}
+
+/// Saves the audit log for a DID to the cache.
+///
+/// The cache is an optimisation, so failures to save it are ignored.
+async fn save_cached_audit_log(did: &Did, entries: &[LogEntry]) {
+    if let (Some(cache_file), Ok(data)) = (audit_log_cache_file(did), serde_json::to_vec(entries)) {
+        if let Some(parent) = cache_file.parent() {
+            let _ = tokio::fs::create_dir_all(parent).await;
+        }
+        let _ = local::write_atomic(&cache_file, data).await;
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct OperationsLog {
+    pub(crate) created_at: Datetime,
     pub(crate) create: PlcData,
-    pub(crate) updates: Vec<PlcDataDiff>,
-    pub(crate) deactivated: bool,
+    pub(crate) updates: Vec<(Datetime, PlcDataDiff)>,
+    /// The time at which the DID was tombstoned, if it has been.
+    pub(crate) tombstoned: Option<Datetime>,
 }
 
 impl OperationsLog {
-    fn new(mut ops: Vec<SignedOperation>) -> Result<Self, Error> {
-        let deactivated = match ops.pop() {
-            Some(SignedOperation {
-                content: Operation::Tombstone(_),
+    /// Constructs the log from the active (non-nullified) entries of an audit log.
+    ///
+    /// Operations of unknown types are ignored, as we can't compute their effects.
+    pub(crate) fn from_audit_log(log: &AuditLog) -> Result<Self, Error> {
+        Self::new(
+            log.entries()
+                .iter()
+                .filter(|entry| {
+                    !entry.nullified && entry.operation.content.unknown_type().is_none()
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Constructs the log from the active (non-nullified) entries for a DID.
+    fn new(mut entries: Vec<LogEntry>) -> Result<Self, Error> {
+        let tombstoned = match entries.pop() {
+            Some(LogEntry {
+                operation:
+                    SignedOperation {
+                        content: Operation::Tombstone(_),
+                        ..
+                    },
+                created_at,
                 ..
-            }) => true,
-            Some(op) => {
-                ops.push(op);
-                false
+            }) => Some(created_at),
+            Some(entry) => {
+                entries.push(entry);
+                None
             }
-            None => false,
+            None => None,
         };
 
-        let mut ops = ops.into_iter();
+        let mut entries = entries.into_iter();
 
-        let create = match ops.next() {
-            Some(SignedOperation {
-                content: Operation::Change(op),
-                ..
-            }) if op.prev.is_none() => Ok(op.data),
-            Some(SignedOperation {
-                content: Operation::LegacyCreate(op),
-                ..
-            }) => Ok(op.into_plc_data()),
-            _ => Err(Error::PlcDirectoryReturnedInvalidOperationLog),
+        let (created_at, create) = match entries.next() {
+            Some(entry) => match entry.operation.content {
+                Operation::Change(op) if op.prev.is_none() => Ok((entry.created_at, op.data)),
+                Operation::LegacyCreate(op) => Ok((entry.created_at, op.into_plc_data())),
+                _ => Err(Error::PlcDirectoryReturnedInvalidOperationLog),
+            },
+            None => Err(Error::PlcDirectoryReturnedInvalidOperationLog),
         }?;
 
-        let updates = ops
-            .scan(create.clone(), |state, op| match op.content {
-                Operation::Change(op) if op.prev.is_some() => {
-                    let delta = state.diff(&op.data);
-                    *state = op.data;
-                    Some(Ok(delta))
+        let updates = entries
+            .scan(create.clone(), |state, entry| {
+                match entry.operation.content {
+                    Operation::Change(op) if op.prev.is_some() => {
+                        let delta = state.diff(&op.data);
+                        *state = op.data;
+                        Some(Ok((entry.created_at, delta)))
+                    }
+                    _ => Some(Err(Error::PlcDirectoryReturnedInvalidOperationLog)),
                 }
-                _ => Some(Err(Error::PlcDirectoryReturnedInvalidOperationLog)),
             })
             .collect::<Result<_, _>>()?;
 
         Ok(Self {
+            created_at,
             create,
             updates,
-            deactivated,
+            tombstoned,
         })
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct LogEntry {
+pub(crate) struct LogEntry {
     did: Did,
     operation: SignedOperation,
     cid: Cid,
@@ -125,6 +552,29 @@ struct LogEntry {
     created_at: Datetime,
 }
 
+impl LogEntry {
+    pub(crate) fn did(&self) -> &Did {
+        &self.did
+    }
+
+    pub(crate) fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Returns the CID of the operation this entry follows, if any.
+    pub(crate) fn prev(&self) -> Option<&Cid> {
+        self.operation.prev()
+    }
+
+    pub(crate) fn created_at(&self) -> &Datetime {
+        &self.created_at
+    }
+
+    pub(crate) fn is_nullified(&self) -> bool {
+        self.nullified
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct SignedOperation {
     #[serde(flatten)]
@@ -142,10 +592,102 @@ impl SignedOperation {
         serde_ipld_dagcbor::to_vec(self).unwrap()
     }
 
+    /// Decodes an operation received as DAG-CBOR from an untrusted source.
+    ///
+    /// The operation is re-encoded and must match `bytes` exactly. Non-canonical
+    /// encodings would otherwise be silently normalised, changing the operation's CID.
+    pub(crate) fn from_dag_cbor_strict(bytes: &[u8]) -> Result<Self, Error> {
+        let op: Self = serde_ipld_dagcbor::from_slice(bytes)
+            .map_err(|e| Error::InvalidOperationCbor(e.to_string()))?;
+
+        if op.signed_bytes() == bytes {
+            Ok(op)
+        } else {
+            Err(Error::NonCanonicalOperation)
+        }
+    }
+
+    /// Returns the input for `com.atproto.identity.submitPlcOperation`, which submits
+    /// this operation to the directory via the user's PDS.
+    pub(crate) fn to_submit_plc_operation(&self) -> serde_json::Value {
+        serde_json::json!({ "operation": self })
+    }
+
+    /// Returns the state this operation sets, or `None` if it is a tombstone (or of an
+    /// unknown type).
+    pub(crate) fn data(&self) -> Option<PlcData> {
+        match &self.content {
+            Operation::Change(op) => Some(op.data.clone()),
+            Operation::LegacyCreate(op) => Some(op.clone().into_plc_data()),
+            Operation::Tombstone(_) | Operation::Unknown(_) => None,
+        }
+    }
+
+    /// Returns the CID of the operation this one follows, or `None` for a genesis
+    /// operation (or an unknown operation type).
+    pub(crate) fn prev(&self) -> Option<&Cid> {
+        match &self.content {
+            Operation::Change(op) => op.prev.as_ref(),
+            Operation::Tombstone(op) => Some(&op.prev),
+            Operation::LegacyCreate(_) | Operation::Unknown(_) => None,
+        }
+    }
+
+    /// Returns the DID that this operation creates, or `None` if this is not a genesis
+    /// operation.
+    pub(crate) fn derived_did(&self) -> Option<Did> {
+        match &self.content {
+            Operation::Change(op) if op.prev.is_none() => Some(derive_did(&self.signed_bytes())),
+            Operation::LegacyCreate(_) => Some(derive_did(&self.signed_bytes())),
+            _ => None,
+        }
+    }
+
+    /// For a genesis operation, returns the index of the operation's own rotation key
+    /// that signed it, or `None` if the signature is invalid or this is not a genesis
+    /// operation.
+    pub(crate) fn genesis_signer(&self) -> Option<usize> {
+        let signature = base64ct::Base64UrlUnpadded::decode_vec(&self.sig).ok()?;
+        let unsigned = self.unsigned_bytes();
+        let check_sig = |(_, did_key): &(_, &str)| {
+            atrium_crypto::verify::verify_signature(did_key, &unsigned, &signature).is_ok()
+        };
+
+        match &self.content {
+            Operation::Change(op) if op.prev.is_none() => {
+                op.rotation_keys().enumerate().find(check_sig)
+            }
+            Operation::LegacyCreate(op) => op.rotation_keys().enumerate().find(check_sig),
+            _ => None,
+        }
+        .map(|(index, _)| index)
+    }
+
+    /// Returns the kinds of change this operation makes to `prev`, the state it
+    /// updates.
+    ///
+    /// If `prev` is unknown, an update is assumed to change the rotation keys.
+    pub(crate) fn kinds(&self, prev: Option<&PlcData>) -> Vec<OperationKind> {
+        match &self.content {
+            Operation::Change(op) if op.prev.is_none() => vec![OperationKind::Genesis],
+            Operation::LegacyCreate(_) => vec![OperationKind::Genesis],
+            Operation::Change(op) => {
+                let mut kinds = vec![OperationKind::Update];
+                if prev.map_or(true, |prev| prev.rotation_keys != op.data.rotation_keys) {
+                    kinds.push(OperationKind::RotationKeys);
+                }
+                kinds
+            }
+            Operation::Tombstone(_) => vec![OperationKind::Tombstone],
+            // The policy's default applies to operations we don't understand.
+            Operation::Unknown(_) => vec![],
+        }
+    }
+
     /// Computes the CID for this operation.
     ///
     /// This is used in `prev` references to prior operations.
-    fn cid(&self) -> Cid {
+    pub(crate) fn cid(&self) -> Cid {
         Cid::new(cid::Cid::new_v1(
             0x71,
             Multihash::wrap(0x12, &Sha256::digest(self.signed_bytes())).expect("correct length"),
@@ -153,21 +695,135 @@ impl SignedOperation {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
+/// An operation that has not been signed yet.
+#[derive(Clone, Debug)]
+pub(crate) struct UnsignedOperation(Operation);
+
+impl UnsignedOperation {
+    /// A genesis operation, which creates a DID with `data`.
+    pub(crate) fn genesis(data: PlcData) -> Self {
+        Self(Operation::Change(ChangeOp { data, prev: None }))
+    }
+
+    /// An operation that updates a DID to `data`, following `prev`.
+    pub(crate) fn update(prev: Cid, data: PlcData) -> Self {
+        Self(Operation::Change(ChangeOp {
+            data,
+            prev: Some(prev),
+        }))
+    }
+
+    /// An operation that permanently deactivates a DID, following `prev`.
+    pub(crate) fn tombstone(prev: Cid) -> Self {
+        Self(Operation::Tombstone(TombstoneOp { prev }))
+    }
+
+    /// Parses an unsigned `plc_operation` or `plc_tombstone` from JSON.
+    ///
+    /// If an update has no `prev`, it follows `head`; operations are never parsed as
+    /// genesis operations.
+    pub(crate) fn from_template(template: serde_json::Value, head: &Cid) -> Result<Self, Error> {
+        let mut op: Operation =
+            serde_json::from_value(template).map_err(Error::InvalidOperation)?;
+        match &mut op {
+            Operation::Change(op) => {
+                op.prev.get_or_insert_with(|| head.clone());
+            }
+            Operation::Tombstone(_) => (),
+            Operation::LegacyCreate(_) | Operation::Unknown(_) => {
+                return Err(Error::UnsupportedOperationTemplate)
+            }
+        }
+        Ok(Self(op))
+    }
+
+    /// Returns the operation as JSON, without a signature.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(&self.0).expect("can serialize")
+    }
+
+    /// Signs the operation with a rotation key.
+    pub(crate) fn sign(self, key: &SecretKey) -> SignedOperation {
+        let sig = key.sign(&self.0.unsigned_bytes());
+        SignedOperation {
+            content: self.0,
+            sig: base64ct::Base64UrlUnpadded::encode_string(&sig),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 enum Operation {
-    #[serde(rename = "plc_operation")]
     Change(ChangeOp),
-    #[serde(rename = "plc_tombstone")]
     Tombstone(TombstoneOp),
-    #[serde(rename = "create")]
     LegacyCreate(LegacyCreateOp),
+    /// An operation of a type this tool doesn't know about (e.g. from a future version
+    /// of the specification), preserved exactly so that its CID can still be checked.
+    Unknown(UnknownOp),
+}
+
+/// The operation types defined by the DID PLC specification, as they are encoded.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum KnownOperation<C, T, L> {
+    #[serde(rename = "plc_operation")]
+    Change(C),
+    #[serde(rename = "plc_tombstone")]
+    Tombstone(T),
+    #[serde(rename = "create")]
+    LegacyCreate(L),
+}
+
+const KNOWN_OPERATION_TYPES: &[&str] = &["plc_operation", "plc_tombstone", "create"];
+
+impl Serialize for Operation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Operation::Change(op) => KnownOperation::<_, (), ()>::Change(op).serialize(serializer),
+            Operation::Tombstone(op) => {
+                KnownOperation::<(), _, ()>::Tombstone(op).serialize(serializer)
+            }
+            Operation::LegacyCreate(op) => {
+                KnownOperation::<(), (), _>::LegacyCreate(op).serialize(serializer)
+            }
+            Operation::Unknown(op) => op.0.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Operation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let fields = serde_json::Map::deserialize(deserializer)?;
+        match fields.get("type").and_then(|t| t.as_str()) {
+            None => Err(D::Error::missing_field("type")),
+            Some(t) if KNOWN_OPERATION_TYPES.contains(&t) => {
+                KnownOperation::deserialize(serde_json::Value::Object(fields))
+                    .map(|op| match op {
+                        KnownOperation::Change(op) => Operation::Change(op),
+                        KnownOperation::Tombstone(op) => Operation::Tombstone(op),
+                        KnownOperation::LegacyCreate(op) => Operation::LegacyCreate(op),
+                    })
+                    .map_err(D::Error::custom)
+            }
+            Some(_) => Ok(Operation::Unknown(UnknownOp(fields))),
+        }
+    }
 }
 
 impl Operation {
     fn unsigned_bytes(&self) -> Vec<u8> {
         serde_ipld_dagcbor::to_vec(self).unwrap()
     }
+
+    /// Returns the type of this operation, if this tool doesn't know about it.
+    fn unknown_type(&self) -> Option<&str> {
+        match self {
+            Operation::Unknown(op) => Some(op.r#type()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -184,7 +840,7 @@ struct ChangeOp {
 }
 
 impl ChangeOp {
-    fn rotation_keys(&self) -> impl Iterator<Item = &str> {
+    fn rotation_keys(&self) -> impl Iterator<Item = &str> + Clone {
         self.data.rotation_keys.iter().map(|s| s.as_str())
     }
 }
@@ -197,6 +853,19 @@ struct TombstoneOp {
     prev: Cid,
 }
 
+/// The fields of an operation of unknown type, including its `type`.
+#[derive(Clone, Debug)]
+struct UnknownOp(serde_json::Map<String, serde_json::Value>);
+
+impl UnknownOp {
+    fn r#type(&self) -> &str {
+        self.0
+            .get("type")
+            .and_then(|t| t.as_str())
+            .expect("checked during parsing")
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct LegacyCreateOp {
@@ -214,7 +883,7 @@ struct LegacyCreateOp {
 }
 
 impl LegacyCreateOp {
-    fn rotation_keys(&self) -> impl Iterator<Item = &str> {
+    fn rotation_keys(&self) -> impl Iterator<Item = &str> + Clone {
         [self.recovery_key.as_str(), self.signing_key.as_str()].into_iter()
     }
 
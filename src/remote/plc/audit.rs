@@ -1,17 +1,74 @@
-use std::collections::HashMap;
+use std::collections::{hash_map::DefaultHasher, HashMap, VecDeque};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-use atrium_api::types::string::{Cid, Did};
+use atrium_api::types::string::{Cid, Datetime, Did};
 use base64ct::Encoding;
+use chrono::{DateTime, FixedOffset};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 
-use crate::util::derive_did;
-
-use super::{LogEntry, Operation};
+use super::{LogEntry, Operation, SignedOperation, SpecProfile};
+use crate::data::PlcData;
 
 #[cfg(test)]
 mod tests;
 
-const RECOVERY_WINDOW: chrono::TimeDelta = chrono::TimeDelta::hours(72);
+/// Groups entries from the directory's export into the audit logs (or fragments of
+/// them) for each DID as they arrive, holding at most `capacity` entries at a time.
+pub(crate) struct ExportGroups {
+    capacity: usize,
+    len: usize,
+    /// DIDs in the order that their buffered fragments started.
+    order: VecDeque<Did>,
+    logs: HashMap<Did, AuditLog>,
+}
+
+impl ExportGroups {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            len: 0,
+            order: VecDeque::new(),
+            logs: HashMap::new(),
+        }
+    }
+
+    /// Adds an entry, returning the oldest fragment if the buffer is full.
+    ///
+    /// A DID's later entries then start a new fragment.
+    pub(crate) fn push(&mut self, entry: LogEntry) -> Option<AuditLog> {
+        match self.logs.get_mut(&entry.did) {
+            Some(log) => log.entries.push(entry),
+            None => {
+                self.order.push_back(entry.did.clone());
+                self.logs.insert(
+                    entry.did.clone(),
+                    AuditLog::new(entry.did.clone(), vec![entry]),
+                );
+            }
+        }
+        self.len += 1;
+
+        if self.len > self.capacity {
+            let did = self.order.pop_front().expect("not empty");
+            let log = self.logs.remove(&did).expect("present");
+            self.len -= log.entries.len();
+            Some(log)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the buffered fragments, oldest first.
+    pub(crate) fn finish(mut self) -> Vec<AuditLog> {
+        self.order
+            .into_iter()
+            .map(|did| self.logs.remove(&did).expect("present"))
+            .collect()
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct AuditLog {
@@ -24,201 +81,833 @@ impl AuditLog {
         Self { did, entries }
     }
 
-    pub(crate) fn validate(&self) -> Result<(), Vec<AuditError>> {
-        let mut errors = vec![];
+    /// Returns the audit log of a DID that has not been created yet.
+    pub(crate) fn empty(did: Did) -> Self {
+        Self::new(did, vec![])
+    }
 
-        // For the genesis operation, validate the DID.
-        match self.entries.first() {
-            None => errors.push(AuditError::AuditLogEmpty),
-            Some(entry) => {
-                let mut validate_did = |signed_bytes| {
-                    let did = derive_did(signed_bytes);
-                    if did != self.did {
-                        errors.push(AuditError::GenesisOperationInvalidDid {
-                            expected: self.did.clone(),
-                            actual: did,
-                        })
-                    }
-                };
+    pub(crate) fn did(&self) -> &Did {
+        &self.did
+    }
 
-                match &entry.operation.content {
-                    Operation::Change(op) if op.prev.is_none() => {
-                        validate_did(&entry.operation.signed_bytes())
-                    }
-                    Operation::LegacyCreate(_) => validate_did(&entry.operation.signed_bytes()),
-                    _ => errors.push(AuditError::GenesisOperationNotCreate),
+    pub(crate) fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Returns `true` if this log starts with a genesis operation.
+    pub(crate) fn starts_with_genesis(&self) -> bool {
+        self.entries
+            .first()
+            .is_some_and(|entry| entry.operation.derived_did().is_some())
+    }
+
+    /// Returns `true` if every operation in `other` is also in this log, ignoring
+    /// whether either has been nullified.
+    pub(crate) fn contains_all(&self, other: &Self) -> bool {
+        let index = cid_index(&self.entries);
+        other.entries.iter().all(|entry| {
+            index
+                .get(&entry.cid)
+                .is_some_and(|&i| self.entries[i].same_operation(entry))
+        })
+    }
+
+    /// Returns the ways in which this log rewrites the history in `previous`, an
+    /// earlier copy of the same DID's audit log.
+    ///
+    /// New operations (including those that nullify earlier ones) are legitimate, but
+    /// operations never disappear, change, or become un-nullified.
+    pub(crate) fn history_changes(&self, previous: &Self) -> Vec<HistoryChange> {
+        let index = cid_index(&self.entries);
+        previous
+            .entries
+            .iter()
+            .filter_map(|old| match index.get(&old.cid) {
+                None => Some(HistoryChange::Removed(old.cid.clone())),
+                Some(&i) if !self.entries[i].same_operation(old) => {
+                    Some(HistoryChange::Rewritten(old.cid.clone()))
+                }
+                Some(&i) if old.nullified && !self.entries[i].nullified => {
+                    Some(HistoryChange::Restored(old.cid.clone()))
                 }
+                Some(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns the index of the first entry at which this log and `other` differ, or
+    /// `None` if they contain the same operations in the same order.
+    pub(crate) fn divergence(&self, other: &Self) -> Option<usize> {
+        (0..self.entries.len().max(other.entries.len())).find(|&i| {
+            match (self.entries.get(i), other.entries.get(i)) {
+                (Some(a), Some(b)) => a.cid != b.cid || a.nullified != b.nullified,
+                _ => true,
             }
+        })
+    }
+
+    /// Returns the CID and type of each operation whose type we don't know about.
+    ///
+    /// These operations are excluded from validation and state computation.
+    pub(crate) fn unknown_operations(&self) -> Vec<(&Cid, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                entry
+                    .operation
+                    .content
+                    .unknown_type()
+                    .map(|t| (&entry.cid, t))
+            })
+            .collect()
+    }
+
+    /// Returns the CID of the most recent entry in this audit log.
+    pub(crate) fn head(&self) -> Option<&Cid> {
+        self.entries.last().map(|entry| &entry.cid)
+    }
+
+    /// Returns a hash of every entry in this audit log, including whether each is
+    /// nullified, so that any change to the log changes the hash.
+    pub(crate) fn digest(&self) -> String {
+        hex::encode(Sha256::digest(
+            serde_json::to_vec(&self.entries).expect("can serialize"),
+        ))
+    }
+
+    /// Returns the authorship of each active (non-nullified) operation, in order.
+    pub(crate) fn authorship(&self) -> Vec<Authorship> {
+        let index = cid_index(&self.entries);
+        let mut key_sets = KeySets::default();
+
+        self.entries
+            .iter()
+            .filter(|entry| !entry.nullified && !entry.is_unknown())
+            .map(|entry| {
+                let prev = match &entry.operation.content {
+                    Operation::Change(op) => op.prev.as_ref(),
+                    Operation::Tombstone(op) => Some(&op.prev),
+                    Operation::LegacyCreate(_) | Operation::Unknown(_) => None,
+                }
+                .map(|prev| {
+                    index
+                        .get(prev)
+                        .map(|&i| &self.entries[i])
+                        .filter(|prev| !prev.is_unknown())
+                });
+
+                match prev {
+                    // The declared previous operation is missing, so we can't determine
+                    // who was authorized to sign this one.
+                    Some(None) => Authorship {
+                        created_at: entry.created_at.clone(),
+                        authorized_keys: Arc::new([]),
+                        signer: None,
+                    },
+                    Some(Some(prev)) => Authorship {
+                        created_at: entry.created_at.clone(),
+                        authorized_keys: key_sets.intern(prev.rotation_keys()),
+                        signer: entry.validate_with_prev(Some(prev)).1,
+                    },
+                    None => Authorship {
+                        created_at: entry.created_at.clone(),
+                        authorized_keys: key_sets.intern(entry.rotation_keys()),
+                        signer: entry.validate_with_prev(None).1,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Validates this audit log against the DID PLC v0.1 specification.
+    pub(crate) fn validate(&self) -> Result<(), Vec<AuditError>> {
+        self.validate_with_profile(&SpecProfile::default())
+    }
+
+    /// Validates this audit log against the given specification profile.
+    pub(crate) fn validate_with_profile(
+        &self,
+        profile: &SpecProfile,
+    ) -> Result<(), Vec<AuditError>> {
+        self.validated_with_profile(profile).map(|_| ())
+    }
+
+    /// Validates this audit log against the DID PLC v0.1 specification, returning the
+    /// facts computed for each entry if it is valid.
+    pub(crate) fn validated(&self) -> Result<ValidatedLog, Vec<AuditError>> {
+        self.validated_with_profile(&SpecProfile::default())
+    }
+
+    fn validated_with_profile(
+        &self,
+        profile: &SpecProfile,
+    ) -> Result<ValidatedLog, Vec<AuditError>> {
+        // We can't validate operations we don't understand, so we exclude them (and
+        // anything that depends on them will fail validation).
+        let entries = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.is_unknown())
+            .collect::<Vec<_>>();
+        let checks = check_entries(&self.did, &entries, vec![None; entries.len()]);
+        validate_graph(&self.did, profile, &entries, &checks)
+    }
+
+    /// Checks that the directory would accept `op` if it received it at `created_at`,
+    /// by validating this log with `op` appended.
+    ///
+    /// If `op` follows an operation other than the most recent one, the active
+    /// operations after its `prev` are treated as nullified by it, so that the
+    /// recovery rules are checked too.
+    pub(crate) fn validate_next(
+        self,
+        op: &SignedOperation,
+        created_at: Datetime,
+    ) -> Result<(), Vec<AuditError>> {
+        let (state, res) = Self::validate_append(
+            ValidatedState::new(self.did.clone(), SpecProfile::default()),
+            self.entries,
+        );
+        // An empty log only becomes valid once its genesis operation is appended.
+        if !state.entries.is_empty() {
+            res?;
         }
 
-        // Track the graph of operations.
-        type EntryWithAuthority<'a> = (&'a LogEntry, Option<usize>);
-        let mut active_graph: HashMap<&Cid, (Option<EntryWithAuthority>, Vec<EntryWithAuthority>)> =
-            HashMap::new();
+        let after_prev = match op.prev() {
+            Some(prev) => state
+                .entries
+                .iter()
+                .position(|entry| &entry.cid == prev)
+                .map_or(state.entries.len(), |i| i + 1),
+            None => 0,
+        };
+        let mut new_entries = state.entries[after_prev..]
+            .iter()
+            .filter(|entry| !entry.nullified)
+            .map(|entry| LogEntry {
+                nullified: true,
+                ..entry.clone()
+            })
+            .collect::<Vec<_>>();
+        new_entries.push(LogEntry {
+            did: state.did.clone(),
+            operation: op.clone(),
+            cid: op.cid(),
+            nullified: false,
+            created_at,
+        });
 
-        for (i, entry) in self.entries.iter().enumerate() {
-            // Perform non-contextual validation.
-            if let Err(e) = entry.validate_self(&self.did) {
-                errors.extend(e);
-            }
+        Self::validate_append(state, new_entries).1.map(|_| ())
+    }
 
-            // Find the operation declared as immediately prior to this one, if any.
-            let find_prev = |prev: &Cid| {
-                let (past, future) = self.entries.split_at(i);
-
-                if let Some(entry) = past.iter().find(|entry| &entry.cid == prev) {
-                    Ok(entry)
-                } else if future.iter().any(|entry| &entry.cid == prev) {
-                    // Audit log operations should be correctly ordered.
-                    Err(AuditError::PrevReferencesFuture {
-                        cid: entry.cid.clone(),
-                        prev: prev.clone(),
-                    })
-                } else {
-                    Err(AuditError::PrevMissing { prev: prev.clone() })
+    /// Extends a previous validation with new entries, returning the updated state
+    /// along with the validation result for the extended log.
+    ///
+    /// An entry with the same CID and content as a previously-validated entry replaces
+    /// it instead of being appended, so a refetched audit log can also be passed in its
+    /// entirety (for example, after some of its entries have been nullified). Only the
+    /// appended entries have their CIDs and signatures verified.
+    fn validate_append(
+        state: ValidatedState,
+        new_entries: Vec<LogEntry>,
+    ) -> (ValidatedState, Result<ValidatedLog, Vec<AuditError>>) {
+        let ValidatedState {
+            did,
+            profile,
+            mut entries,
+            checks,
+        } = state;
+        let mut checks = checks.into_iter().map(Some).collect::<Vec<_>>();
+        // We can't validate operations we don't understand, so we exclude them (and
+        // anything that depends on them will fail validation).
+        let new_entries = new_entries
+            .into_iter()
+            .filter(|entry| !entry.is_unknown())
+            .collect::<Vec<_>>();
+        let replaces = {
+            let existing = cid_index(&entries);
+            new_entries
+                .iter()
+                .map(|entry| {
+                    existing
+                        .get(&entry.cid)
+                        .copied()
+                        .filter(|&i| entries[i].same_operation(entry))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (entry, replaces) in new_entries.into_iter().zip(replaces) {
+            match replaces {
+                Some(i) => entries[i] = entry,
+                None => {
+                    entries.push(entry);
+                    checks.push(None);
                 }
+            }
+        }
+
+        let refs = entries.iter().collect::<Vec<_>>();
+        let checks = check_entries(&did, &refs, checks);
+        let res = validate_graph(&did, &profile, &refs, &checks);
+        let state = ValidatedState {
+            did,
+            profile,
+            entries,
+            checks,
+        };
+        (state, res)
+    }
+}
+
+/// Computes the missing checks for `entries`, where `checks` holds the checks already
+/// computed for a prefix of them.
+fn check_entries(
+    did: &Did,
+    entries: &[&LogEntry],
+    mut checks: Vec<Option<EntryCheck>>,
+) -> Vec<EntryCheck> {
+    // Find the operation declared as immediately prior to each new entry, if any.
+    // This is cheap, and lets us validate each entry independently below.
+    let index = cid_index(entries.iter().copied());
+    #[allow(clippy::result_large_err)]
+    let prevs = checks
+        .iter()
+        .enumerate()
+        .filter(|(_, check)| check.is_none())
+        .map(|(i, _)| {
+            let entry = entries[i];
+            let find_prev = |prev: &Cid| match index.get(prev) {
+                Some(&index) if index < i => Ok(index),
+                // Audit log operations should be correctly ordered.
+                Some(_) => Err(AuditError::PrevReferencesFuture {
+                    cid: entry.cid.clone(),
+                    prev: prev.clone(),
+                }),
+                None => Err(AuditError::PrevMissing { prev: prev.clone() }),
             };
 
             let prev = match &entry.operation.content {
                 Operation::Change(op) => op.prev.as_ref().map(find_prev).transpose(),
                 Operation::Tombstone(op) => find_prev(&op.prev).map(Some),
                 Operation::LegacyCreate(_) => Ok(None),
+                Operation::Unknown(_) => unreachable!("unknown operations are never validated"),
             };
+            (i, prev)
+        })
+        .collect::<Vec<_>>();
 
-            match prev {
-                // We could not locate the declared most-recent previous operation.
-                // We can't perform any more checks on this entry.
-                Err(e) => errors.push(e),
-
-                // Either this is a genesis operation, or we located its most-recent
-                // previous operation.
-                Ok(prev) => {
-                    let (res, signer_authority) = entry.validate_with_prev(prev);
-                    if let Err(e) = res {
-                        errors.extend(e);
-                    }
+    // Computing CIDs and verifying signatures dominates the cost of validation, and
+    // is independent per entry, so we do it in parallel.
+    let new_checks = prevs
+        .into_par_iter()
+        .map(|(i, prev)| {
+            let entry = entries[i];
+            let check = EntryCheck {
+                self_res: entry.validate_self(did),
+                with_prev_res: prev
+                    .as_ref()
+                    .ok()
+                    .map(|prev| entry.validate_with_prev(prev.map(|prev| entries[prev]))),
+                prev,
+            };
+            (i, check)
+        })
+        .collect::<Vec<_>>();
+    for (i, check) in new_checks {
+        checks[i] = Some(check);
+    }
 
-                    // For non-genesis operations:
-                    if let Some(prev) = prev {
-                        let (active_child, nullified_children) = active_graph
-                            .entry(&prev.cid)
-                            .or_insert_with(|| (None, vec![]));
-
-                        // Verify the correctness of "nullified" operations and the current
-                        // active operation log using the rules around rotation keys and
-                        // recovery windows.
-                        if entry.nullified {
-                            // Either `prev` must be nullified, or `prev` must have an
-                            // active child operation within the recovery window from this
-                            // entry.
-                            if !prev.nullified {
-                                // Multiple operations can have the same `prev`; a child
-                                // can be nullified as long as it is not after the active
-                                // child.
-                                if active_child.is_some() {
-                                    errors.push(AuditError::EntryIncorrectlyNullified {
-                                        cid: entry.cid.clone(),
-                                    });
-                                } else {
-                                    nullified_children.push((entry, signer_authority));
-                                }
-                            }
-                        } else if prev.nullified {
-                            errors.push(AuditError::EntryIncorrectlyActive {
-                                cid: entry.cid.clone(),
-                            });
-                        } else if let Some((earlier_entry, earlier_signer_authority)) =
-                            &active_child
-                        {
-                            // An operation can't have two active children. Check which
-                            // one has higher authority.
-                            if entry.nullifies(
-                                signer_authority,
-                                earlier_entry,
-                                *earlier_signer_authority,
-                            ) {
-                                errors.push(AuditError::EntryIncorrectlyActive {
-                                    cid: earlier_entry.cid.clone(),
-                                });
+    checks
+        .into_iter()
+        .map(|check| check.expect("computed above"))
+        .collect()
+}
 
-                                // Set the correct (as of now) active child, so we can
-                                // perform the equivalent check with subsequent
-                                // operations if necessary.
-                                *active_child = Some((entry, signer_authority));
-                            } else {
-                                errors.push(AuditError::MultipleActiveChildren {
-                                    cid: entry.cid.clone(),
-                                    first: earlier_entry.cid.clone(),
-                                });
-                            }
-                        } else {
-                            let mut entry_incorrectly_active = false;
-
-                            for i in (0..nullified_children.len()).rev() {
-                                let (nullified_entry, nullified_signer_authority) =
-                                    nullified_children.get(i).expect("present");
-                                if entry.nullifies(
-                                    signer_authority,
-                                    nullified_entry,
-                                    *nullified_signer_authority,
-                                ) {
-                                    // We confirmed this was nullified correctly, so
-                                    // we don't need to check it anymore.
-                                    nullified_children.remove(i);
-                                } else {
-                                    entry_incorrectly_active |= true;
-                                }
-                            }
+/// The state of a validated audit log, which can be extended with new entries via
+/// [`AuditLog::validate_append`].
+#[derive(Clone, Debug)]
+struct ValidatedState {
+    did: Did,
+    profile: SpecProfile,
+    entries: Vec<LogEntry>,
+    checks: Vec<EntryCheck>,
+}
+
+/// The results of validating an entry on its own and against its previous operation.
+/// These never change once the entry is in the log.
+#[derive(Clone, Debug)]
+struct EntryCheck {
+    self_res: Result<(), Vec<AuditError>>,
+    /// The index of the entry's previous operation, if any.
+    prev: Result<Option<usize>, AuditError>,
+    /// The result of [`LogEntry::validate_with_prev`], if `prev` was located.
+    with_prev_res: Option<WithPrevResult>,
+}
 
-                            if entry_incorrectly_active {
-                                errors.push(AuditError::EntryIncorrectlyActive {
+/// The result of validating an entry against its previous operation, along with the
+/// authority of the rotation key that signed it (if any).
+type WithPrevResult = (Result<(), Vec<AuditError>>, Option<usize>);
+
+impl ValidatedState {
+    /// Returns the state for an empty audit log of the given DID.
+    fn new(did: Did, profile: SpecProfile) -> Self {
+        Self {
+            did,
+            profile,
+            entries: vec![],
+            checks: vec![],
+        }
+    }
+}
+
+/// Validates the graph of operations using the per-entry checks.
+fn validate_graph(
+    did: &Did,
+    profile: &SpecProfile,
+    entries: &[&LogEntry],
+    checks: &[EntryCheck],
+) -> Result<ValidatedLog, Vec<AuditError>> {
+    let mut errors = vec![];
+    let mut nullified_by = HashMap::new();
+
+    // For the genesis operation, validate the DID.
+    match entries.first() {
+        None => errors.push(AuditError::AuditLogEmpty),
+        Some(entry) => match entry.operation.derived_did() {
+            Some(derived) if &derived != did => {
+                errors.push(AuditError::GenesisOperationInvalidDid {
+                    expected: did.clone(),
+                    actual: derived,
+                })
+            }
+            Some(_) => (),
+            None => errors.push(AuditError::GenesisOperationNotCreate),
+        },
+    }
+
+    // Track the graph of operations.
+    type EntryWithAuthority<'a> = (&'a LogEntry, Option<usize>);
+    let mut active_graph: HashMap<&Cid, (Option<EntryWithAuthority>, Vec<EntryWithAuthority>)> =
+        HashMap::new();
+
+    for (i, (entry, check)) in entries.iter().zip(checks).enumerate() {
+        // Perform non-contextual validation.
+        if let Err(e) = &check.self_res {
+            errors.extend(e.iter().cloned());
+        }
+
+        match &check.prev {
+            // We could not locate the declared most-recent previous operation.
+            // We can't perform any more checks on this entry.
+            Err(e) => errors.push(e.clone()),
+
+            // Either this is a genesis operation, or we located its most-recent
+            // previous operation.
+            Ok(prev) => {
+                let prev = prev.map(|prev| &entries[prev]);
+                let (res, signer_authority) = check
+                    .with_prev_res
+                    .as_ref()
+                    .expect("computed for every located prev");
+                if let Err(e) = res {
+                    errors.extend(e.iter().cloned());
+                }
+                let signer_authority = *signer_authority;
+                // For non-genesis operations:
+                if let Some(prev) = prev {
+                    let (active_child, nullified_children) = active_graph
+                        .entry(&prev.cid)
+                        .or_insert_with(|| (None, vec![]));
+
+                    // Verify the correctness of "nullified" operations and the current
+                    // active operation log using the rules around rotation keys and
+                    // recovery windows.
+                    if entry.nullified {
+                        // Either `prev` must be nullified, or `prev` must have an
+                        // active child operation within the recovery window from this
+                        // entry.
+                        if !prev.nullified {
+                            // Multiple operations can have the same `prev`; a child
+                            // can be nullified as long as it is not after the active
+                            // child.
+                            if active_child.is_some() {
+                                errors.push(AuditError::EntryIncorrectlyNullified {
                                     cid: entry.cid.clone(),
                                 });
+                            } else {
+                                nullified_children.push((entry, signer_authority));
                             }
+                        } else {
+                            nullified_by.insert(
+                                &entry.cid,
+                                Nullification::PrevNullified {
+                                    prev: prev.cid.clone(),
+                                },
+                            );
+                        }
+                    } else if prev.nullified {
+                        errors.push(AuditError::EntryIncorrectlyActive {
+                            cid: entry.cid.clone(),
+                        });
+                    } else if let Some((earlier_entry, earlier_signer_authority)) = &active_child {
+                        // An operation can't have two active children. Check which
+                        // one has higher authority.
+                        if entry.nullifies(
+                            signer_authority,
+                            earlier_entry,
+                            *earlier_signer_authority,
+                            profile,
+                        ) {
+                            errors.push(AuditError::EntryIncorrectlyActive {
+                                cid: earlier_entry.cid.clone(),
+                            });
 
-                            // Mark this as the active child even if it is incorrectly
-                            // active, so that we can detect multiple active children,
-                            // and out-of-order nullified children.
+                            // Set the correct (as of now) active child, so we can
+                            // perform the equivalent check with subsequent
+                            // operations if necessary.
                             *active_child = Some((entry, signer_authority));
-                        }
-                    } else {
-                        if i != 0 {
-                            // Genesis operations can only occur once, at the start.
-                            errors.push(AuditError::NonGenesisCreate {
+                        } else {
+                            errors.push(AuditError::MultipleActiveChildren {
                                 cid: entry.cid.clone(),
+                                first: earlier_entry.cid.clone(),
                             });
                         }
-                        if entry.nullified {
-                            // Genesis operations cannot be nullified.
-                            errors.push(AuditError::EntryIncorrectlyNullified {
+                    } else {
+                        let mut entry_incorrectly_active = false;
+
+                        for i in (0..nullified_children.len()).rev() {
+                            let (nullified_entry, nullified_signer_authority) =
+                                nullified_children.get(i).expect("present");
+                            if entry.nullifies(
+                                signer_authority,
+                                nullified_entry,
+                                *nullified_signer_authority,
+                                profile,
+                            ) {
+                                // We confirmed this was nullified correctly, so
+                                // we don't need to check it anymore.
+                                nullified_by.insert(
+                                    &nullified_entry.cid,
+                                    Nullification::Overridden {
+                                        by: entry.cid.clone(),
+                                        authority: signer_authority,
+                                    },
+                                );
+                                nullified_children.remove(i);
+                            } else {
+                                entry_incorrectly_active |= true;
+                            }
+                        }
+
+                        if entry_incorrectly_active {
+                            errors.push(AuditError::EntryIncorrectlyActive {
                                 cid: entry.cid.clone(),
                             });
                         }
+
+                        // Mark this as the active child even if it is incorrectly
+                        // active, so that we can detect multiple active children,
+                        // and out-of-order nullified children.
+                        *active_child = Some((entry, signer_authority));
+                    }
+                } else {
+                    if i != 0 {
+                        // Genesis operations can only occur once, at the start.
+                        errors.push(AuditError::NonGenesisCreate {
+                            cid: entry.cid.clone(),
+                        });
+                    }
+                    if entry.nullified {
+                        // Genesis operations cannot be nullified.
+                        errors.push(AuditError::EntryIncorrectlyNullified {
+                            cid: entry.cid.clone(),
+                        });
                     }
                 }
             }
         }
+    }
 
-        // Any nullified children that remain in the active graph were incorrectly
-        // nullified.
-        for (_, (_, nullified_children)) in active_graph {
-            for (nullified_entry, _) in nullified_children {
-                errors.push(AuditError::EntryIncorrectlyNullified {
-                    cid: nullified_entry.cid.clone(),
-                });
+    // Any nullified children that remain in the active graph were incorrectly
+    // nullified.
+    for (_, (_, nullified_children)) in active_graph {
+        for (nullified_entry, _) in nullified_children {
+            errors.push(AuditError::EntryIncorrectlyNullified {
+                cid: nullified_entry.cid.clone(),
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        // Everything is okay!
+        let mut key_sets = KeySets::default();
+        Ok(ValidatedLog {
+            entries: entries
+                .iter()
+                .zip(checks)
+                .map(|(entry, check)| ValidatedEntry {
+                    cid: entry.cid.clone(),
+                    prev: match check.prev {
+                        Ok(Some(prev)) => Some(entries[prev].cid.clone()),
+                        _ => None,
+                    },
+                    created_at: entry.created_at.clone(),
+                    authorized_keys: key_sets.intern(match check.prev {
+                        Ok(Some(prev)) => entries[prev].rotation_keys(),
+                        _ => entry.rotation_keys(),
+                    }),
+                    signer: check.with_prev_res.as_ref().and_then(|(_, signer)| *signer),
+                    state: entry.state(),
+                    nullified: nullified_by.remove(&entry.cid),
+                })
+                .collect(),
+        })
+    } else {
+        Err(errors)
+    }
+}
+
+/// The facts computed while validating an audit log.
+#[derive(Debug)]
+pub(crate) struct ValidatedLog {
+    /// The entries of the audit log, in order.
+    pub(crate) entries: Vec<ValidatedEntry>,
+}
+
+impl ValidatedLog {
+    /// Returns the active operations that an operation signed by `key` at `now` would
+    /// nullify if it followed their `prev`, oldest first.
+    ///
+    /// Nullifying an operation also nullifies every operation after it.
+    pub(crate) fn nullifiable_by(
+        &self,
+        key: &str,
+        profile: &SpecProfile,
+        now: &DateTime<FixedOffset>,
+    ) -> Vec<&ValidatedEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.nullified.is_none() && entry.prev.is_some())
+            .filter(|entry| {
+                let authority = entry.authorized_keys.iter().position(|k| k == key);
+                authority.is_some()
+                    && overrides(
+                        now,
+                        authority,
+                        entry.created_at.as_ref(),
+                        entry.signer,
+                        profile,
+                    )
+            })
+            .collect()
+    }
+}
+
+/// The facts computed for a single entry of a valid audit log.
+#[derive(Debug)]
+pub(crate) struct ValidatedEntry {
+    pub(crate) cid: Cid,
+    /// The operation this one followed, or `None` for the genesis operation.
+    pub(crate) prev: Option<Cid>,
+    pub(crate) created_at: Datetime,
+    /// The rotation keys that were authorized to sign the operation, in priority order.
+    pub(crate) authorized_keys: Arc<[String]>,
+    /// The index into `authorized_keys` of the key that signed the operation.
+    pub(crate) signer: Option<usize>,
+    /// The DID's state after this operation, or `None` if it deactivated the DID.
+    pub(crate) state: Option<PlcData>,
+    /// Why this operation was nullified, if it was.
+    pub(crate) nullified: Option<Nullification>,
+}
+
+/// The reason an operation was nullified.
+#[derive(Debug)]
+pub(crate) enum Nullification {
+    /// A conflicting operation, signed by a higher-authority rotation key within the
+    /// recovery window, took its place.
+    Overridden { by: Cid, authority: Option<usize> },
+    /// The operation it followed was nullified.
+    PrevNullified { prev: Cid },
+}
+
+/// The signer of an active operation.
+#[derive(Debug)]
+pub(crate) struct Authorship {
+    pub(crate) created_at: Datetime,
+    /// The rotation keys that were authorized to sign the operation, in priority order.
+    pub(crate) authorized_keys: Arc<[String]>,
+    /// The index into `authorized_keys` of the key that signed the operation, or `None`
+    /// if no authorized key did.
+    pub(crate) signer: Option<usize>,
+}
+
+/// Indexes entries by CID. If multiple entries have the same CID, the first is used.
+fn cid_index<'a>(entries: impl IntoIterator<Item = &'a LogEntry>) -> HashMap<&'a Cid, usize> {
+    let mut index = HashMap::new();
+    for (i, entry) in entries.into_iter().enumerate() {
+        index.entry(&entry.cid).or_insert(i);
+    }
+    index
+}
+
+/// Adds `entries` to `seen`, an append-only record of the entries seen for a DID,
+/// returning whether anything changed.
+///
+/// New entries are appended, and seen entries can become nullified; nothing else
+/// changes, so that rewritten history is still detected against `seen`.
+pub(super) fn merge_seen(seen: &mut Vec<LogEntry>, entries: &[LogEntry]) -> bool {
+    let index = cid_index(seen.iter())
+        .into_iter()
+        .map(|(cid, i)| (cid.clone(), i))
+        .collect::<HashMap<_, _>>();
+
+    let mut changed = false;
+    for entry in entries {
+        match index.get(&entry.cid) {
+            None => {
+                seen.push(entry.clone());
+                changed = true;
+            }
+            Some(&i) if entry.nullified && !seen[i].nullified && seen[i].same_operation(entry) => {
+                seen[i].nullified = true;
+                changed = true;
+            }
+            Some(_) => (),
+        }
+    }
+    changed
+}
+
+/// Deduplicates the sets of rotation keys authorized to sign each operation. These
+/// rarely change between operations, so long logs would otherwise hold many copies.
+///
+/// Sets are bucketed by the hash of their keys, so that we can look one up from the
+/// borrowed keys and only allocate a new set when it hasn't been seen before.
+#[derive(Default)]
+struct KeySets(HashMap<u64, Vec<Arc<[String]>>>);
+
+impl KeySets {
+    fn intern<'a>(&mut self, keys: impl Iterator<Item = &'a str> + Clone) -> Arc<[String]> {
+        let mut hasher = DefaultHasher::new();
+        for key in keys.clone() {
+            key.hash(&mut hasher);
+        }
+
+        let bucket = self.0.entry(hasher.finish()).or_default();
+        match bucket
+            .iter()
+            .find(|interned| interned.iter().map(String::as_str).eq(keys.clone()))
+        {
+            Some(interned) => interned.clone(),
+            None => {
+                let keys = keys.map(String::from).collect::<Arc<[String]>>();
+                bucket.push(keys.clone());
+                keys
             }
         }
+    }
+}
 
+impl LogEntry {
+    /// Returns `true` if `other` is the same operation as this entry, ignoring whether
+    /// either has been nullified.
+    fn same_operation(&self, other: &Self) -> bool {
+        self.cid == other.cid
+            && self.did == other.did
+            && self.created_at == other.created_at
+            && self.operation.signed_bytes() == other.operation.signed_bytes()
+    }
+
+    /// Returns `true` if this entry's operation is of a type we don't know about.
+    fn is_unknown(&self) -> bool {
+        self.unknown_type().is_some()
+    }
+
+    /// Returns the type of this entry's operation, if it is one we don't know about.
+    pub(crate) fn unknown_type(&self) -> Option<&str> {
+        self.operation.content.unknown_type()
+    }
+
+    /// Verifies this entry in isolation: that its CID matches its canonical encoding,
+    /// that a genesis operation creates its DID, and that it was signed by a rotation
+    /// key authorized by `prev`, the entry for the operation it follows.
+    ///
+    /// Operations of unknown types can't be verified, and must not be passed in.
+    pub(crate) fn verify(&self, prev: Option<&LogEntry>) -> Result<Authorship, Vec<AuditError>> {
+        assert!(!self.is_unknown());
+        let mut errors = vec![];
+
+        if let Err(e) = self.validate_self(&self.did) {
+            errors.extend(e);
+        }
+        let prev = match (self.operation.prev(), prev) {
+            (None, _) => {
+                match self.operation.derived_did() {
+                    Some(did) if did != self.did => {
+                        errors.push(AuditError::GenesisOperationInvalidDid {
+                            expected: self.did.clone(),
+                            actual: did,
+                        })
+                    }
+                    _ => (),
+                }
+                None
+            }
+            (Some(cid), Some(prev)) if &prev.cid == cid && !prev.is_unknown() => {
+                // The previous entry's CID is what links it to this one, so check it too.
+                if let Err(e) = prev.validate_self(&self.did) {
+                    errors.extend(e);
+                }
+                Some(prev)
+            }
+            (Some(cid), _) => {
+                errors.push(AuditError::PrevMissing { prev: cid.clone() });
+                return Err(errors);
+            }
+        };
+
+        let (res, signer) = self.validate_with_prev(prev);
+        if let Err(e) = res {
+            errors.extend(e);
+        }
         if errors.is_empty() {
-            // Everything is okay!
-            Ok(())
+            Ok(Authorship {
+                created_at: self.created_at.clone(),
+                authorized_keys: prev
+                    .unwrap_or(self)
+                    .rotation_keys()
+                    .map(String::from)
+                    .collect(),
+                signer,
+            })
         } else {
             Err(errors)
         }
     }
-}
 
-impl LogEntry {
+    /// Returns the state produced by this entry, or `None` if it deactivates the DID
+    /// (or its effect is unknown).
+    pub(super) fn state(&self) -> Option<PlcData> {
+        match &self.operation.content {
+            Operation::Change(op) => Some(op.data.clone()),
+            Operation::LegacyCreate(op) => Some(op.clone().into_plc_data()),
+            Operation::Tombstone(_) | Operation::Unknown(_) => None,
+        }
+    }
+
+    /// Returns the rotation keys in the state produced by this entry.
+    fn rotation_keys(&self) -> impl Iterator<Item = &str> + Clone {
+        let (change, legacy) = match &self.operation.content {
+            Operation::Change(op) => (Some(op), None),
+            Operation::LegacyCreate(op) => (None, Some(op)),
+            Operation::Tombstone(_) | Operation::Unknown(_) => (None, None),
+        };
+        change
+            .into_iter()
+            .flat_map(|op| op.rotation_keys())
+            .chain(legacy.into_iter().flat_map(|op| op.rotation_keys()))
+    }
+
     fn validate_self(&self, did: &Did) -> Result<(), Vec<AuditError>> {
         let mut errors = vec![];
 
@@ -246,10 +935,7 @@ impl LogEntry {
         }
     }
 
-    fn validate_with_prev(
-        &self,
-        prev: Option<&Self>,
-    ) -> (Result<(), Vec<AuditError>>, Option<usize>) {
+    fn validate_with_prev(&self, prev: Option<&Self>) -> WithPrevResult {
         let mut errors = vec![];
 
         // Validate signatures.
@@ -278,6 +964,7 @@ impl LogEntry {
             }
         };
 
+        #[allow(clippy::result_large_err)]
         let check_signed = |signed| match signed {
             Some((index, _)) => Ok(index),
             None => Err(AuditError::TrustViolation {
@@ -304,6 +991,9 @@ impl LogEntry {
                         cid: self.cid.clone(),
                         prev: prev.cid.clone(),
                     }),
+                    Operation::Unknown(_) => {
+                        unreachable!("unknown operations are never validated")
+                    }
                 }
             }
             _ => unreachable!("see definition of prev above"),
@@ -345,30 +1035,71 @@ impl LogEntry {
         signer_authority: Option<usize>,
         earlier_entry: &LogEntry,
         earlier_signer_authority: Option<usize>,
+        profile: &SpecProfile,
     ) -> bool {
-        let submitted_in_time =
-            *self.created_at.as_ref() <= *earlier_entry.created_at.as_ref() + RECOVERY_WINDOW;
+        overrides(
+            self.created_at.as_ref(),
+            signer_authority,
+            earlier_entry.created_at.as_ref(),
+            earlier_signer_authority,
+            profile,
+        )
+    }
+}
 
-        let current_is_higher_authority =
-            match (signer_authority.as_ref(), earlier_signer_authority.as_ref()) {
-                (Some(active_authority), Some(earlier_authority)) => {
-                    active_authority < earlier_authority
-                }
-                // If we already raised a trust violation error for
-                // the active entry, ensure we also raise an error
-                // that it is incorrectly active.
-                (None, _) => false,
-                // If we only raised a trust violation error for the
-                // nullified entry, we do not know whether the active
-                // entry is incorrectly active (by this rule).
-                (Some(_), None) => true,
-            };
+/// Returns `true` if an operation created at `created_at` and signed by the rotation
+/// key at `signer_authority` overrides a conflicting earlier operation.
+fn overrides(
+    created_at: &DateTime<FixedOffset>,
+    signer_authority: Option<usize>,
+    earlier_created_at: &DateTime<FixedOffset>,
+    earlier_signer_authority: Option<usize>,
+    profile: &SpecProfile,
+) -> bool {
+    let submitted_in_time = *created_at <= *earlier_created_at + profile.recovery_window;
 
-        submitted_in_time && current_is_higher_authority
+    let current_is_higher_authority =
+        match (signer_authority.as_ref(), earlier_signer_authority.as_ref()) {
+            (Some(active_authority), Some(earlier_authority)) => {
+                active_authority < earlier_authority
+            }
+            // If we already raised a trust violation error for
+            // the active entry, ensure we also raise an error
+            // that it is incorrectly active.
+            (None, _) => false,
+            // If we only raised a trust violation error for the
+            // nullified entry, we do not know whether the active
+            // entry is incorrectly active (by this rule).
+            (Some(_), None) => true,
+        };
+
+    submitted_in_time && current_is_higher_authority
+}
+
+/// A change to a DID's history that no valid operation can explain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum HistoryChange {
+    /// A previously seen operation is no longer in the audit log.
+    Removed(Cid),
+    /// A previously seen operation has different contents or creation time.
+    Rewritten(Cid),
+    /// A previously nullified operation is no longer nullified.
+    Restored(Cid),
+}
+
+impl fmt::Display for HistoryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryChange::Removed(cid) => write!(f, "Entry {} was removed", cid.as_ref()),
+            HistoryChange::Rewritten(cid) => write!(f, "Entry {} was rewritten", cid.as_ref()),
+            HistoryChange::Restored(cid) => {
+                write!(f, "Entry {} is no longer nullified", cid.as_ref())
+            }
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum AuditError {
     AuditLogEmpty,
     EntryCidInvalid { cid: Cid, actual: Cid },
@@ -387,6 +1118,30 @@ pub(crate) enum AuditError {
     TrustViolation { cid: Cid },
 }
 
+impl AuditError {
+    /// Returns the CID of the entry at fault, or `None` if the error concerns the log as
+    /// a whole (or an entry that is missing from it).
+    pub(crate) fn cid(&self) -> Option<&Cid> {
+        match self {
+            AuditError::EntryCidInvalid { cid, .. }
+            | AuditError::EntryCreatedBeforePrev { cid, .. }
+            | AuditError::EntryDidMismatch { cid }
+            | AuditError::EntryIncorrectlyActive { cid }
+            | AuditError::EntryIncorrectlyNullified { cid }
+            | AuditError::InvalidSignatureEncoding { cid }
+            | AuditError::MultipleActiveChildren { cid, .. }
+            | AuditError::NonGenesisCreate { cid }
+            | AuditError::OperationAfterDeactivation { cid, .. }
+            | AuditError::PrevReferencesFuture { cid, .. }
+            | AuditError::TrustViolation { cid } => Some(cid),
+            AuditError::AuditLogEmpty
+            | AuditError::GenesisOperationInvalidDid { .. }
+            | AuditError::GenesisOperationNotCreate
+            | AuditError::PrevMissing { .. } => None,
+        }
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 impl fmt::Display for AuditError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1,7 +1,13 @@
+use std::sync::Arc;
+
 use atrium_api::types::string::Cid;
 use chrono::Duration;
 
-use crate::remote::plc::{audit::AuditError, testing::TestLog};
+use crate::remote::plc::{
+    audit::{merge_seen, AuditError, HistoryChange, ValidatedState},
+    testing::TestLog,
+    AuditLog, ExportGroups, Nullification, SpecProfile,
+};
 
 #[test]
 fn valid_examples() {
@@ -519,6 +525,36 @@ fn nullified_late() {
     );
 }
 
+#[test]
+fn nullified_with_custom_recovery_window() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| {
+            update
+                .change_pds("pds.example.com")
+                .with_prev_op(0)
+                .signed_with_key(0)
+                .created_after(1, Duration::seconds(5 * 60 + 1))
+        });
+
+    let profile = SpecProfile {
+        recovery_window: Duration::minutes(5),
+    };
+
+    assert_eq!(log.audit_log().validate(), Ok(()));
+    assert_eq!(
+        log.audit_log().validate_with_profile(&profile),
+        Err(vec![
+            AuditError::EntryIncorrectlyActive {
+                cid: log.cid_for(2),
+            },
+            AuditError::EntryIncorrectlyNullified {
+                cid: log.cid_for(1),
+            },
+        ])
+    );
+}
+
 #[test]
 fn valid_tombstone() {
     let log = TestLog::with_genesis()
@@ -716,3 +752,394 @@ fn op_after_tombstone() {
         }]),
     );
 }
+
+#[test]
+fn authorship() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").signed_with_key(1))
+        .apply_update(|update| update.change_pds("pds.example.com").nullified())
+        .apply_update(|update| update.change_pds("pds.example.com").invalid_sig());
+
+    let authorship = log.audit_log().authorship();
+    assert_eq!(
+        authorship
+            .iter()
+            .map(|authorship| authorship.signer)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(1), None],
+    );
+    assert!(authorship
+        .iter()
+        .all(|authorship| !authorship.authorized_keys.is_empty()));
+
+    // None of the operations changed the rotation keys, so they share one key set.
+    assert!(authorship
+        .windows(2)
+        .all(|w| Arc::ptr_eq(&w[0].authorized_keys, &w[1].authorized_keys)));
+}
+
+#[test]
+fn validate_append() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com"))
+        .apply_update(|update| update.change_pds("pds.example.com"));
+    let entries = log.audit_log().entries().to_vec();
+    let state = ValidatedState::new(log.did(), SpecProfile::default());
+
+    // Validating in chunks gives the same result as validating all at once.
+    let (state, res) = AuditLog::validate_append(state, entries[..1].to_vec());
+    assert_eq!(res.map(|_| ()), Ok(()));
+    let (state, res) = AuditLog::validate_append(state, entries[1..].to_vec());
+    assert_eq!(res.map(|_| ()), Ok(()));
+
+    // Passing previously-validated entries again doesn't duplicate them.
+    let (_, res) = AuditLog::validate_append(state, entries);
+    assert_eq!(res.map(|_| ()), Ok(()));
+}
+
+#[test]
+fn validate_append_with_nullification() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| {
+            update
+                .change_pds("pds.example.com")
+                .with_prev_op(0)
+                .signed_with_key(0)
+        });
+    let entries = log.audit_log().entries().to_vec();
+
+    // Before the recovery operation, the first update was active.
+    let mut before = entries[..2].to_vec();
+    before[1].nullified = false;
+    let (state, res) = AuditLog::validate_append(
+        ValidatedState::new(log.did(), SpecProfile::default()),
+        before,
+    );
+    assert_eq!(res.map(|_| ()), Ok(()));
+
+    // If only the recovery operation is appended, the earlier update still appears
+    // to be active.
+    let (_, res) = AuditLog::validate_append(state.clone(), entries[2..].to_vec());
+    assert_eq!(
+        res.map(|_| ()),
+        Err(vec![AuditError::EntryIncorrectlyActive {
+            cid: log.cid_for(1),
+        }]),
+    );
+
+    // Passing the refetched log picks up the nullification.
+    let (_, res) = AuditLog::validate_append(state, entries);
+    assert_eq!(res.map(|_| ()), Ok(()));
+}
+
+#[test]
+fn validate_next() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| {
+            update
+                .change_pds("pds.example.com")
+                .with_prev_op(0)
+                .signed_with_key(0)
+        });
+    let entries = log.audit_log().entries().to_vec();
+    let before = |n: usize| {
+        let mut entries = entries[..n].to_vec();
+        for entry in &mut entries {
+            entry.nullified = false;
+        }
+        AuditLog::new(log.did(), entries)
+    };
+    let next = |i: usize| (&entries[i].operation, entries[i].created_at.clone());
+
+    // A genesis operation must create the DID.
+    let (op, created_at) = next(0);
+    assert_eq!(
+        AuditLog::empty(log.did()).validate_next(op, created_at.clone()),
+        Ok(())
+    );
+    assert!(AuditLog::empty(TestLog::with_genesis().did())
+        .validate_next(op, created_at)
+        .is_err());
+
+    // An update follows the most recent operation.
+    let (op, created_at) = next(1);
+    assert_eq!(before(1).validate_next(op, created_at), Ok(()));
+
+    // A recovery operation nullifies the operations after its `prev`.
+    let (op, created_at) = next(2);
+    assert_eq!(before(2).validate_next(op, created_at), Ok(()));
+
+    // It can't nullify an operation signed by a key of the same authority.
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").signed_with_key(0))
+        .apply_update(|update| {
+            update
+                .change_pds("pds.example.com")
+                .with_prev_op(0)
+                .signed_with_key(0)
+        });
+    let mut entries = log.audit_log().entries().to_vec();
+    let op = entries.pop().unwrap();
+    assert_eq!(
+        AuditLog::new(log.did(), entries).validate_next(&op.operation, op.created_at),
+        Err(vec![
+            AuditError::EntryIncorrectlyActive { cid: op.cid },
+            AuditError::EntryIncorrectlyNullified {
+                cid: log.cid_for(1),
+            },
+        ]),
+    );
+}
+
+#[test]
+fn validated_facts() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| {
+            update
+                .change_pds("pds.example.com")
+                .with_prev_op(0)
+                .signed_with_key(0)
+        })
+        .apply_tombstone(|tombstone| tombstone);
+
+    let validated = log.audit_log().validated().unwrap();
+    let entries = &validated.entries;
+    assert_eq!(entries.len(), 4);
+
+    assert_eq!(
+        entries.iter().map(|entry| entry.signer).collect::<Vec<_>>(),
+        vec![Some(1), Some(1), Some(0), Some(1)],
+    );
+    assert!(matches!(
+        &entries[1].nullified,
+        Some(Nullification::Overridden { by, authority: Some(0) }) if by == &log.cid_for(2),
+    ));
+    assert!(entries[2].nullified.is_none());
+    assert!(entries[2].state.is_some());
+    assert!(entries[3].state.is_none());
+}
+
+#[test]
+fn nullifiable_operations() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com"))
+        .apply_update(|update| update.change_pds("pds.example.com"));
+    let validated = log.audit_log().validated().unwrap();
+    let entries = &validated.entries;
+    assert_eq!(entries[1].prev, Some(log.cid_for(0)));
+    let (key0, key1) = (
+        &entries[1].authorized_keys[0],
+        &entries[1].authorized_keys[1],
+    );
+    let profile = SpecProfile::default();
+
+    let cids = |now| {
+        validated
+            .nullifiable_by(key0, &profile, &now)
+            .into_iter()
+            .map(|entry| entry.cid.clone())
+            .collect::<Vec<_>>()
+    };
+    let created = *entries[1].created_at.as_ref();
+    assert_eq!(
+        cids(created + Duration::hours(1)),
+        vec![log.cid_for(1), log.cid_for(2)],
+    );
+    assert_eq!(cids(created + Duration::hours(73)), vec![]);
+
+    // The key that signed the operations can't override them.
+    assert!(validated
+        .nullifiable_by(key1, &profile, &(created + Duration::hours(1)))
+        .is_empty());
+}
+
+#[test]
+fn single_entry_verification() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").signed_with_key(0))
+        .apply_update(|update| update.change_pds("pds.example.com").invalid_sig());
+    let audit_log = log.audit_log();
+    let entries = audit_log.entries();
+
+    assert_eq!(entries[0].verify(None).unwrap().signer, Some(1));
+    let authorship = entries[1].verify(Some(&entries[0])).unwrap();
+    assert_eq!(authorship.signer, Some(0));
+    assert_eq!(authorship.authorized_keys.len(), 2);
+
+    assert_eq!(
+        entries[1].verify(None).unwrap_err(),
+        vec![AuditError::PrevMissing {
+            prev: log.cid_for(0),
+        }],
+    );
+    assert_eq!(
+        entries[1].verify(Some(&entries[1])).unwrap_err(),
+        vec![AuditError::PrevMissing {
+            prev: log.cid_for(0),
+        }],
+    );
+    assert_eq!(
+        entries[2].verify(Some(&entries[1])).unwrap_err(),
+        vec![AuditError::TrustViolation {
+            cid: log.cid_for(2),
+        }],
+    );
+}
+
+#[test]
+fn history_changes() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| update.change_pds("pds.example.com").with_prev_op(0));
+    let previous = log.audit_log();
+
+    // New operations are not history changes.
+    let mut log = log.apply_update(|update| update.change_handle("carol.example.com"));
+    assert_eq!(log.audit_log().history_changes(&previous), vec![]);
+
+    let mut entries = previous.entries().to_vec();
+    entries[1].nullified = false;
+    entries[2].created_at = entries[1].created_at.clone();
+    assert_eq!(
+        AuditLog::new(log.did(), entries).history_changes(&previous),
+        vec![
+            HistoryChange::Restored(log.cid_for(1)),
+            HistoryChange::Rewritten(log.cid_for(2)),
+        ],
+    );
+
+    let removed = log.cid_for(2);
+    log.remove(2);
+    assert_eq!(
+        log.audit_log().history_changes(&previous),
+        vec![HistoryChange::Removed(removed)],
+    );
+}
+
+#[test]
+fn divergence() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com").nullified())
+        .apply_update(|update| update.change_pds("pds.example.com").with_prev_op(0));
+    let previous = log.audit_log();
+
+    let mut log = log.apply_update(|update| update.change_handle("carol.example.com"));
+    log.remove(2);
+
+    assert_eq!(previous.divergence(&previous), None);
+    assert_eq!(log.audit_log().divergence(&previous), Some(2));
+}
+
+#[test]
+fn seen_history_is_append_only() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com"))
+        .apply_update(|update| update.change_pds("pds.example.com"));
+    let mut seen = vec![];
+    assert!(merge_seen(&mut seen, log.audit_log().entries()));
+    assert!(!merge_seen(&mut seen, log.audit_log().entries()));
+    let previous = AuditLog::new(log.did(), seen.clone());
+
+    // A rewritten log doesn't change what we have seen.
+    let mut rewritten = log.audit_log().entries().to_vec();
+    rewritten.remove(2);
+    rewritten[1].created_at = rewritten[0].created_at.clone();
+    assert!(!merge_seen(&mut seen, &rewritten));
+    assert_eq!(
+        AuditLog::new(log.did(), rewritten).history_changes(&previous),
+        vec![
+            HistoryChange::Rewritten(log.cid_for(1)),
+            HistoryChange::Removed(log.cid_for(2)),
+        ],
+    );
+
+    // New operations are appended, and seen operations can become nullified.
+    let mut entries = log
+        .apply_update(|update| update.change_handle("carol.example.com"))
+        .audit_log()
+        .entries()
+        .to_vec();
+    entries[2].nullified = true;
+    assert!(merge_seen(&mut seen, &entries));
+    assert_eq!(seen.len(), 4);
+    assert!(seen[2].nullified);
+
+    // But never un-nullified.
+    entries[2].nullified = false;
+    assert!(!merge_seen(&mut seen, &entries));
+    assert!(seen[2].nullified);
+}
+
+#[test]
+fn unknown_operations_are_skipped() {
+    let log =
+        TestLog::with_genesis().apply_update(|update| update.change_handle("bob.example.com"));
+    let mut entries = log.audit_log().entries().to_vec();
+
+    let mut unknown = entries[1].clone();
+    unknown.operation = serde_json::from_value(serde_json::json!({
+        "type": "plc_future_op",
+        "prev": log.cid_for(1),
+        "sig": "c2lnbmF0dXJl",
+    }))
+    .unwrap();
+    unknown.cid = unknown.operation.cid();
+    entries.push(unknown.clone());
+
+    let audit_log = AuditLog::new(log.did(), entries);
+    assert_eq!(audit_log.validate(), Ok(()));
+    assert_eq!(audit_log.authorship().len(), 2);
+    assert_eq!(
+        audit_log.unknown_operations(),
+        vec![(&unknown.cid, "plc_future_op")],
+    );
+}
+
+#[test]
+fn export_groups() {
+    let alice = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com"))
+        .apply_update(|update| update.change_pds("pds.example.com"));
+    let bob = TestLog::with_genesis().apply_update(|update| update.change_handle("carol.test"));
+    let alice_entries = alice.audit_log().entries().to_vec();
+    let bob_entries = bob.audit_log().entries().to_vec();
+
+    let mut groups = ExportGroups::new(3);
+    assert!(groups.push(alice_entries[0].clone()).is_none());
+    assert!(groups.push(bob_entries[0].clone()).is_none());
+    assert!(groups.push(alice_entries[1].clone()).is_none());
+
+    // The buffer is full, so the oldest fragment is returned.
+    let evicted = groups.push(bob_entries[1].clone()).unwrap();
+    assert_eq!(evicted.did(), &alice.did());
+    assert_eq!(evicted.entries().len(), 2);
+    assert!(evicted.starts_with_genesis());
+
+    // Later entries start a new fragment.
+    assert!(groups.push(alice_entries[2].clone()).is_none());
+    let rest = groups.finish();
+    assert_eq!(rest.len(), 2);
+    assert_eq!(rest[0].did(), &bob.did());
+    assert_eq!(rest[0].entries().len(), 2);
+    assert_eq!(rest[1].did(), &alice.did());
+    assert!(!rest[1].starts_with_genesis());
+}
+
+#[test]
+fn digest() {
+    let log = TestLog::with_genesis()
+        .apply_update(|update| update.change_handle("bob.example.com"))
+        .apply_update(|update| update.change_pds("pds.example.com"));
+    let audit_log = log.audit_log();
+    assert_eq!(audit_log.digest(), log.audit_log().digest());
+
+    // Nullifying an entry doesn't change the head, but does change the digest.
+    let mut entries = audit_log.entries().to_vec();
+    entries[1].nullified = true;
+    let nullified = AuditLog::new(log.did(), entries);
+    assert_eq!(nullified.head(), audit_log.head());
+    assert_ne!(nullified.digest(), audit_log.digest());
+}
@@ -0,0 +1,124 @@
+//! An in-memory PLC directory, for testing command logic without the network.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{DateTime, FixedOffset, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
+
+use super::{testing::TestLog, AuditLog, Directory, LogEntry, Operation, SignedOperation};
+use crate::{data::State, error::Error};
+
+/// The operations submitted to a [`MockDirectory`], in order.
+pub(crate) type Submissions = Arc<Mutex<Vec<(Did, SignedOperation)>>>;
+
+#[derive(Default)]
+pub(crate) struct MockDirectory {
+    logs: HashMap<Did, Vec<LogEntry>>,
+    submitted: Submissions,
+}
+
+impl MockDirectory {
+    /// Adds the DID described by `log` to the directory.
+    pub(crate) fn with_log(mut self, log: &TestLog) -> Self {
+        self.logs
+            .insert(log.did(), log.audit_log().entries().to_vec());
+        self
+    }
+
+    /// Returns a handle to the operations submitted to the directory, which remains
+    /// usable once the directory has been moved into an `AppContext`.
+    pub(crate) fn submissions(&self) -> Submissions {
+        self.submitted.clone()
+    }
+
+    /// Returns the audit log entries for `did`.
+    ///
+    /// The real directory returns 404 for unknown DIDs, which we can't construct a
+    /// `reqwest::Error` for, so unknown DIDs have empty logs instead.
+    fn entries(&self, did: &Did) -> &[LogEntry] {
+        self.logs.get(did).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl Directory for MockDirectory {
+    async fn health(&self) -> Result<Option<DateTime<FixedOffset>>, Error> {
+        Ok(Some(Utc::now().fixed_offset()))
+    }
+
+    async fn resolve(&self, did: &Did) -> Result<State, Error> {
+        let entry = self
+            .entries(did)
+            .iter()
+            .rev()
+            .find(|entry| !entry.nullified)
+            .ok_or(Error::PlcDirectoryReturnedInvalidDidDocument)?;
+        if matches!(entry.operation.content, Operation::Tombstone(_)) {
+            return Err(Error::DidTombstoned(did.as_str().into()));
+        }
+        let data = entry
+            .state()
+            .ok_or(Error::PlcDirectoryReturnedInvalidDidDocument)?;
+
+        let mut state: serde_json::Value = serde_json::to_value(data).expect("can serialize");
+        state["did"] = did.as_str().into();
+        serde_json::from_value(state).map_err(|_| Error::PlcDirectoryReturnedInvalidDidDocument)
+    }
+
+    /// Returns a minimal DID document; enough to tell DIDs apart.
+    #[cfg(feature = "server")]
+    async fn did_document(&self, did: &Did) -> Result<serde_json::Value, Error> {
+        let state = self.resolve(did).await?;
+        Ok(serde_json::json!({
+            "id": did.as_str(),
+            "alsoKnownAs": state.inner_data().also_known_as,
+        }))
+    }
+
+    async fn genesis_op(&self, did: &Did) -> Result<SignedOperation, Error> {
+        self.entries(did)
+            .first()
+            .map(|entry| entry.operation.clone())
+            .ok_or(Error::PlcDirectoryReturnedInvalidOperationLog)
+    }
+
+    async fn audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        self.fetch_audit_log(did).await
+    }
+
+    async fn fetch_audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        self.download_audit_log(did).await
+    }
+
+    async fn download_audit_log(&self, did: &Did) -> Result<AuditLog, Error> {
+        Ok(AuditLog::new(did.clone(), self.entries(did).to_vec()))
+    }
+
+    fn export_stream(&self, after: Option<Datetime>) -> BoxStream<'_, Result<LogEntry, Error>> {
+        let mut entries = self
+            .logs
+            .values()
+            .flatten()
+            .filter(|entry| {
+                after
+                    .as_ref()
+                    .map_or(true, |after| entry.created_at > *after)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        stream::iter(entries.into_iter().map(Ok)).boxed()
+    }
+
+    /// Accepts every operation, recording it without adding it to the log.
+    async fn submit(&self, did: &Did, op: &SignedOperation) -> Result<(), Error> {
+        self.submitted
+            .lock()
+            .expect("not poisoned")
+            .push((did.clone(), op.clone()));
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Probes of a directory's health, for telling directory problems apart from local
+//! ones.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use der::{
+    asn1::{GeneralizedTime, UtcTime},
+    Decode, Reader, SliceReader, Tag, TagNumber,
+};
+use reqwest::{tls::TlsInfo, Client};
+use serde::Serialize;
+
+use super::LogEntry;
+use crate::error::Error;
+
+/// How far back to look in the export for recent operations.
+const EXPORT_WINDOW: TimeDelta = TimeDelta::minutes(1);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DirectoryStatus {
+    pub(crate) url: String,
+    pub(crate) checks: Vec<Check>,
+    /// The creation time of the newest operation in the export, if any were created
+    /// recently.
+    pub(crate) latest_export: Option<Datetime>,
+    /// When the directory's TLS certificate expires.
+    pub(crate) tls_expires: Option<DateTime<Utc>>,
+    /// The rate limit headers the directory sent, if any.
+    pub(crate) rate_limit: BTreeMap<String, String>,
+}
+
+/// The outcome of a single request.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Check {
+    pub(crate) name: &'static str,
+    pub(crate) latency_ms: Option<u64>,
+    pub(crate) status: Option<u16>,
+    pub(crate) error: Option<String>,
+}
+
+impl DirectoryStatus {
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+}
+
+/// Times resolving `did`, fetching its audit log, and reading the recent export from
+/// the directory at `base_url`.
+///
+/// Each request that takes longer than `timeout` is recorded as failed.
+pub(crate) async fn probe(
+    base_url: &str,
+    did: &Did,
+    timeout: Duration,
+) -> Result<DirectoryStatus, Error> {
+    let base_url = base_url.trim_end_matches('/');
+    let client = Client::builder()
+        .tls_info(true)
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()
+        .map_err(Error::PlcDirectoryRequestFailed)?;
+
+    let after = (Utc::now() - EXPORT_WINDOW).to_rfc3339_opts(SecondsFormat::Millis, true);
+    let requests = [
+        ("resolve", did.as_str().to_string()),
+        ("log", format!("{}/log/audit", did.as_str())),
+        ("export", format!("export?count=1000&after={after}")),
+    ];
+
+    let mut status = DirectoryStatus {
+        url: base_url.into(),
+        checks: vec![],
+        latest_export: None,
+        tls_expires: None,
+        rate_limit: BTreeMap::new(),
+    };
+    for (name, path) in requests {
+        let start = Instant::now();
+        let mut check = Check {
+            name,
+            latency_ms: None,
+            status: None,
+            error: None,
+        };
+
+        match client.get(format!("{base_url}/{path}")).send().await {
+            Ok(resp) => {
+                check.status = Some(resp.status().as_u16());
+                if status.tls_expires.is_none() {
+                    status.tls_expires = resp
+                        .extensions()
+                        .get::<TlsInfo>()
+                        .and_then(|info| info.peer_certificate())
+                        .and_then(certificate_expiry);
+                }
+                for (header, value) in resp.headers() {
+                    if header.as_str().contains("ratelimit") {
+                        if let Ok(value) = value.to_str() {
+                            status
+                                .rate_limit
+                                .insert(header.as_str().into(), value.into());
+                        }
+                    }
+                }
+
+                if !resp.status().is_success() {
+                    check.error = Some(format!("HTTP {}", resp.status()));
+                }
+                match resp.text().await {
+                    Ok(body) => {
+                        check.latency_ms = Some(start.elapsed().as_millis() as u64);
+                        if name == "export" && check.error.is_none() {
+                            // The export is in creation order.
+                            status.latest_export = body
+                                .lines()
+                                .rev()
+                                .find_map(|line| serde_json::from_str::<LogEntry>(line).ok())
+                                .map(|entry| entry.created_at().clone());
+                        }
+                    }
+                    Err(e) => check.error = Some(request_error(&e, timeout)),
+                }
+            }
+            Err(e) => check.error = Some(request_error(&e, timeout)),
+        }
+        status.checks.push(check);
+    }
+
+    Ok(status)
+}
+
+fn request_error(e: &reqwest::Error, timeout: Duration) -> String {
+    if e.is_timeout() {
+        format!("timed out after {}s", timeout.as_secs())
+    } else {
+        e.to_string()
+    }
+}
+
+/// Returns the end of the validity period of a DER-encoded X.509 certificate.
+fn certificate_expiry(cert: &[u8]) -> Option<DateTime<Utc>> {
+    let mut reader = SliceReader::new(cert).ok()?;
+    let not_after = reader
+        .sequence(|cert| {
+            let not_after = cert.sequence(|tbs| {
+                // Skip the optional version, then the serial number, signature
+                // algorithm, and issuer.
+                if tbs.peek_tag()?
+                    == (Tag::ContextSpecific {
+                        constructed: true,
+                        number: TagNumber::N0,
+                    })
+                {
+                    tbs.tlv_bytes()?;
+                }
+                for _ in 0..3 {
+                    tbs.tlv_bytes()?;
+                }
+                let not_after = tbs.sequence(|validity| {
+                    validity.tlv_bytes()?;
+                    match validity.peek_tag()? {
+                        Tag::UtcTime => UtcTime::decode(validity).map(|t| t.to_unix_duration()),
+                        _ => GeneralizedTime::decode(validity).map(|t| t.to_unix_duration()),
+                    }
+                })?;
+                while !tbs.is_finished() {
+                    tbs.tlv_bytes()?;
+                }
+                Ok(not_after)
+            })?;
+            while !cert.is_finished() {
+                cert.tlv_bytes()?;
+            }
+            Ok(not_after)
+        })
+        .ok()?;
+    DateTime::from_timestamp(not_after.as_secs().try_into().ok()?, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64ct::{Base64, Encoding};
+
+    use super::certificate_expiry;
+
+    #[test]
+    fn certificate_expiry_is_parsed() {
+        // A self-signed certificate valid until 2034-01-01.
+        let cert = Base64::decode_vec(
+            "MIIBezCCASGgAwIBAgIUGoZj5pGSURi5ot+UmPTxzL+13+AwCgYIKoZIzj0EAwIwEzERMA8GA1UEAwwIcGxj\
+             LnRlc3QwHhcNMjQwMTAxMDAwMDAwWhcNMzQwMTAxMDAwMDAwWjATMREwDwYDVQQDDAhwbGMudGVzdDBZMBMG\
+             ByqGSM49AgEGCCqGSM49AwEHA0IABAqmtI+k0cLhc4/iVELoFx1nytfCb8jQ9Ii1lKlnXzoCaMQyXZYkMAPt\
+             ijpgzzcVBDqeqHsrS3smZ2rFRW4UnpqjUzBRMB0GA1UdDgQWBBQyMt1NHvt8B+JlXkvBtlLv+kPkmTAfBgNV\
+             HSMEGDAWgBQyMt1NHvt8B+JlXkvBtlLv+kPkmTAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUC\
+             IFX+kVT9V1auotr/JLYTtHRcAO/KZzMqDhPLKROWuv7NAiEA3YN+eQr7OJmCx5guO51zcD0l0q5VlhPCpN+1\
+             q/z+Ahw=",
+        )
+        .unwrap();
+        assert_eq!(
+            certificate_expiry(&cert).unwrap().to_rfc3339(),
+            "2034-01-01T00:00:00+00:00",
+        );
+
+        assert!(certificate_expiry(&cert[..100]).is_none());
+    }
+}
@@ -0,0 +1,28 @@
+use chrono::TimeDelta;
+
+/// How many hours the recovery window of DID PLC v0.1 lasts.
+pub(crate) const V0_1_RECOVERY_WINDOW_HOURS: i64 = 72;
+
+/// Parameters of the DID PLC specification that may differ between deployments.
+///
+/// Test networks, for example, may want a much shorter recovery window than the
+/// public directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SpecProfile {
+    /// How long after an operation was created a higher-authority rotation key can
+    /// nullify it.
+    pub(crate) recovery_window: TimeDelta,
+}
+
+impl SpecProfile {
+    /// The profile for DID PLC v0.1, as enforced by plc.directory.
+    pub(crate) const V0_1: Self = Self {
+        recovery_window: TimeDelta::hours(V0_1_RECOVERY_WINDOW_HOURS),
+    };
+}
+
+impl Default for SpecProfile {
+    fn default() -> Self {
+        Self::V0_1
+    }
+}
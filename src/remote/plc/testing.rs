@@ -171,7 +171,7 @@ impl TestLog {
                 match &entry.operation.content {
                     Operation::Change(op) => op.prev.clone(),
                     Operation::Tombstone(op) => Some(op.prev.clone()),
-                    Operation::LegacyCreate(_) => None,
+                    Operation::LegacyCreate(_) | Operation::Unknown(_) => None,
                 },
             )
         };
@@ -180,6 +180,7 @@ impl TestLog {
             Operation::Change(op) => op.prev = prev,
             Operation::Tombstone(op) => op.prev = prev.expect("should swap compatible operations"),
             Operation::LegacyCreate(_) => assert!(prev.is_none()),
+            Operation::Unknown(_) => unreachable!("test logs don't contain unknown operations"),
         };
 
         let (a_cid, a_prev) = get_links(&self.entries[a]);
@@ -277,6 +278,15 @@ impl TestLog {
             .clone()
     }
 
+    /// Returns the raw private key for the given rotation key of the current state.
+    pub(crate) fn rotation_key(&self, authority: usize) -> Vec<u8> {
+        self.state_updates
+            .last()
+            .map_or(&self.initial_state, |(_, state)| state)
+            .rotation[authority]
+            .export()
+    }
+
     /// Returns the audit log corresponding to the current state.
     pub(crate) fn audit_log(&self) -> AuditLog {
         AuditLog::new(self.did.clone(), self.entries.clone())
@@ -454,10 +464,14 @@ impl Update {
                         Operation::Change(op) => break op.data.clone(),
                         Operation::Tombstone(op) => prev = op.prev.clone(),
                         Operation::LegacyCreate(op) => break op.clone().into_plc_data(),
+                        Operation::Unknown(_) => {
+                            unreachable!("test logs don't contain unknown operations")
+                        }
                     }
                 }
             }
             Operation::LegacyCreate(op) => op.clone().into_plc_data(),
+            Operation::Unknown(_) => unreachable!("test logs don't contain unknown operations"),
         };
 
         if !(self.new_rotation_keys.is_empty()
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use base64ct::Encoding;
+
+use crate::{
+    error::Error,
+    keystore::{KeyAlgorithm, SecretKey},
+    remote::plc::{
+        mock::MockDirectory, testing::TestLog, Directory, OperationsLog, PlcDirectoryClient,
+        RetryPolicy, SignedOperation, UnsignedOperation,
+    },
+};
+
+#[test]
+fn genesis_did_preview() {
+    for mut log in [TestLog::with_genesis(), TestLog::with_legacy_genesis()] {
+        let did = log.did();
+        let genesis = log.remove(0).operation;
+        assert_eq!(genesis.derived_did(), Some(did));
+        assert_eq!(genesis.genesis_signer(), Some(1));
+    }
+}
+
+#[test]
+fn non_genesis_did_preview() {
+    let mut log =
+        TestLog::with_genesis().apply_update(|update| update.change_handle("bob.example.com"));
+    let update = log.remove(1).operation;
+    assert_eq!(update.derived_did(), None);
+    assert_eq!(update.genesis_signer(), None);
+}
+
+#[test]
+fn strict_dag_cbor_decode() {
+    let mut log =
+        TestLog::with_genesis().apply_update(|update| update.change_handle("bob.example.com"));
+    for op in [log.remove(1).operation, log.remove(0).operation] {
+        let bytes = op.signed_bytes();
+        let decoded = SignedOperation::from_dag_cbor_strict(&bytes).unwrap();
+        assert_eq!(decoded.cid(), op.cid());
+
+        // Encode the signature's length non-minimally. This is valid CBOR, but not
+        // canonical DAG-CBOR.
+        let sig = op.sig.as_bytes();
+        let pos = bytes
+            .windows(sig.len())
+            .position(|w| w == sig)
+            .expect("present");
+        assert_eq!(&bytes[pos - 2..pos], &[0x78, sig.len() as u8]);
+        let mut non_canonical = bytes[..pos - 2].to_vec();
+        non_canonical.extend_from_slice(&[0x79, 0x00, sig.len() as u8]);
+        non_canonical.extend_from_slice(&bytes[pos..]);
+        assert!(SignedOperation::from_dag_cbor_strict(&non_canonical).is_err());
+
+        // Trailing data is rejected.
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(SignedOperation::from_dag_cbor_strict(&trailing).is_err());
+    }
+}
+
+#[test]
+fn unknown_operation_round_trip() {
+    let json = serde_json::json!({
+        "type": "plc_future_op",
+        "prev": "bafyreid6awsb6lzc54zxaq2roijyvpbjp5d6mii2xyztn55yli7htyjgqy",
+        "newField": ["a", "b"],
+        "sig": "c2lnbmF0dXJl",
+    });
+
+    let op: SignedOperation = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(op.content.unknown_type(), Some("plc_future_op"));
+    assert_eq!(op.kinds(None), vec![]);
+    assert_eq!(serde_json::to_value(&op).unwrap(), json);
+
+    // The exact encoding is preserved, so the CID is unaffected.
+    let bytes = op.signed_bytes();
+    let decoded = SignedOperation::from_dag_cbor_strict(&bytes).unwrap();
+    assert_eq!(decoded.cid(), op.cid());
+
+    // Known operation types are still parsed strictly.
+    let mut invalid = json;
+    invalid["type"] = "plc_operation".into();
+    assert!(serde_json::from_value::<SignedOperation>(invalid).is_err());
+}
+
+#[test]
+fn retry_backoff() {
+    let policy = RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_millis(100),
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(400));
+}
+
+#[test]
+fn directory_base_url() {
+    assert_eq!(
+        PlcDirectoryClient::new("https://mirror.example.com/").base_url,
+        "https://mirror.example.com",
+    );
+    assert_eq!(
+        PlcDirectoryClient::default().base_url,
+        "https://plc.directory",
+    );
+}
+
+#[test]
+fn submit_plc_operation_payload() {
+    let mut log = TestLog::with_genesis();
+    let op = log.remove(0).operation;
+    let payload = op.to_submit_plc_operation();
+    assert_eq!(payload["operation"], serde_json::to_value(&op).unwrap());
+    assert_eq!(payload["operation"]["type"], "plc_operation");
+}
+
+#[test]
+fn unsigned_operation_signing() {
+    let mut log = TestLog::with_genesis();
+    let genesis = log.remove(0).operation;
+    let mut data = genesis.data().unwrap();
+    data.set_handle("new.example.com");
+
+    for algorithm in [KeyAlgorithm::P256, KeyAlgorithm::K256] {
+        let key = SecretKey::generate(algorithm);
+        let op = UnsignedOperation::update(genesis.cid(), data.clone()).sign(&key);
+        assert_eq!(op.prev(), Some(&genesis.cid()));
+        assert_eq!(op.data().as_ref(), Some(&data));
+
+        let sig = base64ct::Base64UrlUnpadded::decode_vec(&op.sig).unwrap();
+        assert!(
+            atrium_crypto::verify::verify_signature(&key.did(), &op.unsigned_bytes(), &sig).is_ok()
+        );
+    }
+}
+
+#[test]
+fn unsigned_operation_templates() {
+    let head = TestLog::with_genesis().cid_for(0);
+    let template = serde_json::json!({
+        "type": "plc_operation",
+        "rotationKeys": [],
+        "verificationMethods": {},
+        "alsoKnownAs": [],
+        "services": {},
+    });
+    let op = UnsignedOperation::from_template(template, &head).unwrap();
+    assert_eq!(op.to_json()["prev"], serde_json::to_value(&head).unwrap());
+
+    let tombstone = UnsignedOperation::tombstone(head.clone()).to_json();
+    assert_eq!(tombstone["type"], "plc_tombstone");
+    assert_eq!(tombstone["prev"], serde_json::to_value(&head).unwrap());
+
+    let legacy = serde_json::json!({
+        "type": "create",
+        "signingKey": "did:key:zQ3shP5gBEz9Z7xYqHVGWsoSQeoxbDBkZzXbTfvXKqMX9SEqn",
+        "recoveryKey": "did:key:zQ3shP5gBEz9Z7xYqHVGWsoSQeoxbDBkZzXbTfvXKqMX9SEqn",
+        "handle": "example.com",
+        "service": "https://pds.example.com",
+        "prev": null,
+    });
+    assert!(matches!(
+        UnsignedOperation::from_template(legacy, &head),
+        Err(Error::UnsupportedOperationTemplate),
+    ));
+}
+
+#[tokio::test]
+async fn tombstoned_dids() {
+    let active = TestLog::with_genesis();
+    let tombstoned = TestLog::with_genesis().apply_tombstone(|t| t);
+    let directory = MockDirectory::default()
+        .with_log(&active)
+        .with_log(&tombstoned);
+
+    assert!(directory.resolve(&active.did()).await.is_ok());
+    assert!(matches!(
+        directory.resolve(&tombstoned.did()).await,
+        Err(Error::DidTombstoned(did)) if did == tombstoned.did().as_str(),
+    ));
+
+    let log = OperationsLog::from_audit_log(&tombstoned.audit_log()).unwrap();
+    assert_eq!(
+        log.tombstoned.as_ref(),
+        Some(&tombstoned.audit_log().entries()[1].created_at),
+    );
+    assert!(OperationsLog::from_audit_log(&active.audit_log())
+        .unwrap()
+        .tombstoned
+        .is_none());
+}
@@ -1,6 +1,11 @@
+use std::env;
+
+use age::secrecy::SecretString;
 use atrium_api::types::string::Did;
 use sha2::{Digest, Sha256};
 
+use crate::error::Error;
+
 pub(crate) fn derive_did(signed_genesis_op: &[u8]) -> Did {
     Did::new(format!(
         "did:plc:{}",
@@ -11,3 +16,38 @@ pub(crate) fn derive_did(signed_genesis_op: &[u8]) -> Did {
     ))
     .expect("valid")
 }
+
+/// Reads a passphrase from the environment variable `var`, or prompts for it.
+///
+/// If `confirm` is set, the passphrase is new: it is prompted for twice, and must not
+/// be empty.
+pub(crate) fn passphrase(
+    purpose: &str,
+    var: &'static str,
+    non_interactive: bool,
+    confirm: bool,
+) -> Result<SecretString, Error> {
+    if let Ok(passphrase) = env::var(var) {
+        if confirm && passphrase.is_empty() {
+            return Err(Error::PassphraseEmpty);
+        }
+        return Ok(passphrase.into());
+    }
+    if non_interactive {
+        return Err(Error::PassphraseRequired(var));
+    }
+
+    let passphrase = rpassword::prompt_password(format!("{purpose} passphrase: "))
+        .map_err(Error::PassphraseReadFailed)?;
+    if confirm {
+        if passphrase.is_empty() {
+            return Err(Error::PassphraseEmpty);
+        }
+        let again = rpassword::prompt_password("Confirm passphrase: ")
+            .map_err(Error::PassphraseReadFailed)?;
+        if again != passphrase {
+            return Err(Error::PassphraseMismatch);
+        }
+    }
+    Ok(passphrase.into())
+}
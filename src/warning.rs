@@ -0,0 +1,174 @@
+//! Warnings collected while a command runs, and reported together.
+
+use std::fmt;
+
+use crate::{output::advisory, pins::PinChange};
+
+/// Something the user should know about that doesn't stop the command.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Warning {
+    /// The user's PDS holds the rotation key that overrides all others.
+    PdsHoldsTopRotationKey,
+    PdsRecommendedNoSigningKey,
+    PdsRecommendedInvalidSigningKey(String),
+    PdsRecommendedInvalidRotationKey(usize, String),
+    PinnedKeysChanged(String, PinChange),
+    /// A temporary rotation key (for a DID) has expired but is still authorized.
+    TemporaryKeyExpired {
+        did: String,
+        key: String,
+        expired: String,
+    },
+    /// A genesis operation that the directory will reject.
+    GenesisUnsigned,
+    /// Removing this rotation key would leave only keys held by the user's PDS.
+    LastSelfControlledRotationKey(String),
+    /// An operation (by CID) was submitted, but recording it in the journal failed.
+    SubmissionNotJournaled(String, String),
+    /// The user's PDS couldn't be asked which keys it holds.
+    PdsServerKeysUnknown(String),
+}
+
+impl Warning {
+    /// Returns a short stable code for the warning, for automation.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Warning::PdsHoldsTopRotationKey => "W001",
+            Warning::PdsRecommendedNoSigningKey => "W002",
+            Warning::PdsRecommendedInvalidSigningKey(..) => "W003",
+            Warning::PdsRecommendedInvalidRotationKey(..) => "W004",
+            Warning::PinnedKeysChanged(..) => "W005",
+            Warning::TemporaryKeyExpired { .. } => "W006",
+            Warning::GenesisUnsigned => "W007",
+            Warning::LastSelfControlledRotationKey(..) => "W008",
+            Warning::SubmissionNotJournaled(..) => "W009",
+            Warning::PdsServerKeysUnknown(..) => "W010",
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Warning::PdsHoldsTopRotationKey => "pds-holds-top-rotation-key",
+            Warning::PdsRecommendedNoSigningKey => "pds-recommended-no-signing-key",
+            Warning::PdsRecommendedInvalidSigningKey(..) => "pds-recommended-invalid-signing-key",
+            Warning::PdsRecommendedInvalidRotationKey(..) => "pds-recommended-invalid-rotation-key",
+            Warning::PinnedKeysChanged(..) => "pinned-keys-changed",
+            Warning::TemporaryKeyExpired { .. } => "temporary-key-expired",
+            Warning::GenesisUnsigned => "genesis-unsigned",
+            Warning::LastSelfControlledRotationKey(..) => "last-self-controlled-rotation-key",
+            Warning::SubmissionNotJournaled(..) => "submission-not-journaled",
+            Warning::PdsServerKeysUnknown(..) => "pds-server-keys-unknown",
+        }
+    }
+
+    /// Returns further details, and what to do about the warning.
+    fn details(&self) -> Vec<String> {
+        match self {
+            Warning::PdsHoldsTopRotationKey => vec![
+                "Add a rotation key you control with `plc keys add-rotation`; it is inserted at [0] by default.".into(),
+            ],
+            Warning::PinnedKeysChanged(did, change) => {
+                let mut details = vec![];
+                details.extend(change.added.iter().map(|key| format!("- Added: {key}")));
+                details.extend(change.removed.iter().map(|key| format!("- Removed: {key}")));
+                if change.reordered {
+                    details.push("- Their priority order changed".into());
+                }
+                details.push(format!(
+                    "If you expected this, run `plc pin {did}` to accept the new keys."
+                ));
+                details
+            }
+            Warning::TemporaryKeyExpired { did, key, .. } => {
+                vec![format!("Run `plc keys remove-rotation {did} {key}` to remove it.")]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::PdsHoldsTopRotationKey => write!(f, "Your PDS holds the highest-priority rotation key, so it can override changes signed by any of your other keys"),
+            Warning::PdsRecommendedNoSigningKey => write!(f, "PDS did not recommend a signing key"),
+            Warning::PdsRecommendedInvalidSigningKey(e) => write!(f, "PDS recommended an invalid signing key: {e}"),
+            Warning::PdsRecommendedInvalidRotationKey(i, e) => write!(f, "PDS recommended an invalid rotation key at position {i}: {e}"),
+            Warning::PinnedKeysChanged(did, _) => write!(f, "The rotation keys for {did} have changed since they were pinned"),
+            Warning::TemporaryKeyExpired { did, key, expired } => write!(f, "Temporary rotation key {key} for {did} expired {expired}"),
+            Warning::GenesisUnsigned => write!(f, "Operation is not signed by any of its rotation keys; plc.directory will reject it"),
+            Warning::LastSelfControlledRotationKey(key) => write!(f, "{key} is the only rotation key not held by your PDS; without it, you can't recover your identity if your PDS misbehaves"),
+            Warning::SubmissionNotJournaled(cid, e) => write!(f, "Operation {cid} was submitted, but could not be recorded in the journal: {e}"),
+            Warning::PdsServerKeysUnknown(e) => write!(f, "Could not ask the PDS which rotation keys it holds, so none are assumed to be: {e}"),
+        }
+    }
+}
+
+/// The warnings raised so far, each reported once.
+#[derive(Debug, Default)]
+pub(crate) struct Warnings {
+    pending: Vec<Warning>,
+    reported: Vec<Warning>,
+}
+
+impl Warnings {
+    /// Adds a warning, unless it has already been raised.
+    pub(crate) fn push(&mut self, warning: Warning) {
+        if !self.pending.contains(&warning) && !self.reported.contains(&warning) {
+            self.pending.push(warning);
+        }
+    }
+
+    /// The number of distinct warnings raised.
+    pub(crate) fn count(&self) -> usize {
+        self.pending.len() + self.reported.len()
+    }
+
+    /// Prints the warnings raised since the last report to stderr, as JSON lines if
+    /// `json` is set.
+    pub(crate) fn report(&mut self, json: bool) {
+        for warning in self.pending.drain(..) {
+            if json {
+                eprintln!(
+                    "{}",
+                    serde_json::json!({
+                        "warning": warning.code(),
+                        "name": warning.name(),
+                        "message": warning.to_string(),
+                        "details": warning.details(),
+                    })
+                );
+            } else {
+                eprintln!(
+                    "{} {warning}",
+                    advisory(format!("Warning {} ({}):", warning.code(), warning.name())),
+                );
+                for line in warning.details() {
+                    eprintln!("  {line}");
+                }
+            }
+            self.reported.push(warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Warning, Warnings};
+
+    #[test]
+    fn warnings_are_reported_once() {
+        let mut warnings = Warnings::default();
+        warnings.push(Warning::PdsHoldsTopRotationKey);
+        warnings.push(Warning::PdsRecommendedInvalidRotationKey(1, "bad".into()));
+        warnings.push(Warning::PdsHoldsTopRotationKey);
+        assert_eq!(warnings.count(), 2);
+
+        warnings.report(true);
+        assert!(warnings.pending.is_empty());
+        warnings.push(Warning::PdsRecommendedInvalidRotationKey(1, "bad".into()));
+        warnings.push(Warning::PdsRecommendedInvalidRotationKey(2, "bad".into()));
+        assert_eq!(warnings.count(), 3);
+        assert_eq!(warnings.pending.len(), 1);
+    }
+}
@@ -36,9 +36,14 @@ impl Plc {
             cmd.env("LOCALAPPDATA", self.config_dir.path().unwrap())
         }
 
+        // Keep the cache and data directories (audit logs, seen history) out of the
+        // user's home too, so runs can't affect each other.
         #[cfg(any(unix, target_os = "redox"))]
         {
-            cmd.env("XDG_CONFIG_HOME", self.config_dir.path().unwrap())
+            let dir = self.config_dir.path().unwrap();
+            cmd.env("XDG_CONFIG_HOME", dir)
+                .env("XDG_CACHE_HOME", dir.join("cache"))
+                .env("XDG_DATA_HOME", dir.join("data"))
         }
     }
 }